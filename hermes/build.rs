@@ -11,6 +11,23 @@ fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
     let out_var = env::var("OUT_DIR").unwrap();
 
+    // Generate the gRPC price service served by `api::grpc` from its protobuf definition.
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/price_service.proto"], &["proto"])
+        .expect("failed to compile the price service protobuf definitions");
+
+    // Generate the Wormhole spy client used by `network::spy`. The server is only used by the mock
+    // spy in its tests.
+    tonic_build::configure()
+        .compile(&["proto/spy/v1/spy.proto"], &["proto"])
+        .expect("failed to compile the Wormhole spy protobuf definitions");
+
+    // `tonic_build` only asks Cargo to rerun this script when the protobuf definitions change, so
+    // the sources of the Go library have to be listed as well.
+    println!("cargo:rerun-if-changed=src/network/p2p.go");
+    println!("cargo:rerun-if-changed=go.mod");
+
     // Download the Wormhole repository at a certain tag, which we need to access the protobuf definitions
     // for Wormhole P2P message types.
     //
@@ -0,0 +1,126 @@
+//! Operator endpoints for inspecting the internals of Hermes.
+//!
+//! They are served next to the metrics on the private metrics address, not with the public API.
+
+use {
+    crate::{
+        aggregate::{
+            quarantine::{
+                QuarantineStatus,
+                QuarantinedSlot,
+            },
+            Slot,
+            UnixTimestamp,
+        },
+        api::{
+            SubscriberKind,
+            SubscriberQueue,
+        },
+        state::State,
+    },
+    axum::{
+        extract::State as AxumState,
+        routing::get,
+        Json,
+        Router,
+    },
+    serde::Serialize,
+    std::sync::Arc,
+};
+
+pub fn routes() -> Router<Arc<State>> {
+    Router::new()
+        .route("/admin/quarantine", get(quarantine))
+        .route("/admin/subscribers", get(subscribers))
+}
+
+#[derive(Debug, Serialize)]
+struct QuarantineCandidateResponse {
+    /// The Merkle root of the candidate messages in hex, absent if there are no messages.
+    root:         Option<String>,
+    num_messages: usize,
+    received_at:  UnixTimestamp,
+}
+
+#[derive(Debug, Serialize)]
+struct QuarantinedSlotResponse {
+    slot:           Slot,
+    status:         QuarantineStatus,
+    expected_root:  Option<String>,
+    candidates:     Vec<QuarantineCandidateResponse>,
+    quarantined_at: UnixTimestamp,
+}
+
+impl From<QuarantinedSlot> for QuarantinedSlotResponse {
+    fn from(quarantined_slot: QuarantinedSlot) -> Self {
+        Self {
+            slot:           quarantined_slot.slot,
+            status:         quarantined_slot.status,
+            expected_root:  quarantined_slot.expected_root.map(hex::encode),
+            candidates:     quarantined_slot
+                .candidates
+                .into_iter()
+                .map(|candidate| QuarantineCandidateResponse {
+                    root:         candidate.root.map(hex::encode),
+                    num_messages: candidate.accumulator_messages.raw_messages.len(),
+                    received_at:  candidate.received_at,
+                })
+                .collect(),
+            quarantined_at: quarantined_slot.quarantined_at,
+        }
+    }
+}
+
+/// List the slots whose accumulator messages are or have been quarantined, oldest first.
+async fn quarantine(AxumState(state): AxumState<Arc<State>>) -> Json<Vec<QuarantinedSlotResponse>> {
+    Json(
+        state
+            .quarantine
+            .list()
+            .await
+            .into_iter()
+            .map(QuarantinedSlotResponse::from)
+            .collect(),
+    )
+}
+
+#[derive(Debug, Serialize)]
+struct SubscriberQueueResponse {
+    id:             usize,
+    kind:           SubscriberKind,
+    /// Number of notifications waiting to be processed by the subscriber.
+    queue_depth:    usize,
+    queue_capacity: usize,
+    /// Number of notifications dropped because the queue was full since the subscriber last
+    /// caught up.
+    dropped_slots:  u64,
+    /// For how long the queue has been full, absent if the subscriber keeps up.
+    lagging_for_ms: Option<u128>,
+}
+
+impl From<SubscriberQueue> for SubscriberQueueResponse {
+    fn from(queue: SubscriberQueue) -> Self {
+        Self {
+            id:             queue.id,
+            kind:           queue.kind,
+            queue_depth:    queue.depth,
+            queue_capacity: queue.capacity,
+            dropped_slots:  queue.dropped_slots,
+            lagging_for_ms: queue.lagging_for.map(|lagging_for| lagging_for.as_millis()),
+        }
+    }
+}
+
+/// List the notification queues of the WebSocket and Server-Sent Events subscribers.
+async fn subscribers(
+    AxumState(state): AxumState<Arc<State>>,
+) -> Json<Vec<SubscriberQueueResponse>> {
+    Json(
+        state
+            .ws
+            .queues()
+            .into_iter()
+            .map(SubscriberQueueResponse::from)
+            .collect(),
+    )
+}
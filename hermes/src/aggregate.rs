@@ -1,30 +1,44 @@
 #[cfg(test)]
 use mock_instant::{
-    Instant,
     SystemTime,
     UNIX_EPOCH,
 };
 #[cfg(not(test))]
 use std::time::{
-    Instant,
     SystemTime,
     UNIX_EPOCH,
 };
 use {
-    self::wormhole_merkle::{
-        construct_message_states_proofs,
-        construct_update_data,
-        store_wormhole_merkle_verified_message,
-        WormholeMerkleMessageProof,
-        WormholeMerkleState,
+    self::{
+        quarantine::{
+            accumulator_messages_root,
+            accumulator_messages_tree,
+            merkle_tree_root,
+            QuarantineAlert,
+        },
+        wormhole_merkle::{
+            construct_message_states_proofs,
+            construct_multiproof_update_data,
+            construct_update_data,
+            store_wormhole_merkle_verified_message,
+            WormholeMerkleMessageProof,
+            WormholeMerkleState,
+        },
     },
     crate::{
+        metrics::{
+            CompletedSlotLabels,
+            QuarantineAlertLabels,
+            SlotOrder,
+        },
         state::{
             benchmarks::Benchmarks,
             cache::{
                 AggregateCache,
                 MessageState,
                 MessageStateFilter,
+                MessageStateKey,
+                MessageStateTime,
             },
             State,
         },
@@ -34,7 +48,10 @@ use {
         anyhow,
         Result,
     },
-    borsh::BorshDeserialize,
+    borsh::{
+        BorshDeserialize,
+        BorshSerialize,
+    },
     byteorder::BigEndian,
     pyth_sdk::{
         Price,
@@ -42,9 +59,12 @@ use {
         PriceIdentifier,
     },
     pythnet_sdk::{
+        accumulators::merkle::MerkleTree,
+        hashers::keccak256_160::Keccak160,
         messages::{
             Message,
             MessageType,
+            TwapMessage,
         },
         wire::{
             from_slice,
@@ -56,11 +76,14 @@ use {
     },
     std::{
         collections::HashSet,
+        ops::Bound,
         time::Duration,
     },
     wormhole_sdk::Vaa,
 };
 
+pub mod feed_health;
+pub mod quarantine;
 pub mod wormhole_merkle;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -106,8 +129,9 @@ pub struct AggregateState {
     /// order.
     pub latest_completed_slot: Option<Slot>,
 
-    /// Time of the latest completed update. This is used for the health probes.
-    pub latest_completed_update_at: Option<Instant>,
+    /// Time of the latest completed update since the unix epoch, as told by `State::clock`. This
+    /// is used for the health probes.
+    pub latest_completed_update_at: Option<Duration>,
 
     /// The latest observed slot among different Aggregate updates. This is used for the health
     /// probes.
@@ -130,7 +154,7 @@ impl AggregateState {
 /// the following struct. We cannot directly have messages as Vec<Messages>
 /// because they are serialized using big-endian byte order and Borsh
 /// uses little-endian byte order.
-#[derive(Clone, PartialEq, Debug, BorshDeserialize)]
+#[derive(Clone, PartialEq, Debug, BorshDeserialize, BorshSerialize)]
 pub struct AccumulatorMessages {
     pub magic:        [u8; 4],
     pub slot:         u64,
@@ -144,7 +168,7 @@ impl AccumulatorMessages {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
 pub enum Update {
     Vaa(VaaBytes),
     AccumulatorMessages(AccumulatorMessages),
@@ -165,6 +189,30 @@ pub struct PriceFeedsWithUpdateData {
     pub update_data: Vec<Vec<u8>>,
 }
 
+/// A time-weighted average price computed from the two TWAP messages that bound a time window.
+///
+/// The accumulator publishes running sums of the price and confidence over slots, so the average
+/// over a window is the difference of the sums at both ends divided by the number of slots in
+/// between. Both messages are returned with their update data so the computation can be verified
+/// on-chain.
+#[derive(Debug, PartialEq)]
+pub struct TwapUpdate {
+    pub price_id:         PriceIdentifier,
+    pub start:            TwapMessage,
+    pub end:              TwapMessage,
+    pub twap:             i64,
+    pub conf:             u64,
+    pub exponent:         i32,
+    /// Fraction of the slots in the window in which the price was not updated.
+    pub down_slots_ratio: f64,
+    pub start_slot:       Slot,
+    pub end_slot:         Slot,
+    pub received_at:      UnixTimestamp,
+    /// Update data for the start and end messages. They are always in different slots and hence
+    /// signed by different VAAs, so there is one update data per message.
+    pub update_data:      Vec<Vec<u8>>,
+}
+
 const READINESS_STALENESS_THRESHOLD: Duration = Duration::from_secs(30);
 
 /// The maximum allowed slot lag between the latest observed slot and the latest completed slot.
@@ -174,6 +222,25 @@ const READINESS_MAX_ALLOWED_SLOT_LAG: Slot = 10;
 /// Stores the update data in the store
 #[tracing::instrument(skip(state, update))]
 pub async fn store_update(state: &State, update: Update) -> Result<()> {
+    let received_at = SystemTime::now().duration_since(UNIX_EPOCH)?;
+
+    if let Some(recorder) = &state.recorder {
+        if let Err(e) = recorder.record(received_at, &update).await {
+            tracing::error!(error = ?e, "Failed to record update.");
+        }
+    }
+
+    store_update_received_at(state, update, received_at).await
+}
+
+/// Stores the update data in the store as if it had been received at `received_at`, the time since
+/// the unix epoch. Replays use it to keep the receive times of the recording.
+#[tracing::instrument(skip(state, update))]
+pub async fn store_update_received_at(
+    state: &State,
+    update: Update,
+    received_at: Duration,
+) -> Result<()> {
     // The slot that the update is originating from. It should be available
     // in all the updates.
     let slot = match update {
@@ -199,10 +266,11 @@ pub async fn store_update(state: &State, update: Update) -> Result<()> {
         Update::AccumulatorMessages(accumulator_messages) => {
             let slot = accumulator_messages.slot;
             tracing::info!(slot = slot, "Storing Accumulator Messages.");
+            state.metrics.accumulator_updates.inc();
 
-            state
-                .store_accumulator_messages(accumulator_messages)
-                .await?;
+            if !store_accumulator_messages_or_quarantine(state, accumulator_messages).await? {
+                return Ok(());
+            }
             slot
         }
     };
@@ -214,6 +282,7 @@ pub async fn store_update(state: &State, update: Update) -> Result<()> {
             .latest_observed_slot
             .map(|latest| latest.max(slot))
             .or(Some(slot));
+        update_slot_lag_metric(state, &aggregate_state);
     }
 
     let accumulator_messages = state.fetch_accumulator_messages(slot).await?;
@@ -227,23 +296,62 @@ pub async fn store_update(state: &State, update: Update) -> Result<()> {
             _ => return Ok(()),
         };
 
+    // The accumulator messages are not signed, so a faulty Pythnet node can deliver messages that
+    // do not match the signed root. Such messages are quarantined instead, and the slot completes
+    // only once a matching copy has been received.
+    // The tree is kept to prove the message states with.
+    let expected_root = wormhole_merkle_state.root.root;
+    let merkle_tree = accumulator_messages_tree(&accumulator_messages);
+    let (accumulator_messages, merkle_tree) =
+        if merkle_tree.as_ref().and_then(merkle_tree_root) == Some(expected_root) {
+            (accumulator_messages, merkle_tree)
+        } else {
+            match state.quarantine.find_candidate(slot, expected_root).await {
+                Some(candidate) => {
+                    state.store_accumulator_messages(candidate.clone()).await?;
+                    let merkle_tree = accumulator_messages_tree(&candidate);
+                    (candidate, merkle_tree)
+                }
+                None => {
+                    let alert = state
+                        .quarantine
+                        .add_candidates(slot, Some(expected_root), vec![accumulator_messages])
+                        .await;
+                    publish_quarantine_alert(state, alert);
+                    return Ok(());
+                }
+            }
+        };
+
+    if let Some(alert) = state.quarantine.resolve(slot, expected_root).await {
+        publish_quarantine_alert(state, alert);
+    }
+
     tracing::info!(slot = wormhole_merkle_state.root.slot, "Completed Update.");
 
     // Once the accumulator reaches a complete state for a specific slot
     // we can build the message states
-    build_message_states(state, accumulator_messages, wormhole_merkle_state).await?;
+    build_message_states(
+        state,
+        accumulator_messages,
+        merkle_tree,
+        wormhole_merkle_state,
+        received_at.as_secs() as _,
+    )
+    .await?;
 
     // Update the aggregate state
     let mut aggregate_state = state.aggregate_state.write().await;
 
     // Check if the update is new or out of order
-    match aggregate_state.latest_completed_slot {
+    let order = match aggregate_state.latest_completed_slot {
         None => {
             aggregate_state.latest_completed_slot.replace(slot);
             state
                 .api_update_tx
                 .send(AggregationEvent::New { slot })
                 .await?;
+            SlotOrder::New
         }
         Some(latest) if slot > latest => {
             aggregate_state.latest_completed_slot.replace(slot);
@@ -251,14 +359,22 @@ pub async fn store_update(state: &State, update: Update) -> Result<()> {
                 .api_update_tx
                 .send(AggregationEvent::New { slot })
                 .await?;
+            SlotOrder::New
         }
         _ => {
             state
                 .api_update_tx
                 .send(AggregationEvent::OutOfOrder { slot })
                 .await?;
+            SlotOrder::OutOfOrder
         }
-    }
+    };
+
+    state
+        .metrics
+        .completed_slots
+        .get_or_create(&CompletedSlotLabels { order })
+        .inc();
 
     aggregate_state.latest_completed_slot = aggregate_state
         .latest_completed_slot
@@ -267,21 +383,103 @@ pub async fn store_update(state: &State, update: Update) -> Result<()> {
 
     aggregate_state
         .latest_completed_update_at
-        .replace(Instant::now());
+        .replace(state.clock.now());
+
+    update_slot_lag_metric(state, &aggregate_state);
 
     Ok(())
 }
 
-#[tracing::instrument(skip(state, accumulator_messages, wormhole_merkle_state))]
+/// Store accumulator messages unless a different copy is already stored for the same slot. In that
+/// case both copies are quarantined, and the new copy only replaces the stored one if it is the one
+/// that matches the signed root. Returns whether the new copy has been stored.
+async fn store_accumulator_messages_or_quarantine(
+    state: &State,
+    accumulator_messages: AccumulatorMessages,
+) -> Result<bool> {
+    let slot = accumulator_messages.slot;
+    let existing = match state.fetch_accumulator_messages(slot).await? {
+        Some(existing) if existing != accumulator_messages => existing,
+        _ => {
+            state
+                .store_accumulator_messages(accumulator_messages)
+                .await?;
+            return Ok(true);
+        }
+    };
+
+    let expected_root = state
+        .fetch_wormhole_merkle_state(slot)
+        .await?
+        .map(|wormhole_merkle_state| wormhole_merkle_state.root.root);
+    let existing_root = accumulator_messages_root(&existing);
+    let new_root = accumulator_messages_root(&accumulator_messages);
+
+    tracing::warn!(slot, "Received conflicting Accumulator Messages.");
+    let alert = state
+        .quarantine
+        .add_candidates(
+            slot,
+            expected_root,
+            vec![existing, accumulator_messages.clone()],
+        )
+        .await;
+    publish_quarantine_alert(state, alert);
+
+    match expected_root {
+        Some(expected_root) if existing_root == Some(expected_root) => {
+            // The slot is already complete with the correct copy.
+            if let Some(alert) = state.quarantine.resolve(slot, expected_root).await {
+                publish_quarantine_alert(state, alert);
+            }
+            Ok(false)
+        }
+        Some(expected_root) if new_root == Some(expected_root) => {
+            state
+                .store_accumulator_messages(accumulator_messages)
+                .await?;
+            Ok(true)
+        }
+        // Either the root is not known yet, in which case the right copy is picked up from the
+        // quarantine once the VAA arrives, or neither copy matches it.
+        _ => Ok(false),
+    }
+}
+
+fn publish_quarantine_alert(state: &State, alert: QuarantineAlert) {
+    state
+        .metrics
+        .quarantine_alerts
+        .get_or_create(&QuarantineAlertLabels { kind: alert.kind() })
+        .inc();
+    state.quarantine.publish(alert);
+}
+
+fn update_slot_lag_metric(state: &State, aggregate_state: &AggregateState) {
+    if let (Some(latest_completed_slot), Some(latest_observed_slot)) = (
+        aggregate_state.latest_completed_slot,
+        aggregate_state.latest_observed_slot,
+    ) {
+        state
+            .metrics
+            .slot_lag
+            .set(latest_observed_slot.saturating_sub(latest_completed_slot) as i64);
+    }
+}
+
+#[tracing::instrument(skip(state, accumulator_messages, merkle_tree, wormhole_merkle_state))]
 async fn build_message_states(
     state: &State,
     accumulator_messages: AccumulatorMessages,
+    merkle_tree: Option<MerkleTree<Keccak160>>,
     wormhole_merkle_state: WormholeMerkleState,
+    current_time: UnixTimestamp,
 ) -> Result<()> {
-    let wormhole_merkle_message_states_proofs =
-        construct_message_states_proofs(&accumulator_messages, &wormhole_merkle_state)?;
-
-    let current_time: UnixTimestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as _;
+    let wormhole_merkle_message_states_proofs = construct_message_states_proofs(
+        &accumulator_messages,
+        merkle_tree.as_ref(),
+        &wormhole_merkle_state,
+    )?;
 
     let message_states = accumulator_messages
         .raw_messages
@@ -304,6 +502,22 @@ async fn build_message_states(
         })
         .collect::<Result<Vec<_>>>()?;
 
+    state
+        .feed_health
+        .observe(
+            message_states
+                .iter()
+                .filter_map(|message_state| match message_state.message {
+                    Message::PriceFeedMessage(price_feed) => Some((
+                        PriceIdentifier::new(price_feed.feed_id),
+                        price_feed.publish_time,
+                    )),
+                    _ => None,
+                }),
+            current_time,
+        )
+        .await;
+
     tracing::info!(len = message_states.len(), "Storing Message States.");
 
     state.store_message_states(message_states).await?;
@@ -311,6 +525,46 @@ async fn build_message_states(
     Ok(())
 }
 
+/// Convert a price feed message state into a price feed update, optionally with update data that
+/// proves this single message.
+fn price_feed_update_from_message_state(
+    message_state: &MessageState,
+    with_update_data: bool,
+) -> Result<PriceFeedUpdate> {
+    match message_state.message {
+        Message::PriceFeedMessage(price_feed) => Ok(PriceFeedUpdate {
+            price_feed:        PriceFeed::new(
+                PriceIdentifier::new(price_feed.feed_id),
+                Price {
+                    price:        price_feed.price,
+                    conf:         price_feed.conf,
+                    expo:         price_feed.exponent,
+                    publish_time: price_feed.publish_time,
+                },
+                Price {
+                    price:        price_feed.ema_price,
+                    conf:         price_feed.ema_conf,
+                    expo:         price_feed.exponent,
+                    publish_time: price_feed.publish_time,
+                },
+            ),
+            received_at:       Some(message_state.received_at),
+            slot:              Some(message_state.slot),
+            update_data:       match with_update_data {
+                true => Some(
+                    construct_update_data(vec![message_state.clone().into()])?
+                        .into_iter()
+                        .next()
+                        .ok_or(anyhow!("Missing update data for message"))?,
+                ),
+                false => None,
+            },
+            prev_publish_time: Some(price_feed.prev_publish_time),
+        }),
+        _ => Err(anyhow!("Invalid message state type")),
+    }
+}
+
 async fn get_verified_price_feeds<S>(
     state: &S,
     price_ids: Vec<PriceIdentifier>,
@@ -332,35 +586,7 @@ where
 
     let price_feeds = messages
         .iter()
-        .map(|message_state| match message_state.message {
-            Message::PriceFeedMessage(price_feed) => Ok(PriceFeedUpdate {
-                price_feed:        PriceFeed::new(
-                    PriceIdentifier::new(price_feed.feed_id),
-                    Price {
-                        price:        price_feed.price,
-                        conf:         price_feed.conf,
-                        expo:         price_feed.exponent,
-                        publish_time: price_feed.publish_time,
-                    },
-                    Price {
-                        price:        price_feed.ema_price,
-                        conf:         price_feed.ema_conf,
-                        expo:         price_feed.exponent,
-                        publish_time: price_feed.publish_time,
-                    },
-                ),
-                received_at:       Some(message_state.received_at),
-                slot:              Some(message_state.slot),
-                update_data:       Some(
-                    construct_update_data(vec![message_state.clone().into()])?
-                        .into_iter()
-                        .next()
-                        .ok_or(anyhow!("Missing update data for message"))?,
-                ),
-                prev_publish_time: Some(price_feed.prev_publish_time),
-            }),
-            _ => Err(anyhow!("Invalid message state type")),
-        })
+        .map(|message_state| price_feed_update_from_message_state(message_state, true))
         .collect::<Result<Vec<_>>>()?;
 
     let update_data = construct_update_data(messages.into_iter().map(|m| m.into()).collect())?;
@@ -391,6 +617,200 @@ where
     }
 }
 
+/// A page of the price feed updates of a single feed within a time range.
+#[derive(Debug, PartialEq)]
+pub struct PriceFeedUpdatesPage {
+    pub price_feeds: Vec<PriceFeedUpdate>,
+    /// The time of the last update in the page if there are more updates in the range. Passing it
+    /// as the cursor of the next request continues right after it.
+    pub next_cursor: Option<MessageStateTime>,
+}
+
+/// Get every update of a price feed with a publish time between `start_time` and `end_time`
+/// (both inclusive), in ascending order and at most `limit` of them.
+///
+/// If `cursor` is set, the page starts right after it instead of at `start_time`. Updates are
+/// ordered by publish time and slot, so the cursor is unambiguous even when several updates share
+/// the same publish time.
+pub async fn get_price_feed_updates_in_range<S>(
+    state: &S,
+    price_id: PriceIdentifier,
+    start_time: UnixTimestamp,
+    end_time: UnixTimestamp,
+    cursor: Option<MessageStateTime>,
+    limit: usize,
+    with_update_data: bool,
+) -> Result<PriceFeedUpdatesPage>
+where
+    S: AggregateCache,
+{
+    let start = match cursor {
+        Some(cursor) => Bound::Excluded(cursor),
+        None => Bound::Included(MessageStateTime {
+            publish_time: start_time,
+            slot:         Slot::MIN,
+        }),
+    };
+    let end = Bound::Included(MessageStateTime {
+        publish_time: end_time,
+        slot:         Slot::MAX,
+    });
+
+    // Fetch one more than requested to know whether there is a next page.
+    let mut message_states = state
+        .fetch_message_states_in_range(
+            MessageStateKey {
+                feed_id: price_id.to_bytes(),
+                type_:   MessageType::PriceFeedMessage,
+            },
+            (start, end),
+            limit.saturating_add(1),
+        )
+        .await?;
+
+    let next_cursor = match message_states.len() > limit {
+        true => {
+            message_states.truncate(limit);
+            message_states
+                .last()
+                .map(|message_state| message_state.time())
+        }
+        false => None,
+    };
+
+    Ok(PriceFeedUpdatesPage {
+        price_feeds: message_states
+            .iter()
+            .map(|message_state| {
+                price_feed_update_from_message_state(message_state, with_update_data)
+            })
+            .collect::<Result<Vec<_>>>()?,
+        next_cursor,
+    })
+}
+
+/// Get update data for a set of price feeds in which the messages of each slot share a single
+/// Merkle multiproof.
+///
+/// Unlike `get_price_feeds_with_update_data` this does not fall back to Benchmarks, as the
+/// multiproof is built from the accumulator messages held in the cache.
+pub async fn get_multiproof_update_data<S>(
+    state: &S,
+    price_ids: Vec<PriceIdentifier>,
+    request_time: RequestTime,
+) -> Result<Vec<Vec<u8>>>
+where
+    S: AggregateCache,
+{
+    let messages = state
+        .fetch_message_states(
+            price_ids
+                .iter()
+                .map(|price_id| price_id.to_bytes())
+                .collect(),
+            request_time,
+            MessageStateFilter::Only(MessageType::PriceFeedMessage),
+        )
+        .await?;
+
+    construct_multiproof_update_data(state, messages.into_iter().map(|m| m.into()).collect()).await
+}
+
+/// Compute the TWAP of a price feed between `start_time` and `end_time`.
+///
+/// The window starts at the first TWAP message published at or after `start_time` and ends at the
+/// message selected by `end_time`.
+pub async fn get_twap<S>(
+    state: &S,
+    price_id: PriceIdentifier,
+    start_time: UnixTimestamp,
+    end_time: RequestTime,
+) -> Result<TwapUpdate>
+where
+    S: AggregateCache,
+{
+    let (start, start_message_state) =
+        fetch_twap_message_state(state, price_id, RequestTime::FirstAfter(start_time)).await?;
+    let (end, end_message_state) = fetch_twap_message_state(state, price_id, end_time).await?;
+
+    let (twap, conf, down_slots_ratio) = calculate_twap(&start, &end)?;
+
+    Ok(TwapUpdate {
+        price_id,
+        start,
+        end,
+        twap,
+        conf,
+        exponent: end.exponent,
+        down_slots_ratio,
+        start_slot: start_message_state.slot,
+        end_slot: end_message_state.slot,
+        received_at: end_message_state.received_at,
+        update_data: construct_update_data(vec![
+            start_message_state.into(),
+            end_message_state.into(),
+        ])?,
+    })
+}
+
+async fn fetch_twap_message_state<S>(
+    state: &S,
+    price_id: PriceIdentifier,
+    request_time: RequestTime,
+) -> Result<(TwapMessage, MessageState)>
+where
+    S: AggregateCache,
+{
+    let message_state = state
+        .fetch_message_states(
+            vec![price_id.to_bytes()],
+            request_time,
+            MessageStateFilter::Only(MessageType::TwapMessage),
+        )
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("Twap message not found"))?;
+
+    match message_state.message {
+        Message::TwapMessage(twap_message) => Ok((twap_message, message_state)),
+        _ => Err(anyhow!("Invalid message state type")),
+    }
+}
+
+/// Returns the average price, the average confidence and the ratio of down slots between two TWAP
+/// messages.
+fn calculate_twap(start: &TwapMessage, end: &TwapMessage) -> Result<(i64, u64, f64)> {
+    if end.publish_slot <= start.publish_slot {
+        return Err(anyhow!(
+            "Twap window is empty: start slot {} is not before end slot {}",
+            start.publish_slot,
+            end.publish_slot
+        ));
+    }
+
+    let slot_diff = end.publish_slot - start.publish_slot;
+
+    let twap = end
+        .cumulative_price
+        .checked_sub(start.cumulative_price)
+        .and_then(|diff| diff.checked_div(slot_diff as i128))
+        .and_then(|twap| i64::try_from(twap).ok())
+        .ok_or(anyhow!("Twap price overflow"))?;
+
+    let conf = end
+        .cumulative_conf
+        .checked_sub(start.cumulative_conf)
+        .and_then(|diff| diff.checked_div(slot_diff as u128))
+        .and_then(|conf| u64::try_from(conf).ok())
+        .ok_or(anyhow!("Twap confidence overflow"))?;
+
+    let down_slots = end.num_down_slots.saturating_sub(start.num_down_slots);
+    let down_slots_ratio = down_slots as f64 / slot_diff as f64;
+
+    Ok((twap, conf, down_slots_ratio))
+}
+
 pub async fn get_price_feed_ids<S>(state: &S) -> HashSet<PriceIdentifier>
 where
     S: AggregateCache,
@@ -406,9 +826,13 @@ where
 pub async fn is_ready(state: &State) -> bool {
     let metadata = state.aggregate_state.read().await;
 
-    let has_completed_recently = match metadata.latest_completed_update_at.as_ref() {
+    let has_completed_recently = match metadata.latest_completed_update_at {
         Some(latest_completed_update_time) => {
-            latest_completed_update_time.elapsed() < READINESS_STALENESS_THRESHOLD
+            state
+                .clock
+                .now()
+                .saturating_sub(latest_completed_update_time)
+                < READINESS_STALENESS_THRESHOLD
         }
         None => false,
     };
@@ -427,15 +851,22 @@ pub async fn is_ready(state: &State) -> bool {
 }
 
 #[cfg(test)]
-mod test {
+pub mod test {
     use {
         super::*,
-        crate::state::test::setup_state,
+        crate::{
+            aggregate::quarantine::QuarantineStatus,
+            state::{
+                cache::test::create_and_store_dummy_price_feed_message_state,
+                test::setup_state,
+            },
+        },
         futures::future::join_all,
         mock_instant::MockClock,
         pythnet_sdk::{
             accumulators::{
                 merkle::{
+                    MerkleMultiProof,
                     MerkleRoot,
                     MerkleTree,
                 },
@@ -650,9 +1081,86 @@ mod test {
                 let merkle_root = MerkleRoot::<Keccak160>::new(merkle_root.root);
                 assert!(merkle_root.check(update.proof.clone(), message.as_ref()));
             }
+            _ => panic!("Unexpected proof type"),
         }
     }
 
+    #[tokio::test]
+    pub async fn test_mismatching_accumulator_messages_are_quarantined() {
+        let (state, mut update_rx) = setup_state(10).await;
+        let mut alerts = state.quarantine.subscribe();
+
+        let price_feed_message = create_dummy_price_feed_message(100, 10, 9);
+        let mut updates =
+            generate_update(vec![Message::PriceFeedMessage(price_feed_message)], 10, 20);
+        let vaa_update = updates.pop().unwrap();
+        let correct_update = updates.pop().unwrap();
+
+        // A faulty source delivers a different price for the same slot.
+        let faulty_update = generate_update(
+            vec![Message::PriceFeedMessage(create_dummy_price_feed_message(
+                100, 10, 8,
+            ))],
+            10,
+            21,
+        )
+        .remove(0);
+
+        store_update(&state, faulty_update).await.unwrap();
+        store_update(&state, vaa_update).await.unwrap();
+
+        // The slot does not complete with the faulty messages.
+        assert!(update_rx.try_recv().is_err());
+        let expected_root = state
+            .fetch_wormhole_merkle_state(10)
+            .await
+            .unwrap()
+            .unwrap()
+            .root
+            .root;
+        assert!(matches!(
+            alerts.try_recv(),
+            Ok(QuarantineAlert::Mismatch { slot: 10, expected_root: root, .. }) if root == expected_root
+        ));
+
+        // The correct copy from another source completes the slot.
+        store_update(&state, correct_update).await.unwrap();
+        assert_eq!(
+            update_rx.recv().await,
+            Some(AggregationEvent::New { slot: 10 })
+        );
+        assert!(matches!(
+            alerts.try_recv(),
+            Ok(QuarantineAlert::Mismatch { slot: 10, .. })
+        ));
+        assert_eq!(
+            alerts.try_recv().unwrap(),
+            QuarantineAlert::Resolved {
+                slot: 10,
+                root: expected_root,
+            }
+        );
+
+        let price_feeds_with_update_data = get_price_feeds_with_update_data(
+            &*state,
+            vec![PriceIdentifier::new([100; 32])],
+            RequestTime::Latest,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            price_feeds_with_update_data.price_feeds[0].prev_publish_time,
+            Some(9)
+        );
+
+        // Both copies are kept in the quarantine.
+        let quarantined_slots = state.quarantine.list().await;
+        assert_eq!(quarantined_slots.len(), 1);
+        assert_eq!(quarantined_slots[0].status, QuarantineStatus::Resolved);
+        assert_eq!(quarantined_slots[0].expected_root, Some(expected_root));
+        assert_eq!(quarantined_slots[0].candidates.len(), 2);
+    }
+
     #[tokio::test]
     pub async fn test_metadata_times_and_readiness_work() {
         // The receiver channel should stay open for the state to work
@@ -711,6 +1219,240 @@ mod test {
         assert!(!is_ready(&state).await);
     }
 
+    pub fn create_dummy_twap_message(
+        seed: u8,
+        cumulative_price: i128,
+        num_down_slots: u64,
+        publish_time: i64,
+        publish_slot: u64,
+    ) -> TwapMessage {
+        TwapMessage {
+            feed_id: [seed; 32],
+            cumulative_price,
+            cumulative_conf: cumulative_price as u128 / 10,
+            num_down_slots,
+            exponent: -8,
+            publish_time,
+            prev_publish_time: publish_time - 1,
+            publish_slot,
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_get_twap_works() {
+        let (state, _receiver_tx) = setup_state(10).await;
+
+        let start_message = create_dummy_twap_message(100, 1_000, 0, 10, 10);
+        let end_message = create_dummy_twap_message(100, 11_000, 5, 20, 30);
+
+        let mut updates = generate_update(vec![Message::TwapMessage(start_message)], 10, 20);
+        updates.extend(generate_update(
+            vec![Message::TwapMessage(end_message)],
+            30,
+            21,
+        ));
+        store_multiple_concurrent_valid_updates(state.clone(), updates).await;
+
+        let twap_update = get_twap(
+            &*state,
+            PriceIdentifier::new([100; 32]),
+            10,
+            RequestTime::Latest,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(twap_update.start, start_message);
+        assert_eq!(twap_update.end, end_message);
+        assert_eq!(twap_update.twap, 500);
+        assert_eq!(twap_update.conf, 50);
+        assert_eq!(twap_update.exponent, -8);
+        assert_eq!(twap_update.down_slots_ratio, 0.25);
+        assert_eq!(twap_update.start_slot, 10);
+        assert_eq!(twap_update.end_slot, 30);
+
+        // Each message comes with a proof against its own slot's root.
+        assert_eq!(twap_update.update_data.len(), 2);
+        for (update_data, message) in twap_update
+            .update_data
+            .iter()
+            .zip([start_message, end_message])
+        {
+            let update_data = AccumulatorUpdateData::try_from_slice(update_data.as_ref()).unwrap();
+            let (vaa, updates) = match update_data.proof {
+                Proof::WormholeMerkle { vaa, updates } => (vaa, updates),
+                _ => panic!("Unexpected proof type"),
+            };
+            let vaa: Vec<u8> = vaa.into();
+            let vaa: Vaa<&RawMessage> = serde_wormhole::from_slice(vaa.as_ref()).unwrap();
+            let WormholePayload::Merkle(merkle_root) =
+                WormholeMessage::try_from_bytes(vaa.payload.as_ref())
+                    .unwrap()
+                    .payload;
+
+            assert_eq!(updates.len(), 1);
+            let raw_message: Vec<u8> = updates[0].message.clone().into();
+            assert_eq!(
+                pythnet_sdk::wire::from_slice::<byteorder::BE, Message>(raw_message.as_ref())
+                    .unwrap(),
+                Message::TwapMessage(message)
+            );
+            assert!(MerkleRoot::<Keccak160>::new(merkle_root.root)
+                .check(updates[0].proof.clone(), raw_message.as_ref()));
+        }
+
+        // A window that starts and ends on the same message is empty.
+        assert!(get_twap(
+            &*state,
+            PriceIdentifier::new([100; 32]),
+            20,
+            RequestTime::Latest,
+        )
+        .await
+        .is_err());
+
+        // Feeds without TWAP messages have no TWAP.
+        assert!(get_twap(
+            &*state,
+            PriceIdentifier::new([200; 32]),
+            10,
+            RequestTime::Latest,
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    pub async fn test_get_multiproof_update_data_works() {
+        let (state, _update_rx) = setup_state(10).await;
+
+        let messages: Vec<Message> = (1..=10)
+            .map(|seed| Message::PriceFeedMessage(create_dummy_price_feed_message(seed, 10, 9)))
+            .collect();
+
+        store_multiple_concurrent_valid_updates(
+            state.clone(),
+            generate_update(messages.clone(), 10, 20),
+        )
+        .await;
+
+        let update_data = get_multiproof_update_data(
+            &*state,
+            vec![
+                PriceIdentifier::new([2; 32]),
+                PriceIdentifier::new([5; 32]),
+                PriceIdentifier::new([9; 32]),
+            ],
+            RequestTime::Latest,
+        )
+        .await
+        .unwrap();
+
+        // All messages are in the same slot, so they share a single proof.
+        assert_eq!(update_data.len(), 1);
+        let update_data = AccumulatorUpdateData::try_from_slice(update_data[0].as_ref()).unwrap();
+        match update_data.proof {
+            Proof::WormholeMerkleMulti {
+                vaa,
+                messages: proven_messages,
+                hashes,
+                flags,
+            } => {
+                let vaa: Vec<u8> = vaa.into();
+                let vaa: Vaa<&RawMessage> = serde_wormhole::from_slice(vaa.as_ref()).unwrap();
+                let WormholePayload::Merkle(merkle_root) =
+                    WormholeMessage::try_from_bytes(vaa.payload.as_ref())
+                        .unwrap()
+                        .payload;
+
+                let proven_messages: Vec<Vec<u8>> =
+                    proven_messages.into_iter().map(|m| m.into()).collect();
+                assert_eq!(proven_messages.len(), 3);
+                for message in [&messages[1], &messages[4], &messages[8]] {
+                    let raw_message =
+                        pythnet_sdk::wire::to_vec::<_, byteorder::BE>(message).unwrap();
+                    assert!(proven_messages.contains(&raw_message));
+                }
+
+                let proof = MerkleMultiProof::<Keccak160> {
+                    hashes: hashes.into(),
+                    flags:  flags.into(),
+                };
+                assert!(MerkleRoot::<Keccak160>::new(merkle_root.root).check_multi(
+                    &proof,
+                    &proven_messages
+                        .iter()
+                        .map(|m| m.as_ref())
+                        .collect::<Vec<&[u8]>>(),
+                ));
+            }
+            _ => panic!("Unexpected proof type"),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_get_price_feed_updates_in_range_works() {
+        let (state, _update_rx) = setup_state(10).await;
+
+        // Two updates share the publish time 11 and are told apart by their slot.
+        for (publish_time, slot) in [(10, 100), (11, 101), (11, 102), (12, 103), (13, 104)] {
+            create_and_store_dummy_price_feed_message_state(&*state, [1; 32], publish_time, slot)
+                .await;
+        }
+
+        let get_page = |cursor| {
+            get_price_feed_updates_in_range(
+                &*state,
+                PriceIdentifier::new([1; 32]),
+                11,
+                12,
+                cursor,
+                2,
+                false,
+            )
+        };
+
+        let page = get_page(None).await.unwrap();
+        assert_eq!(
+            page.price_feeds
+                .iter()
+                .map(|update| update.slot.unwrap())
+                .collect::<Vec<_>>(),
+            vec![101, 102]
+        );
+        assert!(page
+            .price_feeds
+            .iter()
+            .all(|update| update.update_data.is_none()));
+        assert_eq!(
+            page.next_cursor,
+            Some(MessageStateTime {
+                publish_time: 11,
+                slot:         102,
+            })
+        );
+
+        let page = get_page(page.next_cursor).await.unwrap();
+        assert_eq!(
+            page.price_feeds
+                .iter()
+                .map(|update| update.slot.unwrap())
+                .collect::<Vec<_>>(),
+            vec![103]
+        );
+        assert_eq!(page.next_cursor, None);
+
+        // A cursor past the end of the range yields an empty page.
+        let page = get_page(Some(MessageStateTime {
+            publish_time: 13,
+            slot:         104,
+        }))
+        .await
+        .unwrap();
+        assert!(page.price_feeds.is_empty());
+        assert_eq!(page.next_cursor, None);
+    }
+
     /// Test that the state retains the latest slots upon cache eviction.
     ///
     /// state is set up with cache size of 100 and 1000 slot updates will
@@ -0,0 +1,331 @@
+//! Health of the individual price feeds.
+//!
+//! The readiness of Hermes only reflects the slots as a whole, so a single feed that stops
+//! publishing goes unnoticed there. This module tracks the last publish time and the publish rate
+//! of every feed, and periodically checks them against a staleness threshold that depends on the
+//! asset type of the feed. An event is emitted whenever a feed goes stale or recovers.
+
+use {
+    super::UnixTimestamp,
+    crate::{
+        config::feed_health::Options,
+        state::State,
+    },
+    anyhow::Result,
+    pyth_sdk::PriceIdentifier,
+    serde::Serialize,
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::Ordering,
+            Arc,
+        },
+        time::Duration,
+    },
+    tokio::sync::{
+        broadcast,
+        RwLock,
+    },
+};
+
+/// How often the feeds are checked for staleness.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of events buffered for each event subscriber.
+const EVENTS_CHAN_LEN: usize = 1000;
+
+/// The weight of the latest interval in the moving average of the intervals between publishes.
+const PUBLISH_INTERVAL_SMOOTHING: f64 = 0.1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedStatus {
+    pub last_publish_time:        UnixTimestamp,
+    /// When the last publish was received by Hermes.
+    pub last_received_at:         UnixTimestamp,
+    /// Exponential moving average of the time between two publishes in seconds, once the feed
+    /// has published twice.
+    pub average_publish_interval: Option<f64>,
+    /// The staleness threshold of the feed as of the last check.
+    pub staleness_threshold:      Duration,
+    pub stale:                    bool,
+}
+
+impl FeedStatus {
+    /// The number of publishes per second.
+    pub fn update_rate(&self) -> Option<f64> {
+        self.average_publish_interval
+            .filter(|interval| *interval > 0.0)
+            .map(|interval| 1.0 / interval)
+    }
+}
+
+/// An event emitted whenever a feed goes stale or recovers.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FeedHealthEvent {
+    Stale {
+        id:                  PriceIdentifier,
+        last_publish_time:   UnixTimestamp,
+        /// The staleness threshold of the feed in seconds.
+        staleness_threshold: u64,
+    },
+    Recovered {
+        id:                PriceIdentifier,
+        last_publish_time: UnixTimestamp,
+    },
+}
+
+/// The staleness threshold of the feeds by asset type.
+#[derive(Clone, Debug)]
+pub struct StalenessThresholds {
+    default:       Duration,
+    by_asset_type: HashMap<String, Duration>,
+}
+
+impl StalenessThresholds {
+    pub fn threshold(&self, asset_type: Option<&str>) -> Duration {
+        asset_type
+            .and_then(|asset_type| self.by_asset_type.get(&asset_type.to_lowercase()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl From<&Options> for StalenessThresholds {
+    fn from(opts: &Options) -> Self {
+        Self {
+            default:       *opts.staleness_threshold,
+            by_asset_type: opts
+                .asset_type_thresholds
+                .iter()
+                .map(|threshold| (threshold.asset_type.clone(), threshold.threshold))
+                .collect(),
+        }
+    }
+}
+
+pub struct FeedHealth {
+    feeds:  RwLock<HashMap<PriceIdentifier, FeedStatus>>,
+    events: broadcast::Sender<FeedHealthEvent>,
+}
+
+impl FeedHealth {
+    pub fn new() -> Self {
+        Self {
+            feeds:  RwLock::new(HashMap::new()),
+            events: broadcast::channel(EVENTS_CHAN_LEN).0,
+        }
+    }
+
+    /// Subscribe to the events emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<FeedHealthEvent> {
+        self.events.subscribe()
+    }
+
+    /// Record the publish times of feeds received at `received_at`. Publishes that are not newer
+    /// than the last one of their feed, such as the ones of out of order slots, are ignored.
+    pub async fn observe(
+        &self,
+        publishes: impl IntoIterator<Item = (PriceIdentifier, UnixTimestamp)>,
+        received_at: UnixTimestamp,
+    ) {
+        let mut feeds = self.feeds.write().await;
+        for (id, publish_time) in publishes {
+            let status = feeds.entry(id).or_insert_with(|| FeedStatus {
+                last_publish_time:        publish_time,
+                last_received_at:         received_at,
+                average_publish_interval: None,
+                staleness_threshold:      Duration::ZERO,
+                stale:                    false,
+            });
+            if publish_time <= status.last_publish_time {
+                continue;
+            }
+
+            let interval = (publish_time - status.last_publish_time) as f64;
+            status.average_publish_interval = Some(match status.average_publish_interval {
+                Some(average) => average + PUBLISH_INTERVAL_SMOOTHING * (interval - average),
+                None => interval,
+            });
+            status.last_publish_time = publish_time;
+            status.last_received_at = received_at;
+        }
+    }
+
+    /// Check the feeds against their staleness threshold at `now`, and emit an event for every
+    /// feed that went stale or recovered since the last check. Returns the emitted events.
+    pub async fn check(
+        &self,
+        now: UnixTimestamp,
+        threshold: impl Fn(&PriceIdentifier) -> Duration,
+    ) -> Vec<FeedHealthEvent> {
+        let mut events = vec![];
+        for (id, status) in self.feeds.write().await.iter_mut() {
+            status.staleness_threshold = threshold(id);
+            let age = now.saturating_sub(status.last_publish_time).max(0) as u64;
+            let stale = age > status.staleness_threshold.as_secs();
+            if stale == status.stale {
+                continue;
+            }
+
+            status.stale = stale;
+            events.push(match stale {
+                true => FeedHealthEvent::Stale {
+                    id:                  *id,
+                    last_publish_time:   status.last_publish_time,
+                    staleness_threshold: status.staleness_threshold.as_secs(),
+                },
+                false => FeedHealthEvent::Recovered {
+                    id:                *id,
+                    last_publish_time: status.last_publish_time,
+                },
+            });
+        }
+
+        for event in &events {
+            tracing::info!(?event, "Feed health changed.");
+            // There are no receivers when nothing is subscribed, which is fine.
+            let _ = self.events.send(event.clone());
+        }
+
+        events
+    }
+
+    pub async fn statuses(&self) -> HashMap<PriceIdentifier, FeedStatus> {
+        self.feeds.read().await.clone()
+    }
+}
+
+impl Default for FeedHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Check the health of the feeds periodically, using the asset types of the feed metadata to pick
+/// their threshold.
+#[tracing::instrument(skip(opts, state))]
+pub async fn run(opts: Options, state: Arc<State>) -> Result<()> {
+    let thresholds = StalenessThresholds::from(&opts);
+    tracing::info!(?thresholds, "Started feed health checker.");
+
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
+        interval.tick().await;
+
+        let now = state.clock.now().as_secs() as UnixTimestamp;
+        let feed_metadata = state.feed_metadata.read().await;
+        state
+            .feed_health
+            .check(now, |id| {
+                thresholds.threshold(
+                    feed_metadata
+                        .get(id)
+                        .and_then(|metadata| metadata.asset_type.as_deref()),
+                )
+            })
+            .await;
+        drop(feed_metadata);
+
+        let num_stale_feeds = state
+            .feed_health
+            .statuses()
+            .await
+            .values()
+            .filter(|status| status.stale)
+            .count();
+        state.metrics.stale_feeds.set(num_stale_feeds as i64);
+    }
+
+    tracing::info!("Shutting down feed health checker...");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn thresholds() -> StalenessThresholds {
+        StalenessThresholds {
+            default:       Duration::from_secs(60),
+            by_asset_type: [("fx".to_owned(), Duration::from_secs(300))]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_thresholds_depend_on_asset_type() {
+        let thresholds = thresholds();
+        assert_eq!(thresholds.threshold(None), Duration::from_secs(60));
+        assert_eq!(
+            thresholds.threshold(Some("Crypto")),
+            Duration::from_secs(60)
+        );
+        assert_eq!(thresholds.threshold(Some("FX")), Duration::from_secs(300));
+    }
+
+    #[tokio::test]
+    async fn test_publish_rate_is_tracked() {
+        let feed_health = FeedHealth::new();
+        let id = PriceIdentifier::new([1; 32]);
+
+        feed_health.observe([(id, 100)], 101).await;
+        assert_eq!(feed_health.statuses().await[&id].update_rate(), None);
+
+        feed_health.observe([(id, 102)], 103).await;
+        feed_health.observe([(id, 104)], 105).await;
+        // Older publishes from out of order slots are ignored.
+        feed_health.observe([(id, 90)], 106).await;
+
+        let status = &feed_health.statuses().await[&id];
+        assert_eq!(status.last_publish_time, 104);
+        assert_eq!(status.last_received_at, 105);
+        assert_eq!(status.update_rate(), Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn test_stale_and_recovered_feeds_emit_events() {
+        let feed_health = FeedHealth::new();
+        let mut events = feed_health.subscribe();
+        let crypto = PriceIdentifier::new([1; 32]);
+        let fx = PriceIdentifier::new([2; 32]);
+        let thresholds = thresholds();
+        let threshold = |id: &PriceIdentifier| {
+            thresholds.threshold(Some(if *id == fx { "fx" } else { "crypto" }))
+        };
+
+        feed_health
+            .observe([(crypto, 1000), (fx, 1000)], 1000)
+            .await;
+        assert_eq!(feed_health.check(1060, threshold).await, vec![]);
+
+        // Only the crypto feed is past its threshold.
+        let stale = FeedHealthEvent::Stale {
+            id:                  crypto,
+            last_publish_time:   1000,
+            staleness_threshold: 60,
+        };
+        assert_eq!(
+            feed_health.check(1061, threshold).await,
+            vec![stale.clone()]
+        );
+        assert_eq!(events.recv().await.unwrap(), stale);
+
+        // The event is only emitted once.
+        assert_eq!(feed_health.check(1100, threshold).await, vec![]);
+        assert!(feed_health.statuses().await[&crypto].stale);
+        assert!(!feed_health.statuses().await[&fx].stale);
+
+        feed_health.observe([(crypto, 1100)], 1100).await;
+        let recovered = FeedHealthEvent::Recovered {
+            id:                crypto,
+            last_publish_time: 1100,
+        };
+        assert_eq!(
+            feed_health.check(1100, threshold).await,
+            vec![recovered.clone()]
+        );
+        assert_eq!(events.recv().await.unwrap(), recovered);
+    }
+}
@@ -0,0 +1,293 @@
+//! Quarantine for accumulator messages that do not match the Merkle root signed by the guardians.
+//!
+//! Accumulator messages come from Pythnet RPC nodes and are not signed, so a faulty node can
+//! deliver a dataset that does not match the root in the VAA of the same slot. Instead of dropping
+//! such a slot, every distinct dataset received for it is kept here as a candidate. This allows
+//! accepting a correct copy that arrives later from another source, and keeps a record of which
+//! copies were wrong.
+
+use {
+    super::{
+        AccumulatorMessages,
+        Slot,
+        UnixTimestamp,
+    },
+    crate::metrics::QuarantineAlertKind,
+    pythnet_sdk::{
+        accumulators::{
+            merkle::MerkleTree,
+            Accumulator,
+        },
+        hashers::keccak256_160::Keccak160,
+    },
+    serde::Serialize,
+    std::{
+        collections::BTreeMap,
+        time::{
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+    tokio::sync::{
+        broadcast,
+        RwLock,
+    },
+};
+
+/// The number of quarantined slots kept. The oldest slots are forgotten beyond this.
+const MAX_QUARANTINED_SLOTS: usize = 1000;
+
+/// The number of alerts buffered for each alert subscriber.
+const ALERTS_CHAN_LEN: usize = 100;
+
+pub type MerkleRootHash = [u8; 20];
+
+/// The Merkle tree of a set of accumulator messages. There is no tree for an empty set.
+pub fn accumulator_messages_tree(
+    accumulator_messages: &AccumulatorMessages,
+) -> Option<MerkleTree<Keccak160>> {
+    MerkleTree::<Keccak160>::from_set(accumulator_messages.raw_messages.iter().map(|m| m.as_ref()))
+}
+
+/// The root of a Merkle tree in the form it is signed in the VAAs.
+pub fn merkle_tree_root(merkle_tree: &MerkleTree<Keccak160>) -> Option<MerkleRootHash> {
+    merkle_tree.root.as_bytes().try_into().ok()
+}
+
+/// The Merkle root of a set of accumulator messages. There is no root for an empty set.
+pub fn accumulator_messages_root(
+    accumulator_messages: &AccumulatorMessages,
+) -> Option<MerkleRootHash> {
+    accumulator_messages_tree(accumulator_messages)
+        .as_ref()
+        .and_then(merkle_tree_root)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineStatus {
+    /// Sources disagree on the messages of the slot and its root is not known yet.
+    Conflicting,
+    /// None of the candidates match the root signed by the guardians.
+    Mismatched,
+    /// A candidate matching the root signed by the guardians has been accepted.
+    Resolved,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuarantineCandidate {
+    pub root:                 Option<MerkleRootHash>,
+    pub accumulator_messages: AccumulatorMessages,
+    pub received_at:          UnixTimestamp,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuarantinedSlot {
+    pub slot:           Slot,
+    pub status:         QuarantineStatus,
+    /// The root signed by the guardians, if the VAA of the slot has been received.
+    pub expected_root:  Option<MerkleRootHash>,
+    pub candidates:     Vec<QuarantineCandidate>,
+    pub quarantined_at: UnixTimestamp,
+}
+
+/// An alert emitted whenever the quarantine of a slot changes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuarantineAlert {
+    /// Sources delivered different messages for a slot whose root is not known yet.
+    Conflict {
+        slot:            Slot,
+        #[serde(with = "hex_roots")]
+        candidate_roots: Vec<Option<MerkleRootHash>>,
+    },
+    /// No candidate matches the root signed by the guardians.
+    Mismatch {
+        slot:            Slot,
+        #[serde(with = "hex::serde")]
+        expected_root:   MerkleRootHash,
+        #[serde(with = "hex_roots")]
+        candidate_roots: Vec<Option<MerkleRootHash>>,
+    },
+    /// A candidate matching the root signed by the guardians has been accepted.
+    Resolved {
+        slot: Slot,
+        #[serde(with = "hex::serde")]
+        root: MerkleRootHash,
+    },
+}
+
+impl QuarantineAlert {
+    pub fn kind(&self) -> QuarantineAlertKind {
+        match self {
+            QuarantineAlert::Conflict { .. } => QuarantineAlertKind::Conflict,
+            QuarantineAlert::Mismatch { .. } => QuarantineAlertKind::Mismatch,
+            QuarantineAlert::Resolved { .. } => QuarantineAlertKind::Resolved,
+        }
+    }
+}
+
+mod hex_roots {
+    use {
+        super::MerkleRootHash,
+        serde::{
+            Serialize,
+            Serializer,
+        },
+    };
+
+    pub fn serialize<S: Serializer>(
+        roots: &[Option<MerkleRootHash>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        roots
+            .iter()
+            .map(|root| root.map(hex::encode))
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+}
+
+fn now() -> UnixTimestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as UnixTimestamp)
+        .unwrap_or_default()
+}
+
+pub struct Quarantine {
+    slots:  RwLock<BTreeMap<Slot, QuarantinedSlot>>,
+    alerts: broadcast::Sender<QuarantineAlert>,
+}
+
+impl Quarantine {
+    pub fn new() -> Self {
+        Self {
+            slots:  RwLock::new(BTreeMap::new()),
+            alerts: broadcast::channel(ALERTS_CHAN_LEN).0,
+        }
+    }
+
+    /// Subscribe to the alerts emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<QuarantineAlert> {
+        self.alerts.subscribe()
+    }
+
+    /// Log an alert and send it to the subscribers.
+    pub fn publish(&self, alert: QuarantineAlert) {
+        match serde_json::to_string(&alert) {
+            Ok(encoded) => {
+                tracing::warn!(kind = ?alert.kind(), alert = encoded, "Quarantine alert.")
+            }
+            Err(e) => tracing::error!(error = ?e, "Failed to encode quarantine alert."),
+        }
+
+        // There are no receivers when nothing is subscribed, which is fine.
+        let _ = self.alerts.send(alert);
+    }
+
+    /// Quarantine candidate datasets for a slot. Candidates that are already quarantined are not
+    /// added twice. Returns the alert describing the new state of the slot.
+    pub async fn add_candidates(
+        &self,
+        slot: Slot,
+        expected_root: Option<MerkleRootHash>,
+        candidates: Vec<AccumulatorMessages>,
+    ) -> QuarantineAlert {
+        let mut slots = self.slots.write().await;
+        let quarantined_slot = slots.entry(slot).or_insert_with(|| QuarantinedSlot {
+            slot,
+            status: QuarantineStatus::Conflicting,
+            expected_root: None,
+            candidates: vec![],
+            quarantined_at: now(),
+        });
+
+        for accumulator_messages in candidates {
+            if !quarantined_slot
+                .candidates
+                .iter()
+                .any(|candidate| candidate.accumulator_messages == accumulator_messages)
+            {
+                quarantined_slot.candidates.push(QuarantineCandidate {
+                    root: accumulator_messages_root(&accumulator_messages),
+                    accumulator_messages,
+                    received_at: now(),
+                });
+            }
+        }
+
+        let candidate_roots = quarantined_slot
+            .candidates
+            .iter()
+            .map(|candidate| candidate.root)
+            .collect();
+
+        let alert = match expected_root.or(quarantined_slot.expected_root) {
+            Some(expected_root) => {
+                quarantined_slot.expected_root = Some(expected_root);
+                if quarantined_slot.status != QuarantineStatus::Resolved {
+                    quarantined_slot.status = QuarantineStatus::Mismatched;
+                }
+                QuarantineAlert::Mismatch {
+                    slot,
+                    expected_root,
+                    candidate_roots,
+                }
+            }
+            None => QuarantineAlert::Conflict {
+                slot,
+                candidate_roots,
+            },
+        };
+
+        while slots.len() > MAX_QUARANTINED_SLOTS {
+            slots.pop_first();
+        }
+
+        alert
+    }
+
+    /// Find a quarantined candidate of a slot with the given root.
+    pub async fn find_candidate(
+        &self,
+        slot: Slot,
+        root: MerkleRootHash,
+    ) -> Option<AccumulatorMessages> {
+        self.slots
+            .read()
+            .await
+            .get(&slot)
+            .and_then(|quarantined_slot| {
+                quarantined_slot
+                    .candidates
+                    .iter()
+                    .find(|candidate| candidate.root == Some(root))
+                    .map(|candidate| candidate.accumulator_messages.clone())
+            })
+    }
+
+    /// Mark a quarantined slot as resolved by a candidate with the given root. Returns an alert if
+    /// the slot was quarantined and not resolved before.
+    pub async fn resolve(&self, slot: Slot, root: MerkleRootHash) -> Option<QuarantineAlert> {
+        let mut slots = self.slots.write().await;
+        let quarantined_slot = slots.get_mut(&slot)?;
+        if quarantined_slot.status == QuarantineStatus::Resolved {
+            return None;
+        }
+
+        quarantined_slot.status = QuarantineStatus::Resolved;
+        quarantined_slot.expected_root = Some(root);
+        Some(QuarantineAlert::Resolved { slot, root })
+    }
+
+    pub async fn list(&self) -> Vec<QuarantinedSlot> {
+        self.slots.read().await.values().cloned().collect()
+    }
+}
+
+impl Default for Quarantine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -18,6 +18,7 @@ use {
     pythnet_sdk::{
         accumulators::{
             merkle::{
+                MerkleMultiProof,
                 MerklePath,
                 MerkleTree,
             },
@@ -83,14 +84,15 @@ where
     Ok(())
 }
 
+/// Prove every accumulator message of a slot with the Merkle tree built from them, see
+/// `accumulator_messages_tree`.
 pub fn construct_message_states_proofs(
     accumulator_messages: &AccumulatorMessages,
+    merkle_tree: Option<&MerkleTree<Keccak160>>,
     wormhole_merkle_state: &WormholeMerkleState,
 ) -> Result<Vec<WormholeMerkleMessageProof>> {
     // Check whether the state is valid
-    let merkle_acc = match MerkleTree::<Keccak160>::from_set(
-        accumulator_messages.raw_messages.iter().map(|m| m.as_ref()),
-    ) {
+    let merkle_acc = match merkle_tree {
         Some(merkle_acc) => merkle_acc,
         None => return Ok(vec![]), // It only happens when the message set is empty
     };
@@ -99,18 +101,19 @@ pub fn construct_message_states_proofs(
         return Err(anyhow!("Invalid merkle root"));
     }
 
-    accumulator_messages
-        .raw_messages
-        .iter()
-        .map(|m| {
-            Ok(WormholeMerkleMessageProof {
-                vaa:   wormhole_merkle_state.vaa.clone(),
-                proof: merkle_acc
-                    .prove(m.as_ref())
-                    .ok_or(anyhow!("Failed to prove message"))?,
-            })
+    // The proofs are in the same order as the messages the tree was built from.
+    let proofs = merkle_acc.prove_all();
+    if proofs.len() != accumulator_messages.raw_messages.len() {
+        return Err(anyhow!("Failed to prove messages"));
+    }
+
+    Ok(proofs
+        .into_iter()
+        .map(|proof| WormholeMerkleMessageProof {
+            vaa: wormhole_merkle_state.vaa.clone(),
+            proof,
         })
-        .collect::<Result<Vec<WormholeMerkleMessageProof>>>()
+        .collect())
 }
 
 pub fn construct_update_data(mut messages: Vec<RawMessageWithMerkleProof>) -> Result<Vec<Vec<u8>>> {
@@ -157,6 +160,82 @@ pub fn construct_update_data(mut messages: Vec<RawMessageWithMerkleProof>) -> Re
     Ok(result)
 }
 
+/// Construct update data that proves all messages of a slot with a single Merkle multiproof.
+///
+/// Messages that share a slot share most of their proof nodes, so this is considerably smaller
+/// than `construct_update_data` when many feeds are requested. The accumulator messages of every
+/// slot are needed to rebuild the tree, so the slots must still be in the cache.
+pub async fn construct_multiproof_update_data<S>(
+    state: &S,
+    mut messages: Vec<RawMessageWithMerkleProof>,
+) -> Result<Vec<Vec<u8>>>
+where
+    S: AggregateCache,
+{
+    tracing::info!(
+        "Constructing multiproof update data for {} messages",
+        messages.len()
+    );
+
+    messages.sort_by_key(|m| m.slot);
+
+    let mut iter = messages.into_iter().peekable();
+    let mut result: Vec<Vec<u8>> = vec![];
+
+    while let Some(message) = iter.next() {
+        let slot = message.slot;
+        let vaa = message.proof.vaa;
+
+        let accumulator_messages = state
+            .fetch_accumulator_messages(slot)
+            .await?
+            .ok_or(anyhow!("Accumulator messages for slot {} not found", slot))?;
+        let merkle_acc = MerkleTree::<Keccak160>::from_set(
+            accumulator_messages.raw_messages.iter().map(|m| m.as_ref()),
+        )
+        .ok_or(anyhow!("Accumulator messages for slot {} are empty", slot))?;
+
+        let mut raw_messages = vec![message.raw_message];
+        while raw_messages.len() < MAX_MESSAGE_IN_SINGLE_UPDATE_DATA {
+            if let Some(message) = iter.next_if(|m| m.slot == slot) {
+                raw_messages.push(message.raw_message);
+            } else {
+                break;
+            }
+        }
+
+        let (order, proof) = merkle_acc
+            .prove_multi(
+                &raw_messages
+                    .iter()
+                    .map(|m| m.as_ref())
+                    .collect::<Vec<&[u8]>>(),
+            )
+            .ok_or(anyhow!("Failed to prove messages"))?;
+
+        tracing::info!(
+            slot = slot,
+            "Combining {} messages in a single multiproof updateData",
+            order.len()
+        );
+
+        let MerkleMultiProof { hashes, flags } = proof;
+        result.push(to_vec::<_, byteorder::BE>(&AccumulatorUpdateData::new(
+            Proof::WormholeMerkleMulti {
+                vaa:      vaa.into(),
+                messages: order
+                    .into_iter()
+                    .map(|i| raw_messages[i].clone().into())
+                    .collect(),
+                hashes:   hashes.into(),
+                flags:    flags.into(),
+            },
+        ))?);
+    }
+
+    Ok(result)
+}
+
 #[cfg(test)]
 mod test {
     use {
@@ -218,6 +297,7 @@ mod test {
             let update_data = AccumulatorUpdateData::try_from_slice(update_data).unwrap();
             let price_updates = match &update_data.proof {
                 Proof::WormholeMerkle { updates, .. } => updates,
+                _ => panic!("Unexpected proof type"),
             };
 
             let price_update_message = price_updates.first().unwrap().clone();
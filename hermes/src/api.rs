@@ -1,20 +1,37 @@
 use {
-    self::ws::notify_updates,
+    self::{
+        auth::ApiKeys,
+        ws::notify_updates,
+    },
     crate::{
         aggregate::AggregationEvent,
-        config::RunOptions,
+        config::rpc,
+        metrics::RestRouteLabels,
         state::State,
     },
     anyhow::Result,
     axum::{
-        extract::Extension,
+        extract::{
+            Extension,
+            MatchedPath,
+            State as AxumState,
+        },
+        http::Request,
+        middleware::{
+            self,
+            Next,
+        },
+        response::IntoResponse,
         routing::get,
         Router,
     },
     serde_qs::axum::QsQueryConfig,
-    std::sync::{
-        atomic::Ordering,
-        Arc,
+    std::{
+        sync::{
+            atomic::Ordering,
+            Arc,
+        },
+        time::Instant,
     },
     tokio::{
         signal,
@@ -25,57 +42,107 @@ use {
     utoipa_swagger_ui::SwaggerUi,
 };
 
+mod auth;
+mod grpc;
 mod rest;
+mod sse;
 mod types;
 mod ws;
 
+pub use ws::{
+    SubscriberKind,
+    SubscriberQueue,
+    WsState,
+};
+
 #[derive(Clone)]
 pub struct ApiState {
-    pub state: Arc<State>,
-    pub ws:    Arc<ws::WsState>,
+    pub state:    Arc<State>,
+    pub ws:       Arc<WsState>,
+    /// The clients allowed to use the API, which is open to everyone if not set.
+    pub api_keys: Option<Arc<ApiKeys>>,
 }
 
 impl ApiState {
-    pub fn new(state: Arc<State>) -> Self {
+    pub fn new(state: Arc<State>, api_keys: Option<ApiKeys>) -> Self {
         Self {
+            ws: state.ws.clone(),
             state,
-            ws: Arc::new(ws::WsState::new()),
+            api_keys: api_keys.map(Arc::new),
         }
     }
 }
 
+/// Record the latency of every request in the REST latency histogram, labeled by the route that
+/// matched the request rather than the raw path to keep the number of labels bounded.
+async fn track_request_duration<B>(
+    AxumState(state): AxumState<ApiState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> impl IntoResponse {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str().to_owned(),
+        None => request.uri().path().to_owned(),
+    };
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    state
+        .state
+        .metrics
+        .rest_request_duration
+        .get_or_create(&RestRouteLabels {
+            route,
+            status: response.status().as_u16(),
+        })
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
 /// This method provides a background service that responds to REST requests
 ///
 /// Currently this is based on Axum due to the simplicity and strong ecosystem support for the
 /// packages they are based on (tokio & hyper).
 #[tracing::instrument(skip(opts, state, update_rx))]
 pub async fn run(
-    opts: RunOptions,
+    opts: rpc::Options,
     state: Arc<State>,
     mut update_rx: Receiver<AggregationEvent>,
 ) -> Result<()> {
-    tracing::info!(endpoint = %opts.rpc.addr, "Starting RPC Server.");
+    tracing::info!(endpoint = %opts.addr, "Starting RPC Server.");
 
     #[derive(OpenApi)]
     #[openapi(
         paths(
+            rest::feed_health,
             rest::get_price_feed,
+            rest::get_price_feed_range,
+            rest::get_twap,
             rest::get_vaa,
             rest::get_vaa_ccip,
             rest::latest_price_feeds,
             rest::latest_vaas,
             rest::price_feed_ids,
+            rest::price_feeds,
+            sse::price_feeds_stream,
         ),
         components(
             schemas(
+                rest::GetPriceFeedRangeResponse,
                 rest::GetVaaCcipInput,
                 rest::GetVaaCcipResponse,
                 rest::GetVaaResponse,
                 types::PriceIdInput,
+                types::RpcFeedHealth,
+                types::RpcFeedMetadata,
                 types::RpcPrice,
                 types::RpcPriceFeed,
                 types::RpcPriceFeedMetadata,
                 types::RpcPriceIdentifier,
+                types::RpcTwap,
+                types::RpcTwapMessage,
             )
         ),
         tags(
@@ -84,7 +151,31 @@ pub async fn run(
     )]
     struct ApiDoc;
 
-    let state = ApiState::new(state);
+    let api_keys = match opts.api_keys_path {
+        Some(ref path) => Some(ApiKeys::load(path)?),
+        None => None,
+    };
+    let state = ApiState::new(state, api_keys);
+
+    // The price routes require an API key when API keys are configured, unlike the documentation
+    // and the health checks.
+    let price_routes = Router::new()
+        .route("/ws", get(ws::ws_route_handler))
+        .route("/api/latest_price_feeds", get(rest::latest_price_feeds))
+        .route("/api/latest_vaas", get(rest::latest_vaas))
+        .route("/api/get_price_feed", get(rest::get_price_feed))
+        .route("/api/get_price_feed_range", get(rest::get_price_feed_range))
+        .route("/api/get_twap", get(rest::get_twap))
+        .route("/api/get_vaa", get(rest::get_vaa))
+        .route("/api/get_vaa_ccip", get(rest::get_vaa_ccip))
+        .route("/api/price_feed_ids", get(rest::price_feed_ids))
+        .route("/api/price_feeds", get(rest::price_feeds))
+        .route("/api/feed_health", get(rest::feed_health))
+        .route("/api/price_feeds/stream", get(sse::price_feeds_stream))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::authenticate,
+        ));
 
     // Initialize Axum Router. Note the type here is a `Router<State>` due to the use of the
     // `with_state` method which replaces `Body` with `State` in the type signature.
@@ -94,13 +185,13 @@ pub async fn run(
         .route("/", get(rest::index))
         .route("/live", get(rest::live))
         .route("/ready", get(rest::ready))
-        .route("/ws", get(ws::ws_route_handler))
-        .route("/api/latest_price_feeds", get(rest::latest_price_feeds))
-        .route("/api/latest_vaas", get(rest::latest_vaas))
-        .route("/api/get_price_feed", get(rest::get_price_feed))
-        .route("/api/get_vaa", get(rest::get_vaa))
-        .route("/api/get_vaa_ccip", get(rest::get_vaa_ccip))
-        .route("/api/price_feed_ids", get(rest::price_feed_ids))
+        .merge(price_routes)
+        // Route layers only apply to matched routes, which is what makes `MatchedPath` available
+        // to the middleware.
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_request_duration,
+        ))
         .with_state(state.clone())
         // Permissive CORS layer to allow all origins
         .layer(CorsLayer::permissive())
@@ -108,6 +199,9 @@ pub async fn run(
         // default value for this parameter).
         .layer(Extension(QsQueryConfig::new(5, false)));
 
+    // The gRPC API is served on its own address, with the same state and API keys.
+    let grpc_server = grpc::run(opts.grpc_addr, state.clone());
+
     tokio::spawn(async move {
         while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
             match update_rx.recv().await {
@@ -120,7 +214,8 @@ pub async fn run(
                     break;
                 }
                 Some(event) => {
-                    notify_updates(state.ws.clone(), event).await;
+                    // Both websocket and SSE subscribers are registered in the websocket state.
+                    notify_updates(&state.ws, event);
                 }
             }
         }
@@ -130,15 +225,20 @@ pub async fn run(
 
     // Binds the axum's server to the configured address and port. This is a blocking call and will
     // not return until the server is shutdown.
-    axum::Server::try_bind(&opts.rpc.addr)?
-        .serve(app.into_make_service())
-        .with_graceful_shutdown(async {
-            // Ignore Ctrl+C errors, either way we need to shut down. The main Ctrl+C handler
-            // should also have triggered so we will let that one print the shutdown warning.
-            let _ = signal::ctrl_c().await;
-            crate::SHOULD_EXIT.store(true, Ordering::Release);
-        })
-        .await?;
+    let rest_server = async {
+        axum::Server::try_bind(&opts.addr)?
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(async {
+                // Ignore Ctrl+C errors, either way we need to shut down. The main Ctrl+C handler
+                // should also have triggered so we will let that one print the shutdown warning.
+                let _ = signal::ctrl_c().await;
+                crate::SHOULD_EXIT.store(true, Ordering::Release);
+            })
+            .await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    tokio::try_join!(rest_server, grpc_server)?;
 
     Ok(())
 }
@@ -0,0 +1,456 @@
+//! Optional API key authentication and rate limiting of the public API.
+//!
+//! The keys are loaded from a JSON file listing the clients and their limits, for example:
+//!
+//! ```json
+//! [
+//!     {
+//!         "name": "partner-a",
+//!         "key": "3f1b5c0e9a7d",
+//!         "requests_per_second": 20,
+//!         "burst": 100,
+//!         "max_subscriptions": 5
+//!     }
+//! ]
+//! ```
+//!
+//! Clients pass their key in the `x-api-key` header or the `api_key` query parameter, or in the
+//! `x-api-key` metadata of gRPC calls. Each key gets a token bucket refilled at
+//! `requests_per_second` that holds up to `burst` requests, and can hold at most
+//! `max_subscriptions` WebSocket, Server-Sent Events and gRPC streams at once. Limits that
+//! are not set are not enforced. The requests of each key are counted in the Prometheus metrics so
+//! that usage can be billed.
+
+use {
+    super::{
+        rest::RestError,
+        ApiState,
+    },
+    crate::{
+        metrics::{
+            ApiKeyLabels,
+            ApiKeyRequestLabels,
+            ApiKeyRequestOutcome,
+        },
+        state::State,
+    },
+    anyhow::{
+        anyhow,
+        Result,
+    },
+    axum::{
+        extract::{
+            Extension,
+            State as AxumState,
+        },
+        http::{
+            header::RETRY_AFTER,
+            Request,
+            StatusCode,
+        },
+        middleware::Next,
+        response::{
+            IntoResponse,
+            Response,
+        },
+    },
+    serde::Deserialize,
+    std::{
+        collections::HashMap,
+        path::Path,
+        sync::{
+            atomic::{
+                AtomicUsize,
+                Ordering,
+            },
+            Arc,
+            Mutex,
+        },
+        time::{
+            Duration,
+            Instant,
+        },
+    },
+};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, Deserialize)]
+struct ApiKeyConfig {
+    /// The name of the client, used to label its metrics.
+    name:                String,
+    key:                 String,
+    requests_per_second: Option<u32>,
+    /// Defaults to one second worth of requests.
+    burst:               Option<u32>,
+    max_subscriptions:   Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeyQueryParams {
+    api_key: Option<String>,
+}
+
+/// A token bucket that allows `capacity` requests at once and refills at `refill_rate` requests
+/// per second.
+#[derive(Debug)]
+struct RateLimiter {
+    capacity:    f64,
+    refill_rate: f64,
+    tokens:      f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32, burst: u32, now: Instant) -> Self {
+        Self {
+            capacity:    burst as f64,
+            refill_rate: requests_per_second as f64,
+            tokens:      burst as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Take a token from the bucket, or return how long to wait until one is available.
+    fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_rate,
+            ))
+        }
+    }
+}
+
+/// A client of the API identified by its key.
+#[derive(Debug)]
+pub struct ApiClient {
+    pub name:          String,
+    rate_limiter:      Option<Mutex<RateLimiter>>,
+    max_subscriptions: Option<usize>,
+    subscriptions:     AtomicUsize,
+}
+
+impl ApiClient {
+    fn labels(&self) -> ApiKeyLabels {
+        ApiKeyLabels {
+            api_key: self.name.clone(),
+        }
+    }
+
+    fn check_rate_limit(&self) -> Result<(), Duration> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => rate_limiter
+                .lock()
+                .expect("Rate limiter lock is poisoned.")
+                .try_acquire(Instant::now()),
+            None => Ok(()),
+        }
+    }
+
+    /// Reserve a subscription for the client, which is released when the returned guard is
+    /// dropped. Returns `None` if the client already has as many subscriptions as it is allowed.
+    pub fn try_subscribe(self: &Arc<Self>, state: Arc<State>) -> Option<SubscriptionGuard> {
+        self.subscriptions
+            .fetch_update(
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                |subscriptions| match self.max_subscriptions {
+                    Some(max_subscriptions) if subscriptions >= max_subscriptions => None,
+                    _ => Some(subscriptions + 1),
+                },
+            )
+            .ok()?;
+
+        state
+            .metrics
+            .api_key_subscriptions
+            .get_or_create(&self.labels())
+            .inc();
+
+        Some(SubscriptionGuard {
+            client: self.clone(),
+            state,
+        })
+    }
+}
+
+/// Reserve a subscription for the client that made a request, if API keys are configured.
+pub fn subscribe(
+    client: Option<Extension<Arc<ApiClient>>>,
+    state: &Arc<State>,
+) -> Result<Option<SubscriptionGuard>, RestError> {
+    match client {
+        Some(Extension(client)) => client
+            .try_subscribe(state.clone())
+            .map(Some)
+            .ok_or(RestError::TooManySubscriptions),
+        None => Ok(None),
+    }
+}
+
+/// A subscription held by a client, see `ApiClient::try_subscribe`.
+pub struct SubscriptionGuard {
+    client: Arc<ApiClient>,
+    state:  Arc<State>,
+}
+
+impl Drop for SubscriptionGuard {
+    fn drop(&mut self) {
+        self.client.subscriptions.fetch_sub(1, Ordering::AcqRel);
+        self.state
+            .metrics
+            .api_key_subscriptions
+            .get_or_create(&self.client.labels())
+            .dec();
+    }
+}
+
+/// The clients allowed to use the API, by key.
+#[derive(Debug)]
+pub struct ApiKeys {
+    clients: HashMap<String, Arc<ApiClient>>,
+}
+
+impl ApiKeys {
+    pub fn load(path: &Path) -> Result<Self> {
+        let configs: Vec<ApiKeyConfig> = serde_json::from_slice(&std::fs::read(path)?)?;
+        let api_keys = Self::from_configs(configs)?;
+        tracing::info!(
+            path = %path.display(),
+            num_keys = api_keys.clients.len(),
+            "Loaded API keys."
+        );
+        Ok(api_keys)
+    }
+
+    fn from_configs(configs: Vec<ApiKeyConfig>) -> Result<Self> {
+        let now = Instant::now();
+        let mut clients = HashMap::new();
+
+        for config in configs {
+            let rate_limiter = match config.requests_per_second {
+                Some(0) => return Err(anyhow!("API key {} allows no requests.", config.name)),
+                Some(requests_per_second) => Some(Mutex::new(RateLimiter::new(
+                    requests_per_second,
+                    config.burst.unwrap_or(requests_per_second).max(1),
+                    now,
+                ))),
+                None => None,
+            };
+
+            let client = Arc::new(ApiClient {
+                name: config.name,
+                rate_limiter,
+                max_subscriptions: config.max_subscriptions,
+                subscriptions: AtomicUsize::new(0),
+            });
+
+            if let Some(client) = clients.insert(config.key, client) {
+                return Err(anyhow!(
+                    "API key of {} is used more than once.",
+                    client.name
+                ));
+            }
+        }
+
+        Ok(Self { clients })
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<ApiClient>> {
+        self.clients.get(key).cloned()
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingApiKey,
+    InvalidApiKey,
+    RateLimited { retry_after: Duration },
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthError::MissingApiKey => {
+                (StatusCode::UNAUTHORIZED, "Missing API key").into_response()
+            }
+            AuthError::InvalidApiKey => {
+                (StatusCode::UNAUTHORIZED, "Invalid API key").into_response()
+            }
+            AuthError::RateLimited { retry_after } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.as_secs_f64().ceil().to_string())],
+                "Rate limit exceeded",
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Read the API key of a request from the header or, failing that, the query string.
+fn api_key<B>(request: &Request<B>) -> Option<String> {
+    if let Some(key) = request.headers().get(API_KEY_HEADER) {
+        return key.to_str().ok().map(str::to_owned);
+    }
+
+    // Parse the query string the same way the handlers do, so that the other parameters do not
+    // cause errors.
+    serde_qs::Config::new(5, false)
+        .deserialize_str::<ApiKeyQueryParams>(request.uri().query()?)
+        .ok()?
+        .api_key
+}
+
+/// Identify the client of a request by its API key and check the rate limit of the key. Returns
+/// `None` when API keys are not configured, in which case every request is allowed.
+pub fn authorize(state: &ApiState, key: Option<&str>) -> Result<Option<Arc<ApiClient>>, AuthError> {
+    let api_keys = match &state.api_keys {
+        Some(api_keys) => api_keys,
+        None => return Ok(None),
+    };
+
+    let key = key.ok_or(AuthError::MissingApiKey)?;
+    let client = api_keys.get(key).ok_or(AuthError::InvalidApiKey)?;
+
+    let rate_limit = client.check_rate_limit();
+    state
+        .state
+        .metrics
+        .api_key_requests
+        .get_or_create(&ApiKeyRequestLabels {
+            api_key: client.name.clone(),
+            outcome: match rate_limit {
+                Ok(()) => ApiKeyRequestOutcome::Allowed,
+                Err(_) => ApiKeyRequestOutcome::RateLimited,
+            },
+        })
+        .inc();
+    rate_limit.map_err(|retry_after| AuthError::RateLimited { retry_after })?;
+
+    Ok(Some(client))
+}
+
+/// Reject the requests without a known API key or over the rate limit of their key when API keys
+/// are configured. The client is added to the request extensions for the handlers that enforce
+/// other limits.
+pub async fn authenticate<B>(
+    AxumState(state): AxumState<ApiState>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, AuthError> {
+    if let Some(client) = authorize(&state, api_key(&request).as_deref())? {
+        request.extensions_mut().insert(client);
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::test::setup_state,
+    };
+
+    fn api_keys(json: &str) -> Result<ApiKeys> {
+        ApiKeys::from_configs(serde_json::from_str(json)?)
+    }
+
+    #[test]
+    fn test_rate_limiter_allows_bursts_and_refills() {
+        let now = Instant::now();
+        let mut rate_limiter = RateLimiter::new(2, 3, now);
+
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.try_acquire(now), Ok(()));
+        }
+        assert_eq!(
+            rate_limiter.try_acquire(now),
+            Err(Duration::from_millis(500))
+        );
+
+        // Half a second refills a single request.
+        let now = now + Duration::from_millis(500);
+        assert_eq!(rate_limiter.try_acquire(now), Ok(()));
+        assert!(rate_limiter.try_acquire(now).is_err());
+
+        // The bucket never holds more than the burst.
+        let now = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.try_acquire(now), Ok(()));
+        }
+        assert!(rate_limiter.try_acquire(now).is_err());
+    }
+
+    #[test]
+    fn test_api_keys_are_validated() {
+        let keys = api_keys(
+            r#"[
+                {"name": "a", "key": "key-a", "requests_per_second": 10},
+                {"name": "b", "key": "key-b", "max_subscriptions": 2}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(keys.get("key-a").unwrap().name, "a");
+        assert!(keys.get("key-b").unwrap().rate_limiter.is_none());
+        assert!(keys.get("key-c").is_none());
+
+        assert!(api_keys(r#"[{"name": "a", "key": "key-a", "requests_per_second": 0}]"#).is_err());
+        assert!(
+            api_keys(r#"[{"name": "a", "key": "key-a"}, {"name": "b", "key": "key-a"}]"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_api_key_is_read_from_header_or_query() {
+        let request = Request::builder()
+            .uri("/api/latest_price_feeds?ids[]=abc")
+            .header(API_KEY_HEADER, "from-header")
+            .body(())
+            .unwrap();
+        assert_eq!(api_key(&request), Some("from-header".to_owned()));
+
+        let request = Request::builder()
+            .uri("/api/latest_price_feeds?ids%5B%5D=abc&ids%5B%5D=def&api_key=from-query")
+            .body(())
+            .unwrap();
+        assert_eq!(api_key(&request), Some("from-query".to_owned()));
+
+        let request = Request::builder()
+            .uri("/api/latest_price_feeds?ids[]=abc")
+            .body(())
+            .unwrap();
+        assert_eq!(api_key(&request), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_are_limited_per_key() {
+        let (state, _) = setup_state(10).await;
+        let keys = api_keys(r#"[{"name": "a", "key": "key-a", "max_subscriptions": 2}]"#).unwrap();
+        let client = keys.get("key-a").unwrap();
+
+        let first = client.try_subscribe(state.clone()).unwrap();
+        let _second = client.try_subscribe(state.clone()).unwrap();
+        assert!(client.try_subscribe(state.clone()).is_none());
+        assert_eq!(
+            state
+                .metrics
+                .api_key_subscriptions
+                .get_or_create(&client.labels())
+                .get(),
+            2
+        );
+
+        // Dropping a subscription makes room for a new one.
+        drop(first);
+        assert!(client.try_subscribe(state.clone()).is_some());
+    }
+}
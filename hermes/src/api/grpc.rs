@@ -0,0 +1,656 @@
+//! gRPC API serving the same price feeds as the REST and WebSocket APIs, for services where gRPC is
+//! the standard transport.
+//!
+//! The service and its messages are generated from `proto/price_service.proto` by the build script.
+
+use {
+    super::{
+        auth::{
+            self,
+            ApiClient,
+            AuthError,
+            SubscriptionGuard,
+            API_KEY_HEADER,
+        },
+        ws::{
+            Notification,
+            Notifications,
+            SubscriberId,
+            SubscriberKind,
+        },
+        ApiState,
+    },
+    crate::{
+        aggregate::{
+            self,
+            AggregationEvent,
+            PriceFeedUpdate,
+            Slot,
+        },
+        state::State,
+    },
+    anyhow::Result,
+    futures::Stream,
+    proto::{
+        price_service_server::{
+            PriceService,
+            PriceServiceServer,
+        },
+        request_time,
+        GetLatestPriceFeedsRequest,
+        GetLatestPriceFeedsResponse,
+        GetPriceFeedAtRequest,
+        GetPriceFeedIdsRequest,
+        GetPriceFeedIdsResponse,
+        Price,
+        PriceFeed,
+        RequestTime,
+        SubscribePriceFeedsRequest,
+        SubscribePriceFeedsResponse,
+    },
+    pyth_sdk::PriceIdentifier,
+    std::{
+        net::SocketAddr,
+        pin::Pin,
+        sync::{
+            atomic::Ordering,
+            Arc,
+        },
+    },
+    tokio::signal,
+    tonic::{
+        transport::Server,
+        Status,
+    },
+};
+
+/// The messages and the service of `pyth.hermes.v1`.
+pub mod proto {
+    #![allow(clippy::derive_partial_eq_without_eq)]
+
+    tonic::include_proto!("pyth.hermes.v1");
+}
+
+impl From<RequestTime> for aggregate::RequestTime {
+    fn from(request_time: RequestTime) -> Self {
+        match request_time.time {
+            None => aggregate::RequestTime::Latest,
+            Some(request_time::Time::FirstAfter(publish_time)) => {
+                aggregate::RequestTime::FirstAfter(publish_time)
+            }
+            Some(request_time::Time::AtSlot(slot)) => aggregate::RequestTime::AtSlot(slot),
+        }
+    }
+}
+
+impl From<pyth_sdk::Price> for Price {
+    fn from(price: pyth_sdk::Price) -> Self {
+        Self {
+            price:        price.price,
+            conf:         price.conf,
+            expo:         price.expo,
+            publish_time: price.publish_time,
+        }
+    }
+}
+
+impl From<PriceFeedUpdate> for PriceFeed {
+    fn from(update: PriceFeedUpdate) -> Self {
+        Self {
+            id:                update.price_feed.id.to_bytes().to_vec(),
+            price:             Some(update.price_feed.get_price_unchecked().into()),
+            ema_price:         Some(update.price_feed.get_ema_price_unchecked().into()),
+            slot:              update.slot,
+            received_at:       update.received_at,
+            prev_publish_time: update.prev_publish_time,
+            update_data:       update.update_data,
+        }
+    }
+}
+
+impl From<AuthError> for Status {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::MissingApiKey => Status::unauthenticated("Missing API key"),
+            AuthError::InvalidApiKey => Status::unauthenticated("Invalid API key"),
+            AuthError::RateLimited { retry_after } => Status::resource_exhausted(format!(
+                "Rate limit exceeded, retry after {}s",
+                retry_after.as_secs_f64().ceil()
+            )),
+        }
+    }
+}
+
+/// Check the API key in the metadata of a call, see `auth::authorize`.
+fn authorize<T>(
+    state: &ApiState,
+    request: &tonic::Request<T>,
+) -> Result<Option<Arc<ApiClient>>, Status> {
+    let key = request
+        .metadata()
+        .get(API_KEY_HEADER)
+        .and_then(|key| key.to_str().ok());
+    Ok(auth::authorize(state, key)?)
+}
+
+fn parse_price_id(id: &[u8]) -> Result<PriceIdentifier, Status> {
+    <[u8; 32]>::try_from(id)
+        .map(PriceIdentifier::new)
+        .map_err(|_| Status::invalid_argument(format!("Invalid price id: {}", hex::encode(id))))
+}
+
+fn parse_price_ids(ids: &[Vec<u8>]) -> Result<Vec<PriceIdentifier>, Status> {
+    ids.iter().map(|id| parse_price_id(id)).collect()
+}
+
+async fn get_latest_price_feeds(
+    state: ApiState,
+    request: tonic::Request<GetLatestPriceFeedsRequest>,
+) -> Result<tonic::Response<GetLatestPriceFeedsResponse>, Status> {
+    authorize(&state, &request)?;
+    let price_ids = parse_price_ids(&request.get_ref().ids)?;
+
+    let price_feeds_with_update_data = aggregate::get_price_feeds_with_update_data(
+        &*state.state,
+        price_ids,
+        aggregate::RequestTime::Latest,
+    )
+    .await
+    .map_err(|_| Status::not_found("Update data not found"))?;
+
+    Ok(tonic::Response::new(GetLatestPriceFeedsResponse {
+        price_feeds: price_feeds_with_update_data
+            .price_feeds
+            .into_iter()
+            .map(PriceFeed::from)
+            .collect(),
+        update_data: price_feeds_with_update_data.update_data,
+    }))
+}
+
+async fn get_price_feed_at(
+    state: ApiState,
+    request: tonic::Request<GetPriceFeedAtRequest>,
+) -> Result<tonic::Response<PriceFeed>, Status> {
+    authorize(&state, &request)?;
+    let request = request.into_inner();
+    let price_id = parse_price_id(&request.id)?;
+
+    let price_feeds_with_update_data = aggregate::get_price_feeds_with_update_data(
+        &*state.state,
+        vec![price_id],
+        request.time.unwrap_or_default().into(),
+    )
+    .await
+    .map_err(|_| Status::not_found("Update data not found"))?;
+
+    let mut price_feed = price_feeds_with_update_data
+        .price_feeds
+        .into_iter()
+        .next()
+        .ok_or_else(|| Status::not_found("Update data not found"))?;
+
+    // Benchmarks do not give per price feed update data, but since a single feed is requested the
+    // whole update data is the update data of this feed.
+    price_feed.update_data = price_feeds_with_update_data.update_data.into_iter().next();
+
+    Ok(tonic::Response::new(price_feed.into()))
+}
+
+async fn get_price_feed_ids(
+    state: ApiState,
+    request: tonic::Request<GetPriceFeedIdsRequest>,
+) -> Result<tonic::Response<GetPriceFeedIdsResponse>, Status> {
+    authorize(&state, &request)?;
+
+    let mut price_ids: Vec<PriceIdentifier> = aggregate::get_price_feed_ids(&*state.state)
+        .await
+        .into_iter()
+        .collect();
+    price_ids.sort();
+
+    Ok(tonic::Response::new(GetPriceFeedIdsResponse {
+        ids: price_ids
+            .into_iter()
+            .map(|price_id| price_id.to_bytes().to_vec())
+            .collect(),
+    }))
+}
+
+type PriceFeedsStream =
+    Pin<Box<dyn Stream<Item = Result<SubscribePriceFeedsResponse, Status>> + Send>>;
+
+async fn subscribe_price_feeds(
+    state: ApiState,
+    request: tonic::Request<SubscribePriceFeedsRequest>,
+) -> Result<tonic::Response<PriceFeedsStream>, Status> {
+    let subscription = match authorize(&state, &request)? {
+        Some(client) => Some(
+            client
+                .try_subscribe(state.state.clone())
+                .ok_or_else(|| Status::resource_exhausted("Too many subscriptions"))?,
+        ),
+        None => None,
+    };
+
+    let request = request.into_inner();
+    let price_ids = parse_price_ids(&request.ids)?;
+    let available_price_ids = aggregate::get_price_feed_ids(&*state.state).await;
+    let missing_ids: Vec<String> = price_ids
+        .iter()
+        .filter(|price_id| !available_price_ids.contains(price_id))
+        .map(|price_id| price_id.to_hex())
+        .collect();
+    if !missing_ids.is_empty() {
+        return Err(Status::not_found(format!(
+            "Price ids not found: {}",
+            missing_ids.join(", ")
+        )));
+    }
+
+    let (id, notifications) = state.ws.subscribe(SubscriberKind::Grpc);
+    tracing::debug!(id, "New gRPC Subscription");
+    let subscriber = PriceFeedsSubscriber::new(
+        id,
+        state.state.clone(),
+        price_ids,
+        request.allow_out_of_order,
+        notifications,
+        subscription,
+    );
+
+    let stream = futures::stream::unfold(Some(subscriber), |subscriber| async move {
+        let mut subscriber = subscriber?;
+        match subscriber.next_response().await {
+            Some(response) => Some((Ok(response), Some(subscriber))),
+            None if subscriber.notifications.is_evicted() => Some((
+                Err(Status::resource_exhausted(
+                    "The subscriber fell behind the updates for too long",
+                )),
+                None,
+            )),
+            None => None,
+        }
+    });
+
+    Ok(tonic::Response::new(Box::pin(stream) as PriceFeedsStream))
+}
+
+/// The state of a single `SubscribePriceFeeds` stream. When the client cancels the call the stream
+/// is dropped, which closes the notification channel and removes it from the subscribers on the
+/// next update.
+struct PriceFeedsSubscriber {
+    id:                 SubscriberId,
+    store:              Arc<State>,
+    price_ids:          Vec<PriceIdentifier>,
+    allow_out_of_order: bool,
+    notifications:      Notifications,
+    /// Slots skipped since the last response was sent.
+    skipped_slots:      u64,
+    _subscription:      Option<SubscriptionGuard>,
+}
+
+impl PriceFeedsSubscriber {
+    fn new(
+        id: SubscriberId,
+        store: Arc<State>,
+        price_ids: Vec<PriceIdentifier>,
+        allow_out_of_order: bool,
+        notifications: Notifications,
+        subscription: Option<SubscriptionGuard>,
+    ) -> Self {
+        store.metrics.grpc_subscribers.inc();
+        Self {
+            id,
+            store,
+            price_ids,
+            allow_out_of_order,
+            notifications,
+            skipped_slots: 0,
+            _subscription: subscription,
+        }
+    }
+
+    /// Wait for the next slot with updates of the subscribed feeds. Returns `None` once the
+    /// notification channel is closed, which ends the stream.
+    async fn next_response(&mut self) -> Option<SubscribePriceFeedsResponse> {
+        loop {
+            let event = match self.notifications.recv().await? {
+                Notification::Event(event) => event,
+                Notification::Skipped { num_slots, latest } => {
+                    self.skipped_slots += num_slots;
+                    latest
+                }
+            };
+            if let AggregationEvent::OutOfOrder { slot: _ } = event {
+                if !self.allow_out_of_order {
+                    continue;
+                }
+            }
+
+            if let Some(response) = self.response_at_slot(event.slot()).await {
+                return Some(response);
+            }
+        }
+    }
+
+    async fn response_at_slot(&mut self, slot: Slot) -> Option<SubscribePriceFeedsResponse> {
+        let price_feeds_with_update_data = match aggregate::get_price_feeds_with_update_data(
+            &*self.store,
+            self.price_ids.clone(),
+            aggregate::RequestTime::AtSlot(slot),
+        )
+        .await
+        {
+            Ok(price_feeds_with_update_data) => price_feeds_with_update_data,
+            Err(e) => {
+                tracing::debug!(subscriber = self.id, slot, error = ?e, "Failed to get price feeds for slot.");
+                return None;
+            }
+        };
+
+        Some(SubscribePriceFeedsResponse {
+            slot,
+            price_feeds: price_feeds_with_update_data
+                .price_feeds
+                .into_iter()
+                .map(PriceFeed::from)
+                .collect(),
+            update_data: price_feeds_with_update_data.update_data,
+            skipped_slots: std::mem::take(&mut self.skipped_slots),
+        })
+    }
+}
+
+impl Drop for PriceFeedsSubscriber {
+    fn drop(&mut self) {
+        tracing::debug!(id = self.id, "gRPC Subscription Closed");
+        self.store.metrics.grpc_subscribers.dec();
+    }
+}
+
+/// The price service, where each method is implemented by the function of the same name.
+struct GrpcApi {
+    state: ApiState,
+}
+
+#[tonic::async_trait]
+impl PriceService for GrpcApi {
+    type SubscribePriceFeedsStream = PriceFeedsStream;
+
+    async fn get_latest_price_feeds(
+        &self,
+        request: tonic::Request<GetLatestPriceFeedsRequest>,
+    ) -> Result<tonic::Response<GetLatestPriceFeedsResponse>, Status> {
+        get_latest_price_feeds(self.state.clone(), request).await
+    }
+
+    async fn get_price_feed_at(
+        &self,
+        request: tonic::Request<GetPriceFeedAtRequest>,
+    ) -> Result<tonic::Response<PriceFeed>, Status> {
+        get_price_feed_at(self.state.clone(), request).await
+    }
+
+    async fn get_price_feed_ids(
+        &self,
+        request: tonic::Request<GetPriceFeedIdsRequest>,
+    ) -> Result<tonic::Response<GetPriceFeedIdsResponse>, Status> {
+        get_price_feed_ids(self.state.clone(), request).await
+    }
+
+    async fn subscribe_price_feeds(
+        &self,
+        request: tonic::Request<SubscribePriceFeedsRequest>,
+    ) -> Result<tonic::Response<PriceFeedsStream>, Status> {
+        subscribe_price_feeds(self.state.clone(), request).await
+    }
+}
+
+/// Serve the gRPC API until shutdown.
+#[tracing::instrument(skip(state))]
+pub async fn run(addr: SocketAddr, state: ApiState) -> Result<()> {
+    tracing::info!(endpoint = %addr, "Starting gRPC Server.");
+
+    Server::builder()
+        .add_service(PriceServiceServer::new(GrpcApi { state }))
+        .serve_with_shutdown(addr, async {
+            // Ignore Ctrl+C errors, either way we need to shut down.
+            let _ = signal::ctrl_c().await;
+            crate::SHOULD_EXIT.store(true, Ordering::Release);
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            aggregate::test::{
+                create_dummy_price_feed_message,
+                generate_update,
+                store_multiple_concurrent_valid_updates,
+            },
+            api::ws::{
+                notify_updates,
+                test::expire_lag,
+                NOTIFICATIONS_CHAN_LEN,
+            },
+            state::test::setup_state,
+        },
+        futures::StreamExt,
+        pythnet_sdk::messages::Message,
+    };
+
+    async fn setup_api_state() -> ApiState {
+        let (state, _) = setup_state(10).await;
+        store_multiple_concurrent_valid_updates(
+            state.clone(),
+            generate_update(
+                vec![
+                    Message::PriceFeedMessage(create_dummy_price_feed_message(100, 10, 9)),
+                    Message::PriceFeedMessage(create_dummy_price_feed_message(200, 10, 9)),
+                ],
+                10,
+                20,
+            ),
+        )
+        .await;
+        ApiState::new(state, None)
+    }
+
+    /// Store a second slot, in which only the price feed `100` is updated.
+    async fn store_next_slot(state: &ApiState) {
+        store_multiple_concurrent_valid_updates(
+            state.state.clone(),
+            generate_update(
+                vec![Message::PriceFeedMessage(create_dummy_price_feed_message(
+                    100, 11, 10,
+                ))],
+                11,
+                21,
+            ),
+        )
+        .await;
+    }
+
+    async fn subscribe(state: &ApiState, allow_out_of_order: bool) -> PriceFeedsStream {
+        subscribe_price_feeds(
+            state.clone(),
+            tonic::Request::new(SubscribePriceFeedsRequest {
+                ids: vec![vec![100; 32]],
+                allow_out_of_order,
+            }),
+        )
+        .await
+        .unwrap()
+        .into_inner()
+    }
+
+    #[test]
+    fn test_request_time_defaults_to_latest() {
+        assert_eq!(
+            aggregate::RequestTime::from(RequestTime::default()),
+            aggregate::RequestTime::Latest
+        );
+        assert_eq!(
+            aggregate::RequestTime::from(RequestTime {
+                time: Some(request_time::Time::AtSlot(10)),
+            }),
+            aggregate::RequestTime::AtSlot(10)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_price_feeds() {
+        let state = setup_api_state().await;
+
+        let response = get_latest_price_feeds(
+            state.clone(),
+            tonic::Request::new(GetLatestPriceFeedsRequest {
+                ids: vec![vec![100; 32]],
+            }),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+        assert_eq!(response.price_feeds.len(), 1);
+        assert_eq!(response.price_feeds[0].id, vec![100; 32]);
+        assert_eq!(response.price_feeds[0].slot, Some(10));
+        assert_eq!(response.price_feeds[0].price.as_ref().unwrap().price, 100);
+        assert_eq!(response.update_data.len(), 1);
+
+        let status = get_latest_price_feeds(
+            state,
+            tonic::Request::new(GetLatestPriceFeedsRequest {
+                ids: vec![vec![100; 20]],
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_get_price_feed_ids() {
+        let state = setup_api_state().await;
+
+        let response = get_price_feed_ids(state, tonic::Request::new(GetPriceFeedIdsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.ids, vec![vec![100; 32], vec![200; 32]]);
+    }
+
+    #[tokio::test]
+    async fn test_get_price_feed_at() {
+        let state = setup_api_state().await;
+        store_next_slot(&state).await;
+
+        let get_at = |time: Option<request_time::Time>| {
+            get_price_feed_at(
+                state.clone(),
+                tonic::Request::new(GetPriceFeedAtRequest {
+                    id:   vec![100; 32],
+                    time: Some(RequestTime { time }),
+                }),
+            )
+        };
+
+        let price_feed = get_at(None).await.unwrap().into_inner();
+        assert_eq!(price_feed.id, vec![100; 32]);
+        assert_eq!(price_feed.slot, Some(11));
+        assert_eq!(price_feed.price.as_ref().unwrap().publish_time, 11);
+        assert_eq!(price_feed.prev_publish_time, Some(10));
+        assert!(price_feed.update_data.is_some());
+
+        let price_feed = get_at(Some(request_time::Time::AtSlot(10)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(price_feed.slot, Some(10));
+        assert_eq!(price_feed.price.as_ref().unwrap().publish_time, 10);
+
+        let status = get_at(Some(request_time::Time::AtSlot(12)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let status = get_price_feed_at(
+            state.clone(),
+            tonic::Request::new(GetPriceFeedAtRequest {
+                id:   vec![100; 20],
+                time: None,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_price_feeds_filters_out_of_order_slots() {
+        let state = setup_api_state().await;
+        store_next_slot(&state).await;
+
+        let mut in_order = subscribe(&state, false).await;
+        let mut out_of_order = subscribe(&state, true).await;
+
+        notify_updates(&state.ws, AggregationEvent::OutOfOrder { slot: 10 });
+        notify_updates(&state.ws, AggregationEvent::New { slot: 11 });
+
+        let response = in_order.next().await.unwrap().unwrap();
+        assert_eq!(response.slot, 11);
+        assert_eq!(response.price_feeds.len(), 1);
+        assert_eq!(response.price_feeds[0].id, vec![100; 32]);
+        assert_eq!(response.skipped_slots, 0);
+
+        assert_eq!(out_of_order.next().await.unwrap().unwrap().slot, 10);
+        assert_eq!(out_of_order.next().await.unwrap().unwrap().slot, 11);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_price_feeds_reports_skipped_slots() {
+        let state = setup_api_state().await;
+        store_next_slot(&state).await;
+
+        let mut stream = subscribe(&state, false).await;
+
+        // Fill the queue of the subscriber, so that the two next updates are dropped.
+        for _ in 0..NOTIFICATIONS_CHAN_LEN {
+            notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        }
+        notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        notify_updates(&state.ws, AggregationEvent::New { slot: 11 });
+
+        // The subscriber resumes from the newest update, skipping everything else.
+        let response = stream.next().await.unwrap().unwrap();
+        assert_eq!(response.slot, 11);
+        assert_eq!(response.skipped_slots, NOTIFICATIONS_CHAN_LEN as u64 + 1);
+
+        notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        let response = stream.next().await.unwrap().unwrap();
+        assert_eq!(response.slot, 10);
+        assert_eq!(response.skipped_slots, 0);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_price_feeds_evicts_lagging_subscriber() {
+        let state = setup_api_state().await;
+
+        let mut stream = subscribe(&state, false).await;
+
+        for _ in 0..=NOTIFICATIONS_CHAN_LEN {
+            notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        }
+        expire_lag(&state.ws);
+        notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+
+        let status = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -1,12 +1,18 @@
-use axum::{
-    http::StatusCode,
-    response::{
-        IntoResponse,
-        Response,
+use {
+    axum::{
+        http::StatusCode,
+        response::{
+            IntoResponse,
+            Response,
+        },
     },
+    pyth_sdk::PriceIdentifier,
 };
 
+mod feed_health;
 mod get_price_feed;
+mod get_price_feed_range;
+mod get_twap;
 mod get_vaa;
 mod get_vaa_ccip;
 mod index;
@@ -14,10 +20,14 @@ mod latest_price_feeds;
 mod latest_vaas;
 mod live;
 mod price_feed_ids;
+mod price_feeds;
 mod ready;
 
 pub use {
+    feed_health::*,
     get_price_feed::*,
+    get_price_feed_range::*,
+    get_twap::*,
     get_vaa::*,
     get_vaa_ccip::*,
     index::*,
@@ -25,6 +35,7 @@ pub use {
     latest_vaas::*,
     live::*,
     price_feed_ids::*,
+    price_feeds::*,
     ready::*,
 };
 
@@ -32,6 +43,9 @@ pub enum RestError {
     UpdateDataNotFound,
     CcipUpdateDataNotFound,
     InvalidCCIPInput,
+    InvalidCursor,
+    PriceIdsNotFound { missing_ids: Vec<PriceIdentifier> },
+    TooManySubscriptions,
 }
 
 impl IntoResponse for RestError {
@@ -53,6 +67,23 @@ impl IntoResponse for RestError {
             RestError::InvalidCCIPInput => {
                 (StatusCode::BAD_REQUEST, "Invalid CCIP input").into_response()
             }
+            RestError::InvalidCursor => (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+            RestError::PriceIdsNotFound { missing_ids } => {
+                let missing_ids = missing_ids
+                    .into_iter()
+                    .map(|id| id.to_hex())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                (
+                    StatusCode::NOT_FOUND,
+                    format!("Price ids not found: {}", missing_ids),
+                )
+                    .into_response()
+            }
+            RestError::TooManySubscriptions => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many subscriptions").into_response()
+            }
         }
     }
 }
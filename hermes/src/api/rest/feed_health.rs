@@ -0,0 +1,54 @@
+use {
+    crate::api::{
+        rest::RestError,
+        types::RpcFeedHealth,
+    },
+    anyhow::Result,
+    axum::{
+        extract::State,
+        Json,
+    },
+    serde_qs::axum::QsQuery,
+    utoipa::IntoParams,
+};
+
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in=Query)]
+pub struct FeedHealthQueryParams {
+    /// If true, also include the feeds that are not stale.
+    #[serde(default)]
+    include_healthy: bool,
+}
+
+/// Get the feeds that are stale.
+///
+/// A feed is stale when it has not published for longer than the staleness threshold of its asset
+/// type. The feeds are sorted by symbol.
+#[utoipa::path(
+    get,
+    path = "/api/feed_health",
+    responses(
+        (status = 200, description = "Feed health retrieved successfully", body = Vec<RpcFeedHealth>)
+    ),
+    params(
+        FeedHealthQueryParams
+    )
+)]
+pub async fn feed_health(
+    State(state): State<crate::api::ApiState>,
+    QsQuery(params): QsQuery<FeedHealthQueryParams>,
+) -> Result<Json<Vec<RpcFeedHealth>>, RestError> {
+    let feed_metadata = state.state.feed_metadata.read().await;
+    let mut feeds: Vec<RpcFeedHealth> = state
+        .state
+        .feed_health
+        .statuses()
+        .await
+        .into_iter()
+        .filter(|(_, status)| params.include_healthy || status.stale)
+        .map(|(id, status)| RpcFeedHealth::new(id, &status, feed_metadata.get(&id)))
+        .collect();
+    feeds.sort_by(|a, b| (&a.symbol, a.id).cmp(&(&b.symbol, b.id)));
+
+    Ok(Json(feeds))
+}
@@ -0,0 +1,149 @@
+use {
+    crate::{
+        aggregate::{
+            Slot,
+            UnixTimestamp,
+        },
+        api::{
+            rest::RestError,
+            types::{
+                PriceIdInput,
+                RpcPriceFeed,
+            },
+        },
+        doc_examples,
+        state::cache::MessageStateTime,
+    },
+    anyhow::Result,
+    axum::{
+        extract::State,
+        Json,
+    },
+    pyth_sdk::PriceIdentifier,
+    serde_qs::axum::QsQuery,
+    utoipa::{
+        IntoParams,
+        ToSchema,
+    },
+};
+
+/// The number of updates returned per page if no limit is provided.
+const DEFAULT_PAGE_LIMIT: usize = 100;
+
+/// The maximum number of updates returned per page.
+const MAX_PAGE_LIMIT: usize = 1000;
+
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in=Query)]
+pub struct GetPriceFeedRangeQueryParams {
+    /// The id of the price feed to get the updates for.
+    id: PriceIdInput,
+
+    /// The unix timestamp in seconds of the start of the range (inclusive).
+    #[param(value_type = i64)]
+    #[param(example = doc_examples::timestamp_example)]
+    start_time: UnixTimestamp,
+
+    /// The unix timestamp in seconds of the end of the range (inclusive).
+    #[param(value_type = i64)]
+    #[param(example = doc_examples::timestamp_example)]
+    end_time: UnixTimestamp,
+
+    /// The `next_cursor` of the previous page. If provided, the page starts right after the last
+    /// update of the previous page.
+    #[param(value_type = Option<String>)]
+    cursor: Option<String>,
+
+    /// The maximum number of updates to return. Defaults to 100 and is capped at 1000.
+    #[param(value_type = Option<usize>)]
+    limit: Option<usize>,
+
+    /// If true, include the `metadata` field in the response with additional metadata about
+    /// each price update.
+    #[serde(default)]
+    verbose: bool,
+
+    /// If true, include the binary price update in the `vaa` field of each returned feed.
+    /// This binary data can be submitted to Pyth contracts to update the on-chain price.
+    #[serde(default)]
+    binary: bool,
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub struct GetPriceFeedRangeResponse {
+    /// The price updates in the range, ordered by publish time and slot.
+    price_feeds: Vec<RpcPriceFeed>,
+
+    /// Pass this as the `cursor` parameter to get the next page. It is absent on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "1690576641_85480034")]
+    next_cursor: Option<String>,
+}
+
+/// A cursor is the publish time and slot of the last update of a page, which uniquely identify an
+/// update of a feed.
+fn encode_cursor(time: &MessageStateTime) -> String {
+    format!("{}_{}", time.publish_time, time.slot)
+}
+
+fn decode_cursor(cursor: &str) -> Option<MessageStateTime> {
+    let (publish_time, slot) = cursor.split_once('_')?;
+    Some(MessageStateTime {
+        publish_time: publish_time.parse::<UnixTimestamp>().ok()?,
+        slot:         slot.parse::<Slot>().ok()?,
+    })
+}
+
+/// Get all the price updates of a price feed within a time range.
+///
+/// Given a price feed id and a time range, retrieve every Pyth price update of the feed published
+/// within the range. The updates are paginated, follow `next_cursor` to retrieve all of them.
+#[utoipa::path(
+    get,
+    path = "/api/get_price_feed_range",
+    responses(
+        (status = 200, description = "Price updates retrieved successfully", body = GetPriceFeedRangeResponse),
+        (status = 400, description = "Invalid cursor", body = String)
+    ),
+    params(
+        GetPriceFeedRangeQueryParams
+    )
+)]
+pub async fn get_price_feed_range(
+    State(state): State<crate::api::ApiState>,
+    QsQuery(params): QsQuery<GetPriceFeedRangeQueryParams>,
+) -> Result<Json<GetPriceFeedRangeResponse>, RestError> {
+    let price_id: PriceIdentifier = params.id.into();
+
+    let cursor = match params.cursor {
+        Some(cursor) => Some(decode_cursor(&cursor).ok_or(RestError::InvalidCursor)?),
+        None => None,
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+
+    let page = crate::aggregate::get_price_feed_updates_in_range(
+        &*state.state,
+        price_id,
+        params.start_time,
+        params.end_time,
+        cursor,
+        limit,
+        params.binary,
+    )
+    .await
+    .map_err(|_| RestError::UpdateDataNotFound)?;
+
+    Ok(Json(GetPriceFeedRangeResponse {
+        price_feeds: page
+            .price_feeds
+            .into_iter()
+            .map(|price_feed| {
+                RpcPriceFeed::from_price_feed_update(price_feed, params.verbose, params.binary)
+            })
+            .collect(),
+        next_cursor: page.next_cursor.as_ref().map(encode_cursor),
+    }))
+}
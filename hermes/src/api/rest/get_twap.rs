@@ -0,0 +1,90 @@
+use {
+    crate::{
+        aggregate::{
+            RequestTime,
+            UnixTimestamp,
+        },
+        api::{
+            rest::RestError,
+            types::{
+                PriceIdInput,
+                RpcTwap,
+            },
+        },
+        doc_examples,
+    },
+    anyhow::Result,
+    axum::{
+        extract::State,
+        Json,
+    },
+    pyth_sdk::PriceIdentifier,
+    serde_qs::axum::QsQuery,
+    utoipa::IntoParams,
+};
+
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in=Query)]
+pub struct GetTwapQueryParams {
+    /// The id of the price feed to compute the TWAP for.
+    id: PriceIdInput,
+
+    /// The unix timestamp in seconds of the start of the window. The window starts at the first
+    /// update whose publish_time is >= the provided value.
+    #[param(value_type = i64)]
+    #[param(example = doc_examples::timestamp_example)]
+    start_time: UnixTimestamp,
+
+    /// The unix timestamp in seconds of the end of the window. The window ends at the first
+    /// update whose publish_time is >= the provided value. If not provided, the window ends at
+    /// the latest update.
+    #[param(value_type = Option<i64>)]
+    end_time: Option<UnixTimestamp>,
+
+    /// If true, include the `metadata` field in the response with additional metadata about the
+    /// end of the window.
+    #[serde(default)]
+    verbose: bool,
+
+    /// If true, include the binary update data of both ends of the window in the `update_data`
+    /// field. This binary data can be submitted to Pyth contracts to verify the TWAP on-chain.
+    #[serde(default)]
+    binary: bool,
+}
+
+/// Get the time-weighted average price of a price feed over a time window.
+///
+/// Given a price feed id and a time window, retrieve the TWAP messages bounding the window and the
+/// TWAP computed from them.
+#[utoipa::path(
+    get,
+    path = "/api/get_twap",
+    responses(
+        (status = 200, description = "TWAP computed successfully", body = RpcTwap)
+    ),
+    params(
+        GetTwapQueryParams
+    )
+)]
+pub async fn get_twap(
+    State(state): State<crate::api::ApiState>,
+    QsQuery(params): QsQuery<GetTwapQueryParams>,
+) -> Result<Json<RpcTwap>, RestError> {
+    let price_id: PriceIdentifier = params.id.into();
+
+    let end_time = match params.end_time {
+        Some(end_time) => RequestTime::FirstAfter(end_time),
+        None => RequestTime::Latest,
+    };
+
+    let twap_update =
+        crate::aggregate::get_twap(&*state.state, price_id, params.start_time, end_time)
+            .await
+            .map_err(|_| RestError::UpdateDataNotFound)?;
+
+    Ok(Json(RpcTwap::from_twap_update(
+        twap_update,
+        params.verbose,
+        params.binary,
+    )))
+}
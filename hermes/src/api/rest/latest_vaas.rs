@@ -35,6 +35,13 @@ pub struct LatestVaasQueryParams {
     #[param(rename = "ids[]")]
     #[param(example = "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43")]
     ids: Vec<PriceIdInput>,
+
+    /// If true, return one update per slot in which all requested feeds share a single Merkle
+    /// multiproof. This is smaller than the default format, but requires a contract that accepts
+    /// multiproof updates.
+    #[serde(default)]
+    #[param(example = "true")]
+    multiproof: bool,
 }
 
 
@@ -58,17 +65,28 @@ pub async fn latest_vaas(
     QsQuery(params): QsQuery<LatestVaasQueryParams>,
 ) -> Result<Json<Vec<String>>, RestError> {
     let price_ids: Vec<PriceIdentifier> = params.ids.into_iter().map(|id| id.into()).collect();
-    let price_feeds_with_update_data = crate::aggregate::get_price_feeds_with_update_data(
-        &*state.state,
-        price_ids,
-        RequestTime::Latest,
-    )
-    .await
-    .map_err(|_| RestError::UpdateDataNotFound)?;
+    let update_data = match params.multiproof {
+        true => crate::aggregate::get_multiproof_update_data(
+            &*state.state,
+            price_ids,
+            RequestTime::Latest,
+        )
+        .await
+        .map_err(|_| RestError::UpdateDataNotFound)?,
+        false => {
+            crate::aggregate::get_price_feeds_with_update_data(
+                &*state.state,
+                price_ids,
+                RequestTime::Latest,
+            )
+            .await
+            .map_err(|_| RestError::UpdateDataNotFound)?
+            .update_data
+        }
+    };
 
     Ok(Json(
-        price_feeds_with_update_data
-            .update_data
+        update_data
             .iter()
             .map(|bytes| base64_standard_engine.encode(bytes)) // TODO: Support multiple
             // encoding formats
@@ -0,0 +1,64 @@
+use {
+    crate::{
+        api::{
+            rest::RestError,
+            types::RpcFeedMetadata,
+        },
+        state::feed_metadata::{
+            FeedMetadataCatalogue,
+            FeedMetadataQuery,
+        },
+    },
+    anyhow::Result,
+    axum::{
+        extract::State,
+        Json,
+    },
+    serde_qs::axum::QsQuery,
+    utoipa::IntoParams,
+};
+
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in=Query)]
+pub struct PriceFeedsQueryParams {
+    /// Only return the feeds whose symbol, base, quote or description contains this text, ignoring
+    /// case.
+    #[param(example = "BTC")]
+    query: Option<String>,
+
+    /// Only return the feeds of this asset type, ignoring case.
+    #[param(example = "crypto")]
+    asset_type: Option<String>,
+}
+
+/// Search the price feeds by their metadata.
+///
+/// Get the metadata of the price feeds, such as their symbol, optionally filtered by a text query
+/// and an asset type. The feeds are sorted by symbol.
+#[utoipa::path(
+    get,
+    path = "/api/price_feeds",
+    responses(
+        (status = 200, description = "Price feeds metadata retrieved successfully", body = Vec<RpcFeedMetadata>)
+    ),
+    params(
+        PriceFeedsQueryParams
+    )
+)]
+pub async fn price_feeds(
+    State(state): State<crate::api::ApiState>,
+    QsQuery(params): QsQuery<PriceFeedsQueryParams>,
+) -> Result<Json<Vec<RpcFeedMetadata>>, RestError> {
+    let price_feeds = state
+        .state
+        .search_feed_metadata(&FeedMetadataQuery {
+            query:      params.query,
+            asset_type: params.asset_type,
+        })
+        .await
+        .into_iter()
+        .map(RpcFeedMetadata::from)
+        .collect();
+
+    Ok(Json(price_feeds))
+}
@@ -0,0 +1,554 @@
+//! Server-Sent Events stream of price updates.
+//!
+//! This serves the same updates as the websocket API to clients that cannot use websockets, and
+//! is driven by the same `AggregationEvent` notifications. Every event carries the slot it was
+//! produced at as its id. A reconnecting client sends the last id it saw in the `Last-Event-ID`
+//! header and gets the slots it missed replayed from the cache before the live updates, as long as
+//! it is no more than `MAX_REPLAY_SLOTS` slots behind.
+//!
+//! A client that reads slower than the updates are produced gets a `skipped` event with the number
+//! of slots it missed and resumes from the latest slot. If it falls behind for too long the stream
+//! is ended.
+
+use {
+    super::{
+        auth::{
+            self,
+            ApiClient,
+        },
+        rest::RestError,
+        types::{
+            PriceIdInput,
+            RpcPriceFeed,
+        },
+        ws::{
+            Notification,
+            Notifications,
+            SubscriberId,
+            SubscriberKind,
+        },
+    },
+    crate::{
+        aggregate::{
+            AggregationEvent,
+            RequestTime,
+            Slot,
+        },
+        state::{
+            cache::AggregateCache,
+            State,
+        },
+    },
+    anyhow::Result,
+    axum::{
+        extract::{
+            Extension,
+            State as AxumState,
+        },
+        http::HeaderMap,
+        response::sse::{
+            Event,
+            KeepAlive,
+            Sse,
+        },
+    },
+    futures::Stream,
+    pyth_sdk::PriceIdentifier,
+    serde_qs::axum::QsQuery,
+    std::{
+        collections::{
+            HashSet,
+            VecDeque,
+        },
+        convert::Infallible,
+        sync::Arc,
+    },
+    utoipa::IntoParams,
+};
+
+const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+const PRICE_UPDATE_EVENT: &str = "price_update";
+const SKIPPED_EVENT: &str = "skipped";
+
+/// The maximum number of slots replayed to a reconnecting client. Clients whose last event id is
+/// further behind than this only receive the live updates.
+const MAX_REPLAY_SLOTS: usize = 1000;
+
+#[derive(Debug, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in=Query)]
+pub struct StreamPriceFeedsQueryParams {
+    /// Stream the price updates for this set of price feed ids.
+    ///
+    /// This parameter can be provided multiple times to stream multiple price feeds,
+    /// for example see the following query string:
+    ///
+    /// ```
+    /// ?ids[]=a12...&ids[]=b4c...
+    /// ```
+    #[param(rename = "ids[]")]
+    #[param(example = "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43")]
+    ids: Vec<PriceIdInput>,
+
+    /// If true, include the `metadata` field in each event with additional metadata about the
+    /// price update.
+    #[serde(default)]
+    verbose: bool,
+
+    /// If true, include the binary price update in the `vaa` field of each event. This binary
+    /// data can be submitted to Pyth contracts to update the on-chain price.
+    #[serde(default)]
+    binary: bool,
+
+    /// If true, also stream updates for slots that complete after a newer slot has already been
+    /// streamed.
+    #[serde(default)]
+    allow_out_of_order: bool,
+}
+
+/// Stream price updates by price feed id as Server-Sent Events.
+///
+/// Each `price_update` event contains a price feed in the same format as `/api/latest_price_feeds`
+/// and has the slot of the update as its id. Clients that reconnect with a `Last-Event-ID` header
+/// receive the updates of the slots after it that are still in the cache first, unless it is more
+/// than 1000 slots behind. Clients that fall behind receive a `skipped` event with the number of
+/// slots skipped before resuming from the latest slot.
+#[utoipa::path(
+    get,
+    path = "/api/price_feeds/stream",
+    responses(
+        (
+            status = 200,
+            description = "Price updates streamed successfully",
+            body = RpcPriceFeed,
+            content_type = "text/event-stream",
+        )
+    ),
+    params(
+        StreamPriceFeedsQueryParams
+    )
+)]
+pub async fn price_feeds_stream(
+    AxumState(state): AxumState<super::ApiState>,
+    client: Option<Extension<Arc<ApiClient>>>,
+    headers: HeaderMap,
+    QsQuery(params): QsQuery<StreamPriceFeedsQueryParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, RestError> {
+    // Held for as long as the stream is open.
+    let subscription = auth::subscribe(client, &state.state)?;
+
+    let price_ids: Vec<PriceIdentifier> = params.ids.into_iter().map(|id| id.into()).collect();
+    let available_price_ids = crate::aggregate::get_price_feed_ids(&*state.state).await;
+    let missing_ids: Vec<PriceIdentifier> = price_ids
+        .iter()
+        .filter(|price_id| !available_price_ids.contains(price_id))
+        .cloned()
+        .collect();
+    if !missing_ids.is_empty() {
+        return Err(RestError::PriceIdsNotFound { missing_ids });
+    }
+
+    // Register for notifications before looking up the slots to replay, so that no slot falls in
+    // between the two.
+    let (id, notifications) = state.ws.subscribe(SubscriberKind::Sse);
+
+    let last_event_id = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Slot>().ok());
+    let replay_slots = replay_slots(&state.state, last_event_id)
+        .await
+        .map_err(|_| RestError::UpdateDataNotFound)?;
+    tracing::debug!(
+        id,
+        ?last_event_id,
+        replay_slots = replay_slots.len(),
+        "New SSE Connection"
+    );
+
+    let stream = PriceFeedsStream::new(
+        id,
+        state.state.clone(),
+        price_ids,
+        StreamConfig {
+            verbose:            params.verbose,
+            binary:             params.binary,
+            allow_out_of_order: params.allow_out_of_order,
+        },
+        replay_slots,
+        notifications,
+    );
+
+    Ok(Sse::new(futures::stream::unfold(
+        (stream, subscription),
+        |(mut stream, subscription)| async move {
+            stream
+                .next_event()
+                .await
+                .map(|event| (Ok::<_, Infallible>(event), (stream, subscription)))
+        },
+    ))
+    .keep_alive(KeepAlive::default()))
+}
+
+/// The slots to replay to a client whose last event id is `last_event_id`: the stored slots after
+/// it, unless it is more than `MAX_REPLAY_SLOTS` slots behind the newest stored slot, in which case
+/// it starts from the live updates.
+async fn replay_slots(store: &State, last_event_id: Option<Slot>) -> Result<Vec<Slot>> {
+    let last_event_id = match last_event_id {
+        Some(last_event_id) => last_event_id,
+        None => return Ok(vec![]),
+    };
+
+    // The stored slots are distinct, so fetching one slot more than the window is enough to tell
+    // whether the client is too far behind.
+    let replay_slots = store
+        .fetch_wormhole_merkle_state_slots_after(last_event_id, MAX_REPLAY_SLOTS + 1)
+        .await?;
+    match replay_slots.last() {
+        Some(newest) if newest - last_event_id > MAX_REPLAY_SLOTS as Slot => Ok(vec![]),
+        _ => Ok(replay_slots),
+    }
+}
+
+struct StreamConfig {
+    verbose:            bool,
+    binary:             bool,
+    allow_out_of_order: bool,
+}
+
+/// The state of a single SSE connection. When the client disconnects the stream is dropped, which
+/// closes the notification channel and removes it from the subscribers on the next update.
+struct PriceFeedsStream {
+    id:             SubscriberId,
+    store:          Arc<State>,
+    price_ids:      Vec<PriceIdentifier>,
+    config:         StreamConfig,
+    replay_slots:   VecDeque<Slot>,
+    /// Slots that are replayed, so that their live notifications can be skipped.
+    replayed_slots: HashSet<Slot>,
+    notifications:  Notifications,
+    pending_events: VecDeque<Event>,
+}
+
+impl PriceFeedsStream {
+    fn new(
+        id: SubscriberId,
+        store: Arc<State>,
+        price_ids: Vec<PriceIdentifier>,
+        config: StreamConfig,
+        replay_slots: Vec<Slot>,
+        notifications: Notifications,
+    ) -> Self {
+        store.metrics.sse_subscribers.inc();
+        Self {
+            id,
+            store,
+            price_ids,
+            config,
+            replayed_slots: replay_slots.iter().cloned().collect(),
+            replay_slots: replay_slots.into(),
+            notifications,
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Wait for the next event to send to the client. Returns `None` once the notification
+    /// channel is closed, which ends the stream.
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Some(event);
+            }
+
+            let slot = match self.replay_slots.pop_front() {
+                Some(slot) => slot,
+                None => {
+                    let event = match self.notifications.recv().await? {
+                        Notification::Event(event) => event,
+                        Notification::Skipped { num_slots, latest } => {
+                            self.pending_events.extend(skipped_event(num_slots));
+                            latest
+                        }
+                    };
+                    if self.replayed_slots.contains(&event.slot()) {
+                        continue;
+                    }
+                    if let AggregationEvent::OutOfOrder { slot: _ } = event {
+                        if !self.config.allow_out_of_order {
+                            continue;
+                        }
+                    }
+                    event.slot()
+                }
+            };
+
+            let events = self.events_at_slot(slot).await;
+            self.pending_events.extend(events);
+        }
+    }
+
+    async fn events_at_slot(&self, slot: Slot) -> VecDeque<Event> {
+        let price_feeds_with_update_data = match crate::aggregate::get_price_feeds_with_update_data(
+            &*self.store,
+            self.price_ids.clone(),
+            RequestTime::AtSlot(slot),
+        )
+        .await
+        {
+            Ok(price_feeds_with_update_data) => price_feeds_with_update_data,
+            Err(e) => {
+                // Replayed slots might not be complete or might have been evicted in the meantime.
+                tracing::debug!(
+                    subscriber = self.id,
+                    slot,
+                    error = ?e,
+                    "Failed to get price feeds for slot."
+                );
+                return VecDeque::new();
+            }
+        };
+
+        price_feeds_with_update_data
+            .price_feeds
+            .into_iter()
+            .filter_map(|update| {
+                Event::default()
+                    .event(PRICE_UPDATE_EVENT)
+                    .id(slot.to_string())
+                    .json_data(RpcPriceFeed::from_price_feed_update(
+                        update,
+                        self.config.verbose,
+                        self.config.binary,
+                    ))
+                    .map_err(|e| {
+                        tracing::warn!(subscriber = self.id, error = ?e, "Failed to encode event.");
+                    })
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+/// The event telling the client that it fell behind and `num_slots` slots were skipped.
+fn skipped_event(num_slots: u64) -> Option<Event> {
+    Event::default()
+        .event(SKIPPED_EVENT)
+        .json_data(serde_json::json!({ "num_slots": num_slots }))
+        .map_err(|e| {
+            tracing::warn!(error = ?e, "Failed to encode event.");
+        })
+        .ok()
+}
+
+impl Drop for PriceFeedsStream {
+    fn drop(&mut self) {
+        tracing::debug!(id = self.id, "SSE Connection Closed");
+        self.store.metrics.sse_subscribers.dec();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            aggregate::test::{
+                create_dummy_price_feed_message,
+                generate_update,
+                store_multiple_concurrent_valid_updates,
+            },
+            api::{
+                ws::{
+                    notify_updates,
+                    NOTIFICATIONS_CHAN_LEN,
+                },
+                ApiState,
+            },
+            state::test::setup_state,
+        },
+        axum::{
+            body::{
+                BoxBody,
+                HttpBody,
+            },
+            response::IntoResponse,
+        },
+        pythnet_sdk::messages::Message,
+    };
+
+    /// An event as received by the client.
+    #[derive(Debug, Default)]
+    struct ReceivedEvent {
+        event: String,
+        id:    Option<String>,
+        data:  serde_json::Value,
+    }
+
+    /// Store an update of the price feed `100` at `slot`.
+    async fn store_slot(state: &Arc<State>, slot: Slot) {
+        store_multiple_concurrent_valid_updates(
+            state.clone(),
+            generate_update(
+                vec![Message::PriceFeedMessage(create_dummy_price_feed_message(
+                    100,
+                    slot as i64,
+                    slot as i64 - 1,
+                ))],
+                slot,
+                slot,
+            ),
+        )
+        .await;
+    }
+
+    async fn setup_api_state(slots: &[Slot]) -> ApiState {
+        let (state, _) = setup_state(10).await;
+        for slot in slots {
+            store_slot(&state, *slot).await;
+        }
+        ApiState::new(state, None)
+    }
+
+    /// Open a stream of the price feed `100` and return the body it is sent in.
+    async fn open_stream(
+        state: &ApiState,
+        last_event_id: Option<Slot>,
+        allow_out_of_order: bool,
+    ) -> BoxBody {
+        let mut headers = HeaderMap::new();
+        if let Some(last_event_id) = last_event_id {
+            headers.insert(
+                LAST_EVENT_ID_HEADER,
+                last_event_id.to_string().parse().unwrap(),
+            );
+        }
+        let query = format!(
+            "ids[]={}&allow_out_of_order={allow_out_of_order}",
+            hex::encode([100; 32])
+        );
+
+        match price_feeds_stream(
+            AxumState(state.clone()),
+            None,
+            headers,
+            QsQuery(serde_qs::from_str(&query).unwrap()),
+        )
+        .await
+        {
+            Ok(sse) => sse.into_response().into_body(),
+            Err(_) => panic!("Failed to open the stream."),
+        }
+    }
+
+    /// Read the next event from the body of a stream. Every event is sent in its own chunk.
+    async fn next_event(body: &mut BoxBody) -> ReceivedEvent {
+        let chunk = body.data().await.unwrap().unwrap();
+        let mut event = ReceivedEvent::default();
+        for line in std::str::from_utf8(&chunk).unwrap().lines() {
+            match line.split_once(':') {
+                Some(("event", value)) => event.event = value.trim_start().to_string(),
+                Some(("id", value)) => event.id = Some(value.trim_start().to_string()),
+                Some(("data", value)) => event.data = serde_json::from_str(value).unwrap(),
+                _ => {}
+            }
+        }
+        event
+    }
+
+    /// Read the next event and check that it is the price update of the feed `100` at `slot`.
+    async fn assert_next_price_update(body: &mut BoxBody, slot: Slot) {
+        let event = next_event(body).await;
+        assert_eq!(event.event, PRICE_UPDATE_EVENT);
+        assert_eq!(event.id, Some(slot.to_string()));
+        assert_eq!(event.data["id"], hex::encode([100; 32]));
+        assert_eq!(event.data["price"]["publish_time"], slot);
+    }
+
+    #[tokio::test]
+    async fn test_replay_slots() {
+        let state = setup_api_state(&[10, 11]).await;
+
+        let replay = |last_event_id| replay_slots(&state.state, last_event_id);
+        assert_eq!(replay(None).await.unwrap(), Vec::<Slot>::new());
+        assert_eq!(replay(Some(9)).await.unwrap(), vec![10, 11]);
+        assert_eq!(replay(Some(10)).await.unwrap(), vec![11]);
+        assert_eq!(replay(Some(11)).await.unwrap(), Vec::<Slot>::new());
+
+        // Only clients that are at most `MAX_REPLAY_SLOTS` slots behind get replayed.
+        let newest = 11 + MAX_REPLAY_SLOTS as Slot;
+        store_slot(&state.state, newest).await;
+        assert_eq!(replay(Some(10)).await.unwrap(), Vec::<Slot>::new());
+        assert_eq!(replay(Some(11)).await.unwrap(), vec![newest]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_replays_slots_after_last_event_id() {
+        let state = setup_api_state(&[10, 11, 12]).await;
+
+        let mut body = open_stream(&state, Some(10), false).await;
+
+        // The live notifications of the replayed slots are not sent again.
+        store_slot(&state.state, 13).await;
+        for slot in [11, 12, 13] {
+            notify_updates(&state.ws, AggregationEvent::New { slot });
+        }
+
+        for slot in [11, 12, 13] {
+            assert_next_price_update(&mut body, slot).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_does_not_replay_too_old_last_event_id() {
+        let newest = 11 + MAX_REPLAY_SLOTS as Slot;
+        let state = setup_api_state(&[10, 11, newest]).await;
+
+        let mut body = open_stream(&state, Some(10), false).await;
+
+        notify_updates(&state.ws, AggregationEvent::New { slot: newest });
+        assert_next_price_update(&mut body, newest).await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_filters_out_of_order_slots() {
+        let state = setup_api_state(&[10, 11]).await;
+
+        let mut in_order = open_stream(&state, None, false).await;
+        let mut out_of_order = open_stream(&state, None, true).await;
+
+        notify_updates(&state.ws, AggregationEvent::OutOfOrder { slot: 10 });
+        notify_updates(&state.ws, AggregationEvent::New { slot: 11 });
+
+        assert_next_price_update(&mut in_order, 11).await;
+
+        assert_next_price_update(&mut out_of_order, 10).await;
+        assert_next_price_update(&mut out_of_order, 11).await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_skipped_slots() {
+        let state = setup_api_state(&[10, 11]).await;
+
+        let mut body = open_stream(&state, None, false).await;
+
+        // Fill the queue of the subscriber, so that the two next updates are dropped.
+        for _ in 0..NOTIFICATIONS_CHAN_LEN {
+            notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        }
+        notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        notify_updates(&state.ws, AggregationEvent::New { slot: 11 });
+
+        // The subscriber resumes from the newest update, skipping everything else.
+        let event = next_event(&mut body).await;
+        assert_eq!(event.event, SKIPPED_EVENT);
+        assert_eq!(event.id, None);
+        assert_eq!(
+            event.data,
+            serde_json::json!({ "num_slots": NOTIFICATIONS_CHAN_LEN + 1 })
+        );
+        assert_next_price_update(&mut body, 11).await;
+
+        notify_updates(&state.ws, AggregationEvent::New { slot: 10 });
+        assert_next_price_update(&mut body, 10).await;
+    }
+}
@@ -1,11 +1,14 @@
 use {
     crate::{
         aggregate::{
+            feed_health::FeedStatus,
             PriceFeedUpdate,
             Slot,
+            TwapUpdate,
             UnixTimestamp,
         },
         doc_examples,
+        state::feed_metadata::FeedMetadata,
     },
     base64::{
         engine::general_purpose::STANDARD as base64_standard_engine,
@@ -20,6 +23,7 @@ use {
         DerefMut,
     },
     pyth_sdk::PriceIdentifier,
+    pythnet_sdk::messages::TwapMessage,
     serde::{
         Deserialize,
         Serialize,
@@ -46,7 +50,66 @@ impl From<PriceIdInput> for PriceIdentifier {
     }
 }
 
-type Base64String = String;
+/// Binary data that is serialized as a base64 string in human-readable formats such as JSON and
+/// as raw bytes in binary formats such as CBOR or MessagePack.
+#[derive(Clone, Debug, PartialEq, Eq, Deref)]
+pub struct Binary(pub Vec<u8>);
+
+impl Serialize for Binary {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64_standard_engine.encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Binary {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BinaryVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BinaryVisitor {
+            type Value = Binary;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a base64 string or bytes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Binary, E> {
+                base64_standard_engine
+                    .decode(v)
+                    .map(Binary)
+                    .map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Binary, E> {
+                Ok(Binary(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Binary, E> {
+                Ok(Binary(v))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Binary, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Binary(bytes))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BinaryVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BinaryVisitor)
+        }
+    }
+}
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, ToSchema)]
 pub struct RpcPriceFeedMetadata {
@@ -67,10 +130,10 @@ pub struct RpcPriceFeed {
     pub ema_price: RpcPrice,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata:  Option<RpcPriceFeedMetadata>,
-    /// The VAA binary represented as a base64 string.
+    /// The VAA binary represented as a base64 string, or as raw bytes in binary encodings.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schema(value_type = Option<String>, example=doc_examples::vaa_example)]
-    pub vaa:       Option<Base64String>,
+    pub vaa:       Option<Binary>,
 }
 
 impl RpcPriceFeed {
@@ -105,10 +168,96 @@ impl RpcPriceFeed {
             }),
             vaa:       match binary {
                 false => None,
-                true => price_feed_update
-                    .update_data
-                    .map(|data| base64_standard_engine.encode(data)),
+                true => price_feed_update.update_data.map(Binary),
+            },
+        }
+    }
+}
+
+/// The running sums published by the accumulator at one end of a TWAP window.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, ToSchema)]
+pub struct RpcTwapMessage {
+    /// Sum of the price over all slots up to `publish_slot`, stored as a string to avoid
+    /// precision loss.
+    #[serde(with = "pyth_sdk::utils::as_string")]
+    #[schema(value_type = String, example="3426537958912839218")]
+    pub cumulative_price:  i128,
+    /// Sum of the confidence interval over all slots up to `publish_slot`, stored as a string to
+    /// avoid precision loss.
+    #[serde(with = "pyth_sdk::utils::as_string")]
+    #[schema(value_type = String, example="582963541247")]
+    pub cumulative_conf:   u128,
+    /// Number of slots up to `publish_slot` in which the price was not updated.
+    #[schema(example = 3)]
+    pub num_down_slots:    u64,
+    #[schema(example=-8)]
+    pub expo:              i32,
+    #[schema(value_type = i64, example=doc_examples::timestamp_example)]
+    pub publish_time:      UnixTimestamp,
+    #[schema(value_type = i64, example=doc_examples::timestamp_example)]
+    pub prev_publish_time: UnixTimestamp,
+    #[schema(value_type = u64, example=85480034)]
+    pub publish_slot:      Slot,
+}
+
+impl From<TwapMessage> for RpcTwapMessage {
+    fn from(message: TwapMessage) -> Self {
+        Self {
+            cumulative_price:  message.cumulative_price,
+            cumulative_conf:   message.cumulative_conf,
+            num_down_slots:    message.num_down_slots,
+            expo:              message.exponent,
+            publish_time:      message.publish_time,
+            prev_publish_time: message.prev_publish_time,
+            publish_slot:      message.publish_slot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, ToSchema)]
+pub struct RpcTwap {
+    pub id:               RpcPriceIdentifier,
+    /// The time-weighted average price and confidence over the window. The `publish_time` is the
+    /// publish time of the end of the window.
+    pub twap:             RpcPrice,
+    /// Fraction of the slots in the window in which the price was not updated.
+    #[schema(example = 0.01)]
+    pub down_slots_ratio: f64,
+    /// The TWAP message at the start of the window.
+    pub start:            RpcTwapMessage,
+    /// The TWAP message at the end of the window.
+    pub end:              RpcTwapMessage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata:         Option<RpcPriceFeedMetadata>,
+    /// The update data for the start and end messages, each represented as a base64 string, or as
+    /// raw bytes in binary encodings. They can be submitted to Pyth contracts to verify the TWAP
+    /// on-chain.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub update_data:      Option<Vec<Binary>>,
+}
+
+impl RpcTwap {
+    pub fn from_twap_update(twap_update: TwapUpdate, verbose: bool, binary: bool) -> Self {
+        Self {
+            id:               RpcPriceIdentifier::new(twap_update.price_id.to_bytes()),
+            twap:             RpcPrice {
+                price:        twap_update.twap,
+                conf:         twap_update.conf,
+                expo:         twap_update.exponent,
+                publish_time: twap_update.end.publish_time,
             },
+            down_slots_ratio: twap_update.down_slots_ratio,
+            start:            twap_update.start.into(),
+            end:              twap_update.end.into(),
+            metadata:         verbose.then_some(RpcPriceFeedMetadata {
+                emitter_chain:              Chain::Pythnet.into(),
+                price_service_receive_time: Some(twap_update.received_at),
+                slot:                       Some(twap_update.end_slot),
+                prev_publish_time:          Some(twap_update.end.prev_publish_time),
+            }),
+            update_data:      binary
+                .then(|| twap_update.update_data.into_iter().map(Binary).collect()),
         }
     }
 }
@@ -133,12 +282,14 @@ impl RpcPriceFeed {
     ToSchema,
 )]
 pub struct RpcPrice {
-    /// The price itself, stored as a string to avoid precision loss
-    #[serde(with = "pyth_sdk::utils::as_string")]
+    /// The price itself, stored as a string to avoid precision loss. Binary encodings store it as
+    /// an integer.
+    #[serde(with = "crate::serde::as_string")]
     #[schema(value_type = String, example="2920679499999")]
     pub price:        i64,
-    /// The confidence interval associated with the price, stored as a string to avoid precision loss
-    #[serde(with = "pyth_sdk::utils::as_string")]
+    /// The confidence interval associated with the price, stored as a string to avoid precision
+    /// loss. Binary encodings store it as an integer.
+    #[serde(with = "crate::serde::as_string")]
     #[schema(value_type = String, example="509500001")]
     pub conf:         u64,
     /// The exponent associated with both the price and confidence interval. Multiply those values
@@ -151,6 +302,71 @@ pub struct RpcPrice {
     pub publish_time: UnixTimestamp,
 }
 
+/// Metadata about a price feed, as read from its Pyth product account.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, ToSchema)]
+pub struct RpcFeedMetadata {
+    pub id:          RpcPriceIdentifier,
+    #[schema(example = "Crypto.BTC/USD")]
+    pub symbol:      Option<String>,
+    #[schema(example = "Crypto")]
+    pub asset_type:  Option<String>,
+    #[schema(example = "BTC")]
+    pub base:        Option<String>,
+    #[schema(example = "USD")]
+    pub quote:       Option<String>,
+    #[schema(example = "BITCOIN / US DOLLAR")]
+    pub description: Option<String>,
+}
+
+impl From<FeedMetadata> for RpcFeedMetadata {
+    fn from(metadata: FeedMetadata) -> Self {
+        Self {
+            id:          RpcPriceIdentifier::new(metadata.id.to_bytes()),
+            symbol:      metadata.symbol,
+            asset_type:  metadata.asset_type,
+            base:        metadata.base,
+            quote:       metadata.quote,
+            description: metadata.description,
+        }
+    }
+}
+/// The health of a price feed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, ToSchema)]
+pub struct RpcFeedHealth {
+    pub id:                  RpcPriceIdentifier,
+    #[schema(example = "Crypto.BTC/USD")]
+    pub symbol:              Option<String>,
+    #[schema(example = "Crypto")]
+    pub asset_type:          Option<String>,
+    /// Whether the feed has not published for longer than its staleness threshold.
+    pub stale:               bool,
+    #[schema(value_type = i64, example=doc_examples::timestamp_example)]
+    pub last_publish_time:   UnixTimestamp,
+    /// When Hermes received the last publish of the feed.
+    #[schema(value_type = i64, example=doc_examples::timestamp_example)]
+    pub last_received_at:    UnixTimestamp,
+    /// Average number of publishes per second, once the feed has published twice.
+    #[schema(example = 2.5)]
+    pub update_rate:         Option<f64>,
+    /// The staleness threshold of the feed in seconds.
+    #[schema(example = 60)]
+    pub staleness_threshold: u64,
+}
+
+impl RpcFeedHealth {
+    pub fn new(id: PriceIdentifier, status: &FeedStatus, metadata: Option<&FeedMetadata>) -> Self {
+        Self {
+            id:                  RpcPriceIdentifier::from(&id),
+            symbol:              metadata.and_then(|metadata| metadata.symbol.clone()),
+            asset_type:          metadata.and_then(|metadata| metadata.asset_type.clone()),
+            stale:               status.stale,
+            last_publish_time:   status.last_publish_time,
+            last_received_at:    status.last_received_at,
+            update_rate:         status.update_rate(),
+            staleness_threshold: status.staleness_threshold.as_secs(),
+        }
+    }
+}
 
 #[derive(
     Copy,
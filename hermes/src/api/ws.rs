@@ -1,11 +1,21 @@
 use {
-    super::types::{
-        PriceIdInput,
-        RpcPriceFeed,
+    super::{
+        auth::{
+            self,
+            ApiClient,
+            SubscriptionGuard,
+        },
+        types::{
+            PriceIdInput,
+            RpcPriceFeed,
+            RpcTwap,
+        },
     },
     crate::{
         aggregate::{
+            feed_health::FeedHealthEvent,
             AggregationEvent,
+            PriceFeedUpdate,
             RequestTime,
         },
         state::State,
@@ -17,17 +27,19 @@ use {
     axum::{
         extract::{
             ws::{
+                close_code,
+                CloseFrame,
                 Message,
                 WebSocket,
                 WebSocketUpgrade,
             },
+            Extension,
             State as AxumState,
         },
         response::IntoResponse,
     },
     dashmap::DashMap,
     futures::{
-        future::join_all,
         stream::{
             SplitSink,
             SplitStream,
@@ -35,7 +47,10 @@ use {
         SinkExt,
         StreamExt,
     },
-    pyth_sdk::PriceIdentifier,
+    pyth_sdk::{
+        Price,
+        PriceIdentifier,
+    },
     serde::{
         Deserialize,
         Serialize,
@@ -48,25 +63,185 @@ use {
                 Ordering,
             },
             Arc,
+            Mutex,
         },
         time::Duration,
     },
-    tokio::sync::mpsc,
+    tokio::{
+        sync::{
+            broadcast::{
+                self,
+                error::RecvError,
+            },
+            mpsc::{
+                self,
+                error::TrySendError,
+            },
+        },
+        time::Instant,
+    },
 };
 
 pub const PING_INTERVAL_DURATION: Duration = Duration::from_secs(30);
 pub const NOTIFICATIONS_CHAN_LEN: usize = 1000;
+/// How long a subscriber can have a full notification queue before it is disconnected.
+pub const MAX_LAG_DURATION: Duration = Duration::from_secs(10);
 
 #[derive(Clone)]
 pub struct PriceFeedClientConfig {
     verbose:            bool,
     binary:             bool,
     allow_out_of_order: bool,
+    /// If set, a TWAP over this many seconds is sent along with every price update.
+    twap_window:        Option<u64>,
+    delivery_policy:    DeliveryPolicy,
+}
+
+/// Limits on how often and which price updates of a feed are sent to a client.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeliveryPolicy {
+    /// Updates are sent at most once per this interval. An update held back by it is sent when the
+    /// interval elapses, with the latest state of the feed at that time.
+    min_interval:         Option<Duration>,
+    /// Updates are only sent if the price moved at least this many basis points since the last
+    /// update sent.
+    min_price_change_bps: Option<u64>,
+    /// Updates are only sent if their confidence interval divided by their price is at most this.
+    max_conf_ratio:       Option<f64>,
+}
+
+impl DeliveryPolicy {
+    fn accepts_confidence(&self, price: &Price) -> bool {
+        match self.max_conf_ratio {
+            Some(max_conf_ratio) => {
+                price.price != 0
+                    && price.conf as f64 / price.price.unsigned_abs() as f64 <= max_conf_ratio
+            }
+            None => true,
+        }
+    }
+
+    fn accepts_price_change(&self, last_price: i64, price: i64) -> bool {
+        match self.min_price_change_bps {
+            Some(min_price_change_bps) if last_price != 0 => {
+                price.abs_diff(last_price) as u128 * 10_000
+                    >= min_price_change_bps as u128 * last_price.unsigned_abs() as u128
+            }
+            _ => true,
+        }
+    }
+}
+
+/// The last update of a feed sent to a client.
+struct DeliveredUpdate {
+    at:    Instant,
+    price: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberKind {
+    WebSocket,
+    Sse,
+    Grpc,
+}
+
+/// How far behind the updates a subscriber is. It is shared between the fan-out, which records
+/// the notifications it had to drop, and the subscriber, which resets it once it catches up.
+#[derive(Default)]
+struct Lag {
+    /// Number of notifications dropped because the queue of the subscriber was full.
+    dropped:        u64,
+    /// The newest dropped notification, which the subscriber resumes from.
+    latest_dropped: Option<AggregationEvent>,
+    /// When the first notification was dropped.
+    since:          Option<Instant>,
+    /// Whether the subscriber lagged for too long and was removed from the fan-out.
+    evicted:        bool,
+}
+
+struct SubscriberHandle {
+    kind:   SubscriberKind,
+    sender: mpsc::Sender<AggregationEvent>,
+    lag:    Arc<Mutex<Lag>>,
+}
+
+/// The notification queue of a subscriber as seen by the admin API.
+#[derive(Debug)]
+pub struct SubscriberQueue {
+    pub id:            SubscriberId,
+    pub kind:          SubscriberKind,
+    pub depth:         usize,
+    pub capacity:      usize,
+    pub dropped_slots: u64,
+    pub lagging_for:   Option<Duration>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Notification {
+    Event(AggregationEvent),
+    /// The subscriber fell behind, so `num_slots` notifications were skipped to resume from the
+    /// latest one.
+    Skipped {
+        num_slots: u64,
+        latest:    AggregationEvent,
+    },
+}
+
+/// The receiving end of the notifications of a subscriber.
+pub struct Notifications {
+    receiver: mpsc::Receiver<AggregationEvent>,
+    lag:      Arc<Mutex<Lag>>,
+}
+
+impl Notifications {
+    /// Wait for the next notification. Returns `None` once the subscriber has been removed from
+    /// the fan-out, either because it lagged for too long (see `is_evicted`) or on shutdown.
+    pub async fn recv(&mut self) -> Option<Notification> {
+        let event = self.receiver.recv().await?;
+
+        let mut lag = self.lag.lock().unwrap();
+        if lag.evicted {
+            return None;
+        }
+        if lag.since.is_none() {
+            return Some(Notification::Event(event));
+        }
+
+        // Everything still queued is stale by now, so skip it along with the dropped
+        // notifications and resume from the newest one.
+        let mut num_slots = lag.dropped;
+        let mut latest = event;
+        while let Ok(event) = self.receiver.try_recv() {
+            num_slots += 1;
+            latest = newer_event(latest, event);
+        }
+        if let Some(event) = lag.latest_dropped.take() {
+            latest = newer_event(latest, event);
+        }
+        *lag = Lag::default();
+
+        Some(Notification::Skipped { num_slots, latest })
+    }
+
+    pub fn is_evicted(&self) -> bool {
+        self.lag.lock().unwrap().evicted
+    }
+}
+
+/// The event with the higher slot, or `b` if both are at the same slot. Out of order events can be
+/// older than the events queued or dropped before them.
+fn newer_event(a: AggregationEvent, b: AggregationEvent) -> AggregationEvent {
+    if a.slot() > b.slot() {
+        a
+    } else {
+        b
+    }
 }
 
 pub struct WsState {
-    pub subscriber_counter: AtomicUsize,
-    pub subscribers:        DashMap<SubscriberId, mpsc::Sender<AggregationEvent>>,
+    subscriber_counter: AtomicUsize,
+    subscribers:        DashMap<SubscriberId, SubscriberHandle>,
 }
 
 impl WsState {
@@ -76,26 +251,107 @@ impl WsState {
             subscribers:        DashMap::new(),
         }
     }
-}
 
+    /// Register a new subscriber to the update notifications.
+    pub fn subscribe(&self, kind: SubscriberKind) -> (SubscriberId, Notifications) {
+        let id = self.subscriber_counter.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = mpsc::channel(NOTIFICATIONS_CHAN_LEN);
+        let lag = Arc::new(Mutex::new(Lag::default()));
+
+        self.subscribers.insert(
+            id,
+            SubscriberHandle {
+                kind,
+                sender,
+                lag: lag.clone(),
+            },
+        );
+
+        (id, Notifications { receiver, lag })
+    }
+
+    /// The notification queues of all subscribers, ordered by subscriber id.
+    pub fn queues(&self) -> Vec<SubscriberQueue> {
+        let now = Instant::now();
+        let mut queues: Vec<SubscriberQueue> = self
+            .subscribers
+            .iter()
+            .map(|subscriber| {
+                let lag = subscriber.lag.lock().unwrap();
+                SubscriberQueue {
+                    id:            *subscriber.key(),
+                    kind:          subscriber.kind,
+                    depth:         subscriber.sender.max_capacity() - subscriber.sender.capacity(),
+                    capacity:      subscriber.sender.max_capacity(),
+                    dropped_slots: lag.dropped,
+                    lagging_for:   lag.since.map(|since| now.saturating_duration_since(since)),
+                }
+            })
+            .collect();
+        queues.sort_by_key(|queue| queue.id);
+        queues
+    }
+}
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 enum ClientMessage {
     #[serde(rename = "subscribe")]
     Subscribe {
-        ids:                Vec<PriceIdInput>,
+        ids:                  Vec<PriceIdInput>,
         #[serde(default)]
-        verbose:            bool,
+        verbose:              bool,
         #[serde(default)]
-        binary:             bool,
+        binary:               bool,
         #[serde(default)]
-        allow_out_of_order: bool,
+        allow_out_of_order:   bool,
+        #[serde(default)]
+        twap_window:          Option<u64>,
+        #[serde(default)]
+        min_interval_ms:      Option<u64>,
+        #[serde(default)]
+        min_price_change_bps: Option<u64>,
+        #[serde(default)]
+        max_conf_ratio:       Option<f64>,
+        #[serde(default)]
+        encoding:             Option<Encoding>,
     },
     #[serde(rename = "unsubscribe")]
     Unsubscribe { ids: Vec<PriceIdInput> },
+    /// Receive an event whenever a feed goes stale or recovers.
+    #[serde(rename = "subscribe_feed_health")]
+    SubscribeFeedHealth,
+    #[serde(rename = "unsubscribe_feed_health")]
+    UnsubscribeFeedHealth,
+}
+
+/// How server messages are framed. The binary encodings have the same schema as JSON, but carry
+/// prices as integers and update data as raw bytes instead of strings.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// JSON in text frames.
+    #[default]
+    Json,
+    /// CBOR in binary frames.
+    Cbor,
+    /// MessagePack in binary frames.
+    Msgpack,
 }
 
+impl Encoding {
+    fn encode(&self, message: &ServerMessage) -> Result<Message> {
+        Ok(match self {
+            Encoding::Json => Message::Text(serde_json::to_string(message)?),
+            Encoding::Cbor => {
+                let mut buffer = vec![];
+                ciborium::into_writer(message, &mut buffer)?;
+                Message::Binary(buffer)
+            }
+            Encoding::Msgpack => Message::Binary(rmp_serde::to_vec_named(message)?),
+        })
+    }
+}
 
 #[derive(Serialize, Debug, Clone)]
 #[serde(tag = "type")]
@@ -104,6 +360,14 @@ enum ServerMessage {
     Response(ServerResponseMessage),
     #[serde(rename = "price_update")]
     PriceUpdate { price_feed: RpcPriceFeed },
+    #[serde(rename = "twap_update")]
+    TwapUpdate { twap: RpcTwap },
+    /// Sent when the subscriber fell behind and updates were skipped. The updates resume from the
+    /// latest slot.
+    #[serde(rename = "skipped")]
+    Skipped { num_slots: u64 },
+    #[serde(rename = "feed_health")]
+    FeedHealth { event: FeedHealthEvent },
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -118,23 +382,31 @@ enum ServerResponseMessage {
 pub async fn ws_route_handler(
     ws: WebSocketUpgrade,
     AxumState(state): AxumState<super::ApiState>,
+    client: Option<Extension<Arc<ApiClient>>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|socket| websocket_handler(socket, state))
+    // The subscription is reserved before the upgrade so that clients over their limit get a 429.
+    let subscription = match auth::subscribe(client, &state.state) {
+        Ok(subscription) => subscription,
+        Err(e) => return e.into_response(),
+    };
+    ws.on_upgrade(|socket| websocket_handler(socket, state, subscription))
 }
 
-#[tracing::instrument(skip(stream, state))]
-async fn websocket_handler(stream: WebSocket, state: super::ApiState) {
-    let ws_state = state.ws.clone();
-    let id = ws_state.subscriber_counter.fetch_add(1, Ordering::SeqCst);
+#[tracing::instrument(skip(stream, state, _subscription))]
+async fn websocket_handler(
+    stream: WebSocket,
+    state: super::ApiState,
+    _subscription: Option<SubscriptionGuard>,
+) {
+    let (id, notifications) = state.ws.subscribe(SubscriberKind::WebSocket);
     tracing::debug!(id, "New Websocket Connection");
 
-    let (notify_sender, notify_receiver) = mpsc::channel(NOTIFICATIONS_CHAN_LEN);
     let (sender, receiver) = stream.split();
-    let mut subscriber =
-        Subscriber::new(id, state.state.clone(), notify_receiver, receiver, sender);
+    let mut subscriber = Subscriber::new(id, state.state.clone(), notifications, receiver, sender);
 
-    ws_state.subscribers.insert(id, notify_sender);
+    state.state.metrics.ws_subscribers.inc();
     subscriber.run().await;
+    state.state.metrics.ws_subscribers.dec();
 }
 
 pub type SubscriberId = usize;
@@ -145,19 +417,25 @@ pub struct Subscriber {
     id:                      SubscriberId,
     closed:                  bool,
     store:                   Arc<State>,
-    notify_receiver:         mpsc::Receiver<AggregationEvent>,
+    notifications:           Notifications,
     receiver:                SplitStream<WebSocket>,
     sender:                  SplitSink<WebSocket, Message>,
     price_feeds_with_config: HashMap<PriceIdentifier, PriceFeedClientConfig>,
     ping_interval:           tokio::time::Interval,
     responded_to_ping:       bool,
+    encoding:                Encoding,
+    delivered_updates:       HashMap<PriceIdentifier, DeliveredUpdate>,
+    /// Feeds with an update held back by their minimum interval, and when it can be sent.
+    throttled_feeds:         HashMap<PriceIdentifier, Instant>,
+    /// Feed health events, if the client subscribed to them.
+    feed_health_events:      Option<broadcast::Receiver<FeedHealthEvent>>,
 }
 
 impl Subscriber {
     pub fn new(
         id: SubscriberId,
         store: Arc<State>,
-        notify_receiver: mpsc::Receiver<AggregationEvent>,
+        notifications: Notifications,
         receiver: SplitStream<WebSocket>,
         sender: SplitSink<WebSocket, Message>,
     ) -> Self {
@@ -165,12 +443,16 @@ impl Subscriber {
             id,
             closed: false,
             store,
-            notify_receiver,
+            notifications,
             receiver,
             sender,
             price_feeds_with_config: HashMap::new(),
             ping_interval: tokio::time::interval(PING_INTERVAL_DURATION),
             responded_to_ping: true, // We start with true so we don't close the connection immediately
+            encoding: Encoding::default(),
+            delivered_updates: HashMap::new(),
+            throttled_feeds: HashMap::new(),
+            feed_health_events: None,
         }
     }
 
@@ -185,10 +467,17 @@ impl Subscriber {
     }
 
     async fn handle_next(&mut self) -> Result<()> {
+        let next_throttled_at = self.throttled_feeds.values().min().cloned();
         tokio::select! {
-            maybe_update_feeds_event = self.notify_receiver.recv() => {
-                match maybe_update_feeds_event {
-                    Some(event) => self.handle_price_feeds_update(event).await,
+            maybe_notification = self.notifications.recv() => {
+                match maybe_notification {
+                    Some(Notification::Event(event)) => self.handle_price_feeds_update(event).await,
+                    Some(Notification::Skipped { num_slots, latest }) => {
+                        let message = self.encoding.encode(&ServerMessage::Skipped { num_slots })?;
+                        self.sender.feed(message).await?;
+                        self.handle_price_feeds_update(latest).await
+                    }
+                    None if self.notifications.is_evicted() => self.close_lagging().await,
                     None => Err(anyhow!("Update channel closed. This should never happen. Closing connection."))
                 }
             },
@@ -204,10 +493,50 @@ impl Subscriber {
                 self.responded_to_ping = false;
                 self.sender.send(Message::Ping(vec![])).await?;
                 Ok(())
+            },
+            _ = tokio::time::sleep_until(next_throttled_at.unwrap_or_else(Instant::now)), if next_throttled_at.is_some() => {
+                self.handle_throttled_updates().await
+            },
+            maybe_event = next_feed_health_event(self.feed_health_events.as_mut()) => {
+                self.handle_feed_health_event(maybe_event).await
             }
         }
     }
 
+    async fn handle_feed_health_event(
+        &mut self,
+        maybe_event: Result<FeedHealthEvent, RecvError>,
+    ) -> Result<()> {
+        match maybe_event {
+            Ok(event) => {
+                let message = self.encoding.encode(&ServerMessage::FeedHealth { event })?;
+                self.sender.send(message).await?;
+            }
+            Err(RecvError::Lagged(num_events)) => {
+                tracing::debug!(
+                    subscriber = self.id,
+                    num_events,
+                    "Subscriber missed feed health events."
+                );
+            }
+            Err(RecvError::Closed) => self.feed_health_events = None,
+        }
+        Ok(())
+    }
+
+    /// Disconnect a subscriber that was evicted from the fan-out for lagging for too long.
+    async fn close_lagging(&mut self) -> Result<()> {
+        tracing::info!(subscriber = self.id, "Closing lagging subscriber.");
+        self.sender
+            .send(Message::Close(Some(CloseFrame {
+                code:   close_code::POLICY,
+                reason: "Subscriber lagged behind the updates for too long".into(),
+            })))
+            .await?;
+        self.closed = true;
+        Ok(())
+    }
+
     async fn handle_price_feeds_update(&mut self, event: AggregationEvent) -> Result<()> {
         let price_feed_ids = self.price_feeds_with_config.keys().cloned().collect();
         for update in crate::aggregate::get_price_feeds_with_update_data(
@@ -223,7 +552,8 @@ impl Subscriber {
                 .get(&update.price_feed.id)
                 .ok_or(anyhow::anyhow!(
                     "Config missing, price feed list was poisoned during iteration."
-                ))?;
+                ))?
+                .clone();
 
             if let AggregationEvent::OutOfOrder { slot: _ } = event {
                 if !config.allow_out_of_order {
@@ -231,25 +561,132 @@ impl Subscriber {
                 }
             }
 
-            // `sender.feed` buffers a message to the client but does not flush it, so we can send
-            // multiple messages and flush them all at once.
-            self.sender
-                .feed(Message::Text(serde_json::to_string(
-                    &ServerMessage::PriceUpdate {
-                        price_feed: RpcPriceFeed::from_price_feed_update(
-                            update,
-                            config.verbose,
-                            config.binary,
-                        ),
-                    },
-                )?))
-                .await?;
+            self.deliver_update(update, &config).await?;
+        }
+
+        self.sender.flush().await?;
+        Ok(())
+    }
+
+    /// Send the latest state of the feeds whose minimum interval has elapsed since an update was
+    /// held back.
+    async fn handle_throttled_updates(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let due_price_ids: Vec<PriceIdentifier> = self
+            .throttled_feeds
+            .iter()
+            .filter(|(_, throttled_until)| **throttled_until <= now)
+            .map(|(price_id, _)| *price_id)
+            .collect();
+        for price_id in &due_price_ids {
+            self.throttled_feeds.remove(price_id);
+        }
+
+        for update in crate::aggregate::get_price_feeds_with_update_data(
+            &*self.store,
+            due_price_ids,
+            RequestTime::Latest,
+        )
+        .await?
+        .price_feeds
+        {
+            if let Some(config) = self
+                .price_feeds_with_config
+                .get(&update.price_feed.id)
+                .cloned()
+            {
+                self.deliver_update(update, &config).await?;
+            }
         }
 
         self.sender.flush().await?;
         Ok(())
     }
 
+    /// Send an update if the delivery policy of the feed allows it. Updates held back by the
+    /// minimum interval are remembered so that the feed is sent again once it elapses.
+    async fn deliver_update(
+        &mut self,
+        update: PriceFeedUpdate,
+        config: &PriceFeedClientConfig,
+    ) -> Result<()> {
+        let price_id = update.price_feed.id;
+        let price = update.price_feed.get_price_unchecked();
+        let policy = &config.delivery_policy;
+
+        if !policy.accepts_confidence(&price) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        if let Some(delivered) = self.delivered_updates.get(&price_id) {
+            if !policy.accepts_price_change(delivered.price, price.price) {
+                return Ok(());
+            }
+
+            if let Some(min_interval) = policy.min_interval {
+                let throttled_until = delivered.at + min_interval;
+                if throttled_until > now {
+                    self.throttled_feeds.insert(price_id, throttled_until);
+                    return Ok(());
+                }
+            }
+        }
+
+        self.throttled_feeds.remove(&price_id);
+        self.delivered_updates.insert(
+            price_id,
+            DeliveredUpdate {
+                at:    now,
+                price: price.price,
+            },
+        );
+        self.send_update(update, config).await
+    }
+
+    /// Buffer an update, and the TWAP along with it if requested, to be sent to the client.
+    async fn send_update(
+        &mut self,
+        update: PriceFeedUpdate,
+        config: &PriceFeedClientConfig,
+    ) -> Result<()> {
+        let price_id = update.price_feed.id;
+        let publish_time = update.price_feed.get_price_unchecked().publish_time;
+        let slot = update.slot;
+
+        // `sender.feed` buffers a message to the client but does not flush it, so we can send
+        // multiple messages and flush them all at once.
+        let message = self.encoding.encode(&ServerMessage::PriceUpdate {
+            price_feed: RpcPriceFeed::from_price_feed_update(update, config.verbose, config.binary),
+        })?;
+        self.sender.feed(message).await?;
+
+        if let (Some(twap_window), Some(slot)) = (config.twap_window, slot) {
+            // The window ends at the TWAP message of this slot. Feeds that do not publish TWAP
+            // messages simply don't get TWAP updates.
+            match crate::aggregate::get_twap(
+                &*self.store,
+                price_id,
+                publish_time.saturating_sub(twap_window as i64),
+                RequestTime::AtSlot(slot),
+            )
+            .await
+            {
+                Ok(twap_update) => {
+                    let message = self.encoding.encode(&ServerMessage::TwapUpdate {
+                        twap: RpcTwap::from_twap_update(twap_update, config.verbose, config.binary),
+                    })?;
+                    self.sender.feed(message).await?;
+                }
+                Err(e) => {
+                    tracing::debug!(subscriber = self.id, error = ?e, "Failed to compute TWAP.");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self, message))]
     async fn handle_client_message(&mut self, message: Message) -> Result<()> {
         let maybe_client_message = match message {
@@ -281,16 +718,12 @@ impl Subscriber {
 
         match maybe_client_message {
             Err(e) => {
-                self.sender
-                    .send(
-                        serde_json::to_string(&ServerMessage::Response(
-                            ServerResponseMessage::Err {
-                                error: e.to_string(),
-                            },
-                        ))?
-                        .into(),
-                    )
-                    .await?;
+                let message =
+                    self.encoding
+                        .encode(&ServerMessage::Response(ServerResponseMessage::Err {
+                            error: e.to_string(),
+                        }))?;
+                self.sender.send(message).await?;
                 return Ok(());
             }
 
@@ -299,7 +732,16 @@ impl Subscriber {
                 verbose,
                 binary,
                 allow_out_of_order,
+                twap_window,
+                min_interval_ms,
+                min_price_change_bps,
+                max_conf_ratio,
+                encoding,
             }) => {
+                if let Some(encoding) = encoding {
+                    self.encoding = encoding;
+                }
+
                 let price_ids: Vec<PriceIdentifier> = ids.into_iter().map(|id| id.into()).collect();
                 let available_price_ids = crate::aggregate::get_price_feed_ids(&*self.store).await;
 
@@ -311,19 +753,15 @@ impl Subscriber {
                 // If there is a single price id that is not found, we don't subscribe to any of the
                 // asked correct price feed ids and return an error to be more explicit and clear.
                 if !not_found_price_ids.is_empty() {
-                    self.sender
-                        .send(
-                            serde_json::to_string(&ServerMessage::Response(
-                                ServerResponseMessage::Err {
-                                    error: format!(
-                                        "Price feed(s) with id(s) {:?} not found",
-                                        not_found_price_ids
-                                    ),
-                                },
-                            ))?
-                            .into(),
-                        )
-                        .await?;
+                    let message = self.encoding.encode(&ServerMessage::Response(
+                        ServerResponseMessage::Err {
+                            error: format!(
+                                "Price feed(s) with id(s) {:?} not found",
+                                not_found_price_ids
+                            ),
+                        },
+                    ))?;
+                    self.sender.send(message).await?;
                     return Ok(());
                 } else {
                     for price_id in price_ids {
@@ -333,6 +771,12 @@ impl Subscriber {
                                 verbose,
                                 binary,
                                 allow_out_of_order,
+                                twap_window,
+                                delivery_policy: DeliveryPolicy {
+                                    min_interval: min_interval_ms.map(Duration::from_millis),
+                                    min_price_change_bps,
+                                    max_conf_ratio,
+                                },
                             },
                         );
                     }
@@ -342,44 +786,346 @@ impl Subscriber {
                 for id in ids {
                     let price_id: PriceIdentifier = id.into();
                     self.price_feeds_with_config.remove(&price_id);
+                    self.delivered_updates.remove(&price_id);
+                    self.throttled_feeds.remove(&price_id);
                 }
             }
+            Ok(ClientMessage::SubscribeFeedHealth) => {
+                if self.feed_health_events.is_none() {
+                    self.feed_health_events = Some(self.store.feed_health.subscribe());
+                }
+            }
+            Ok(ClientMessage::UnsubscribeFeedHealth) => {
+                self.feed_health_events = None;
+            }
         }
 
-        self.sender
-            .send(
-                serde_json::to_string(&ServerMessage::Response(ServerResponseMessage::Success))?
-                    .into(),
-            )
-            .await?;
+        let message = self
+            .encoding
+            .encode(&ServerMessage::Response(ServerResponseMessage::Success))?;
+        self.sender.send(message).await?;
 
         Ok(())
     }
 }
 
-pub async fn notify_updates(ws_state: Arc<WsState>, event: AggregationEvent) {
-    let closed_subscribers: Vec<Option<SubscriberId>> =
-        join_all(ws_state.subscribers.iter_mut().map(|subscriber| {
-            let event = event.clone();
-            async move {
-                match subscriber.send(event).await {
-                    Ok(_) => None,
-                    Err(_) => {
-                        // An error here indicates the channel is closed (which may happen either when the
-                        // client has sent Message::Close or some other abrupt disconnection). We remove
-                        // subscribers only when send fails so we can handle closure only once when we are
-                        // able to see send() fail.
-                        Some(*subscriber.key())
+/// Wait for the next feed health event, forever if the subscriber is not subscribed to them.
+async fn next_feed_health_event(
+    receiver: Option<&mut broadcast::Receiver<FeedHealthEvent>>,
+) -> Result<FeedHealthEvent, RecvError> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => futures::future::pending().await,
+    }
+}
+
+/// Notify all subscribers of an update without waiting on any of them. A subscriber whose queue is
+/// full misses the update and is told how many it missed once it catches up. Subscribers whose
+/// queue stayed full for longer than `MAX_LAG_DURATION` are removed, which disconnects them.
+pub fn notify_updates(ws_state: &WsState, event: AggregationEvent) {
+    let now = Instant::now();
+    let removed_subscribers: Vec<SubscriberId> = ws_state
+        .subscribers
+        .iter()
+        .filter_map(
+            |subscriber| match subscriber.sender.try_send(event.clone()) {
+                Ok(()) => None,
+                Err(TrySendError::Full(event)) => {
+                    let mut lag = subscriber.lag.lock().unwrap();
+                    lag.dropped += 1;
+                    lag.latest_dropped = Some(match lag.latest_dropped.take() {
+                        Some(latest_dropped) => newer_event(latest_dropped, event),
+                        None => event,
+                    });
+                    let since = *lag.since.get_or_insert(now);
+                    if now.saturating_duration_since(since) < MAX_LAG_DURATION {
+                        return None;
                     }
+
+                    tracing::warn!(
+                        subscriber = *subscriber.key(),
+                        dropped = lag.dropped,
+                        "Evicting lagging subscriber."
+                    );
+                    lag.evicted = true;
+                    Some(*subscriber.key())
                 }
-            }
-        }))
-        .await;
+                // An error here indicates the channel is closed (which may happen either when the
+                // client has sent Message::Close or some other abrupt disconnection). We remove
+                // subscribers only when send fails so we can handle closure only once when we are
+                // able to see send() fail.
+                Err(TrySendError::Closed(_)) => Some(*subscriber.key()),
+            },
+        )
+        .collect();
+
+    for id in removed_subscribers {
+        ws_state.subscribers.remove(&id);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use {
+        super::*,
+        crate::api::types::{
+            Binary,
+            RpcPrice,
+            RpcPriceIdentifier,
+        },
+    };
+
+    /// Pretend the queues of all subscribers have been full for too long, so that the ones that
+    /// are still lagging are evicted on the next update.
+    pub fn expire_lag(ws_state: &WsState) {
+        for subscriber in ws_state.subscribers.iter() {
+            subscriber.lag.lock().unwrap().since = Instant::now().checked_sub(MAX_LAG_DURATION);
+        }
+    }
+
+    fn price(price: i64, conf: u64) -> Price {
+        Price {
+            price,
+            conf,
+            expo: -8,
+            publish_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_delivery_policy_filters_confidence() {
+        let policy = DeliveryPolicy {
+            max_conf_ratio: Some(0.01),
+            ..Default::default()
+        };
 
-    // Remove closed_subscribers from ws_state
-    closed_subscribers.into_iter().for_each(|id| {
-        if let Some(id) = id {
-            ws_state.subscribers.remove(&id);
+        assert!(policy.accepts_confidence(&price(1000, 10)));
+        assert!(policy.accepts_confidence(&price(-1000, 10)));
+        assert!(!policy.accepts_confidence(&price(1000, 11)));
+        assert!(!policy.accepts_confidence(&price(0, 0)));
+        assert!(DeliveryPolicy::default().accepts_confidence(&price(0, 100)));
+    }
+
+    #[test]
+    fn test_delivery_policy_filters_price_change() {
+        let policy = DeliveryPolicy {
+            min_price_change_bps: Some(50),
+            ..Default::default()
+        };
+
+        assert!(policy.accepts_price_change(10_000, 10_050));
+        assert!(policy.accepts_price_change(10_000, 9_950));
+        assert!(!policy.accepts_price_change(10_000, 10_049));
+        assert!(!policy.accepts_price_change(-10_000, -10_049));
+        assert!(policy.accepts_price_change(0, 1));
+        assert!(DeliveryPolicy::default().accepts_price_change(10_000, 10_000));
+    }
+
+    fn price_update_message() -> ServerMessage {
+        let rpc_price = RpcPrice {
+            price:        -12345,
+            conf:         67,
+            expo:         -8,
+            publish_time: 10,
+        };
+
+        ServerMessage::PriceUpdate {
+            price_feed: RpcPriceFeed {
+                id:        RpcPriceIdentifier::new([1; 32]),
+                price:     rpc_price,
+                ema_price: rpc_price,
+                metadata:  None,
+                vaa:       Some(Binary(vec![1, 2, 3])),
+            },
+        }
+    }
+
+    #[test]
+    fn test_json_encoding_uses_strings() {
+        let message = Encoding::Json.encode(&price_update_message()).unwrap();
+        let Message::Text(text) = message else {
+            panic!("Expected a text message");
+        };
+
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["type"], "price_update");
+        assert_eq!(value["price_feed"]["price"]["price"], "-12345");
+        assert_eq!(value["price_feed"]["vaa"], "AQID");
+    }
+
+    #[test]
+    fn test_feed_health_message() {
+        let message = Encoding::Json
+            .encode(&ServerMessage::FeedHealth {
+                event: FeedHealthEvent::Stale {
+                    id:                  PriceIdentifier::new([1; 32]),
+                    last_publish_time:   1000,
+                    staleness_threshold: 60,
+                },
+            })
+            .unwrap();
+        let Message::Text(text) = message else {
+            panic!("Expected a text message");
+        };
+
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["type"], "feed_health");
+        assert_eq!(value["event"]["status"], "stale");
+        assert_eq!(value["event"]["id"], hex::encode([1; 32]));
+        assert_eq!(value["event"]["staleness_threshold"], 60);
+    }
+
+    #[test]
+    fn test_cbor_encoding_uses_integers_and_bytes() {
+        let message = Encoding::Cbor.encode(&price_update_message()).unwrap();
+        let Message::Binary(data) = message else {
+            panic!("Expected a binary message");
+        };
+
+        let value: ciborium::value::Value = ciborium::de::from_reader(data.as_slice()).unwrap();
+        let get = |value: &ciborium::value::Value, key: &str| {
+            value
+                .as_map()
+                .unwrap()
+                .iter()
+                .find(|(k, _)| k.as_text() == Some(key))
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+
+        assert_eq!(get(&value, "type").as_text(), Some("price_update"));
+        let price_feed = get(&value, "price_feed");
+        let price = get(&get(&price_feed, "price"), "price");
+        assert_eq!(price.as_integer().map(i128::from), Some(-12345));
+        assert_eq!(get(&price_feed, "vaa").as_bytes(), Some(&vec![1u8, 2, 3]));
+    }
+
+    #[test]
+    fn test_msgpack_encoding_uses_binary_messages() {
+        let message = Encoding::Msgpack.encode(&price_update_message()).unwrap();
+        let Message::Binary(data) = message else {
+            panic!("Expected a binary message");
+        };
+
+        #[derive(Deserialize)]
+        struct DecodedPrice {
+            price: i64,
+        }
+        #[derive(Deserialize)]
+        struct DecodedPriceFeed {
+            price: DecodedPrice,
+            vaa:   Binary,
+        }
+        #[derive(Deserialize)]
+        struct DecodedPriceUpdate {
+            #[serde(rename = "type")]
+            type_:      String,
+            price_feed: DecodedPriceFeed,
+        }
+
+        let update: DecodedPriceUpdate = rmp_serde::from_slice(&data).unwrap();
+        assert_eq!(update.type_, "price_update");
+        assert_eq!(update.price_feed.price.price, -12345);
+        assert_eq!(update.price_feed.vaa, Binary(vec![1, 2, 3]));
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_skips_to_the_latest_slot() {
+        let ws_state = WsState::new();
+        let (_, mut slow) = ws_state.subscribe(SubscriberKind::WebSocket);
+        let (_, mut fast) = ws_state.subscribe(SubscriberKind::Sse);
+
+        let num_slots = NOTIFICATIONS_CHAN_LEN as u64 + 5;
+        for slot in 0..num_slots {
+            notify_updates(&ws_state, AggregationEvent::New { slot });
+            assert_eq!(
+                fast.recv().await,
+                Some(Notification::Event(AggregationEvent::New { slot }))
+            );
+        }
+
+        let queues = ws_state.queues();
+        assert_eq!(queues[0].depth, NOTIFICATIONS_CHAN_LEN);
+        assert_eq!(queues[0].dropped_slots, 5);
+        assert!(queues[0].lagging_for.is_some());
+        assert_eq!(queues[1].depth, 0);
+        assert_eq!(queues[1].dropped_slots, 0);
+
+        assert_eq!(
+            slow.recv().await,
+            Some(Notification::Skipped {
+                num_slots: num_slots - 1,
+                latest:    AggregationEvent::New {
+                    slot: num_slots - 1,
+                },
+            })
+        );
+        assert_eq!(ws_state.queues()[0].lagging_for, None);
+
+        notify_updates(&ws_state, AggregationEvent::New { slot: num_slots });
+        assert_eq!(
+            slow.recv().await,
+            Some(Notification::Event(AggregationEvent::New {
+                slot: num_slots,
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_skips_to_the_newest_slot_with_out_of_order_events() {
+        let ws_state = WsState::new();
+        let (_, mut notifications) = ws_state.subscribe(SubscriberKind::WebSocket);
+        let len = NOTIFICATIONS_CHAN_LEN as u64;
+
+        // A dropped out of order event does not replace a newer dropped event.
+        for slot in 0..len {
+            notify_updates(&ws_state, AggregationEvent::New { slot });
         }
-    });
+        notify_updates(&ws_state, AggregationEvent::New { slot: len + 10 });
+        notify_updates(&ws_state, AggregationEvent::OutOfOrder { slot: len + 5 });
+        assert_eq!(
+            notifications.recv().await,
+            Some(Notification::Skipped {
+                num_slots: len + 1,
+                latest:    AggregationEvent::New { slot: len + 10 },
+            })
+        );
+
+        // A dropped out of order event does not replace a newer queued event.
+        for slot in 0..len - 1 {
+            notify_updates(&ws_state, AggregationEvent::New { slot });
+        }
+        notify_updates(&ws_state, AggregationEvent::New { slot: len + 20 });
+        notify_updates(&ws_state, AggregationEvent::OutOfOrder { slot: len + 15 });
+        assert_eq!(
+            notifications.recv().await,
+            Some(Notification::Skipped {
+                num_slots: len,
+                latest:    AggregationEvent::New { slot: len + 20 },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_is_evicted() {
+        let ws_state = WsState::new();
+        let (_, mut notifications) = ws_state.subscribe(SubscriberKind::WebSocket);
+
+        for slot in 0..=NOTIFICATIONS_CHAN_LEN as u64 {
+            notify_updates(&ws_state, AggregationEvent::New { slot });
+        }
+        assert_eq!(ws_state.queues().len(), 1);
+
+        expire_lag(&ws_state);
+        notify_updates(
+            &ws_state,
+            AggregationEvent::New {
+                slot: NOTIFICATIONS_CHAN_LEN as u64 + 1,
+            },
+        );
+
+        assert!(ws_state.queues().is_empty());
+        assert_eq!(notifications.recv().await, None);
+        assert!(notifications.is_evicted());
+    }
 }
@@ -1,16 +1,23 @@
-use clap::{
-    crate_authors,
-    crate_description,
-    crate_name,
-    crate_version,
-    Args,
-    Parser,
+use {
+    clap::{
+        crate_authors,
+        crate_description,
+        crate_name,
+        crate_version,
+        Args,
+        Parser,
+        ValueEnum,
+    },
+    std::path::PathBuf,
 };
 
 mod benchmarks;
+pub mod feed_health;
 mod pythnet;
-mod rpc;
-mod wormhole;
+mod record;
+pub mod rpc;
+mod storage;
+pub mod wormhole;
 
 // `Options` is a structup definition to provide clean command-line args for Hermes.
 #[derive(Parser, Debug)]
@@ -25,6 +32,9 @@ pub enum Options {
 
     /// Show Overridden Environment Variables.
     ShowEnv(ShowEnvOptions),
+
+    /// Replay a recording made with `--record-path` while serving the API.
+    Replay(ReplayOptions),
 }
 
 #[derive(Args, Clone, Debug)]
@@ -44,6 +54,18 @@ pub struct RunOptions {
     /// Benchmarks Options
     #[command(flatten)]
     pub benchmarks: benchmarks::Options,
+
+    /// Storage Options
+    #[command(flatten)]
+    pub storage: storage::Options,
+
+    /// Record Options
+    #[command(flatten)]
+    pub record: record::Options,
+
+    /// Feed Health Options
+    #[command(flatten)]
+    pub feed_health: feed_health::Options,
 }
 
 #[derive(Args, Clone, Debug)]
@@ -56,3 +78,31 @@ pub struct ShowEnvOptions {
     #[arg(long = "defaults")]
     pub defaults: bool,
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReplaySpeed {
+    /// Replay the updates with the delays they were received with.
+    Original,
+    /// Replay the updates as fast as possible.
+    Max,
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct ReplayOptions {
+    /// Path of the recording to replay.
+    pub path: PathBuf,
+
+    /// How fast to replay the recording.
+    #[arg(long = "speed")]
+    #[arg(value_enum)]
+    #[arg(default_value = "original")]
+    pub speed: ReplaySpeed,
+
+    /// RPC Options
+    #[command(flatten)]
+    pub rpc: rpc::Options,
+
+    /// Feed Health Options
+    #[command(flatten)]
+    pub feed_health: feed_health::Options,
+}
@@ -0,0 +1,51 @@
+use {
+    clap::Args,
+    std::{
+        str::FromStr,
+        time::Duration,
+    },
+};
+
+const DEFAULT_STALENESS_THRESHOLD: &str = "60s";
+
+/// A staleness threshold for the feeds of an asset type, written as `<asset type>=<duration>`,
+/// e.g. `fx=5m`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetTypeThreshold {
+    pub asset_type: String,
+    pub threshold:  Duration,
+}
+
+impl FromStr for AssetTypeThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (asset_type, threshold) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <asset type>=<duration>, got {}", s))?;
+        Ok(Self {
+            asset_type: asset_type.trim().to_lowercase(),
+            threshold:  humantime::parse_duration(threshold.trim()).map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+#[command(next_help_heading = "Feed Health Options")]
+#[group(id = "FeedHealth")]
+pub struct Options {
+    /// How long a feed can go without publishing before it is considered stale, e.g. "60s".
+    #[arg(long = "feed-staleness-threshold")]
+    #[arg(default_value = DEFAULT_STALENESS_THRESHOLD)]
+    #[arg(env = "FEED_STALENESS_THRESHOLD")]
+    pub staleness_threshold: humantime::Duration,
+
+    /// Staleness thresholds overriding the default one for the feeds of some asset types, as
+    /// `<asset type>=<duration>` pairs separated by comma, e.g. "fx=5m,equity=1h".
+    ///
+    /// Asset types are read from the feed metadata and are compared ignoring case.
+    #[arg(long = "feed-staleness-thresholds")]
+    #[arg(value_delimiter = ',')]
+    #[arg(env = "FEED_STALENESS_THRESHOLDS")]
+    pub asset_type_thresholds: Vec<AssetTypeThreshold>,
+}
@@ -1,16 +1,44 @@
-use clap::Args;
+use {
+    clap::Args,
+    solana_sdk::pubkey::Pubkey,
+    std::path::PathBuf,
+};
+
+const DEFAULT_ORACLE_PROGRAM_ADDR: &str = "FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH";
 
 #[derive(Args, Clone, Debug)]
 #[command(next_help_heading = "Pythnet Options")]
 #[group(id = "Pythnet")]
 pub struct Options {
-    /// Address of a PythNet compatible websocket RPC endpoint.
+    /// Addresses of PythNet compatible websocket RPC endpoints.
+    ///
+    /// Hermes subscribes to all of them at once and keeps working as long as one is healthy. Can
+    /// be provided multiple times or as a comma separated list.
     #[arg(long = "pythnet-ws-addr")]
     #[arg(env = "PYTHNET_WS_ENDPOINT")]
-    pub ws_endpoint: String,
+    #[arg(value_delimiter = ',')]
+    #[arg(required = true)]
+    pub ws_endpoints: Vec<String>,
 
-    /// Addres of a PythNet compatible HTP RPC endpoint.
+    /// Addresses of PythNet compatible HTTP RPC endpoints.
+    ///
+    /// They are tried in order until one of them responds. Can be provided multiple times or as a
+    /// comma separated list.
     #[arg(long = "pythnet-http-addr")]
     #[arg(env = "PYTHNET_HTTP_ENDPOINT")]
-    pub http_endpoint: String,
+    #[arg(value_delimiter = ',')]
+    #[arg(required = true)]
+    pub http_endpoints: Vec<String>,
+
+    /// Address of the Pyth oracle program on PythNet, whose product accounts hold the metadata of
+    /// the price feeds.
+    #[arg(long = "pythnet-oracle-program-addr")]
+    #[arg(default_value = DEFAULT_ORACLE_PROGRAM_ADDR)]
+    #[arg(env = "PYTHNET_ORACLE_PROGRAM_ADDR")]
+    pub oracle_program_addr: Pubkey,
+
+    /// Path of a JSON file overriding the metadata of the price feeds read from PythNet.
+    #[arg(long = "feed-metadata-path")]
+    #[arg(env = "FEED_METADATA_PATH")]
+    pub feed_metadata_path: Option<PathBuf>,
 }
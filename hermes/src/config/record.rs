@@ -0,0 +1,15 @@
+use {
+    clap::Args,
+    std::path::PathBuf,
+};
+
+#[derive(Args, Clone, Debug)]
+#[command(next_help_heading = "Record Options")]
+#[group(id = "Record")]
+pub struct Options {
+    /// Path of a file to append every received update to, with the time it was received. The
+    /// recording can be replayed with `hermes replay`. Nothing is recorded if not set.
+    #[arg(long = "record-path")]
+    #[arg(env = "RECORD_PATH")]
+    pub path: Option<PathBuf>,
+}
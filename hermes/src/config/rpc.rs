@@ -1,9 +1,14 @@
 use {
     clap::Args,
-    std::net::SocketAddr,
+    std::{
+        net::SocketAddr,
+        path::PathBuf,
+    },
 };
 
 const DEFAULT_RPC_ADDR: &str = "127.0.0.1:33999";
+const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:33888";
+const DEFAULT_GRPC_ADDR: &str = "127.0.0.1:33777";
 
 #[derive(Args, Clone, Debug)]
 #[command(next_help_heading = "RPC Options")]
@@ -14,4 +19,22 @@ pub struct Options {
     #[arg(default_value = DEFAULT_RPC_ADDR)]
     #[arg(env = "RPC_ADDR")]
     pub addr: SocketAddr,
+
+    /// Address and port the Prometheus metrics server will bind to.
+    #[arg(long = "metrics-listen-addr")]
+    #[arg(default_value = DEFAULT_METRICS_ADDR)]
+    #[arg(env = "METRICS_ADDR")]
+    pub metrics_addr: SocketAddr,
+
+    /// Address and port the gRPC server will bind to.
+    #[arg(long = "grpc-listen-addr")]
+    #[arg(default_value = DEFAULT_GRPC_ADDR)]
+    #[arg(env = "GRPC_ADDR")]
+    pub grpc_addr: SocketAddr,
+
+    /// Path of a JSON file listing the API keys allowed to use the API and their limits. The API
+    /// is open to everyone if not set.
+    #[arg(long = "api-keys-path")]
+    #[arg(env = "API_KEYS_PATH")]
+    pub api_keys_path: Option<PathBuf>,
 }
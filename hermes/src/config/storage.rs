@@ -0,0 +1,30 @@
+use {
+    clap::Args,
+    std::path::PathBuf,
+};
+
+const DEFAULT_STORAGE_RETENTION: &str = "1h";
+
+#[derive(Args, Clone, Debug)]
+#[command(next_help_heading = "Storage Options")]
+#[group(id = "Storage")]
+pub struct Options {
+    /// Path of the on-disk database used to persist the cache. If not set, the cache is kept in
+    /// memory only and is lost on restart.
+    #[arg(long = "storage-path")]
+    #[arg(env = "STORAGE_PATH")]
+    pub path: Option<PathBuf>,
+
+    /// How long updates are kept on disk, e.g. "30m" or "6h".
+    #[arg(long = "storage-retention")]
+    #[arg(default_value = DEFAULT_STORAGE_RETENTION)]
+    #[arg(env = "STORAGE_RETENTION")]
+    pub retention: humantime::Duration,
+
+    /// Maximum size of the on-disk database in bytes. The oldest updates are pruned first when it
+    /// grows beyond this size. This is a soft limit that the database can exceed for a few
+    /// minutes while pruning catches up.
+    #[arg(long = "storage-max-size")]
+    #[arg(env = "STORAGE_MAX_SIZE")]
+    pub max_size: Option<u64>,
+}
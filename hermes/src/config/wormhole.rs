@@ -1,17 +1,34 @@
 use {
-    clap::Args,
+    clap::{
+        Args,
+        ValueEnum,
+    },
     libp2p::Multiaddr,
+    reqwest::Url,
     solana_sdk::pubkey::Pubkey,
 };
 
 const DEFAULT_LISTEN_ADDRS: &str = "/ip4/0.0.0.0/udp/30910/quic,/ip6/::/udp/30910/quic";
 const DEFAULT_CONTRACT_ADDR: &str = "H3fxXJ86ADW2PNuDDmZJg6mzTtPxkYCpNuQUTgmJ7AjU";
+const DEFAULT_VAA_SOURCES: &str = "p2p";
+const DEFAULT_GUARDIAN_HTTP_POLL_INTERVAL: &str = "1s";
 const DEFAULT_NETWORK_ID: &str = "/wormhole/mainnet/2";
 const DEFAULT_BOOTSTRAP_ADDRS: &str = concat![
     "/dns4/wormhole-mainnet-v2-bootstrap.certus.one/udp/8999/quic/p2p/12D3KooWQp644DK27fd3d4Km3jr7gHiuJJ5ZGmy8hH4py7fP4FP7,",
     "/dns4/wormhole-v2-mainnet-bootstrap.xlabs.xyz/udp/8999/quic/p2p/12D3KooWNQ9tVrcb64tw6bNs2CaNrUGPM7yRrKvBBheQ5yCyPHKC",
 ];
 
+/// The ways Hermes can receive VAAs from the Wormhole network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum VaaSourceKind {
+    /// Join the Wormhole gossip network.
+    P2p,
+    /// Stream signed VAAs from a Wormhole spy over gRPC.
+    Spy,
+    /// Poll guardian REST endpoints for signed VAAs.
+    Http,
+}
+
 #[derive(Args, Clone, Debug)]
 #[command(next_help_heading = "Wormhole Options")]
 #[group(id = "Wormhole")]
@@ -45,4 +62,41 @@ pub struct Options {
     #[arg(default_value = DEFAULT_NETWORK_ID)]
     #[arg(env = "WORMHOLE_NETWORK_ID")]
     pub network_id: String,
+
+    /// Sources to receive VAAs from (separated by comma).
+    ///
+    /// Any combination of `p2p`, `spy` and `http` can be enabled. A VAA received from more than
+    /// one source is only processed once.
+    #[arg(long = "wormhole-vaa-sources")]
+    #[arg(value_delimiter = ',')]
+    #[arg(default_value = DEFAULT_VAA_SOURCES)]
+    #[arg(env = "WORMHOLE_VAA_SOURCES")]
+    pub vaa_sources: Vec<VaaSourceKind>,
+
+    /// Address of the Wormhole spy gRPC endpoint used by the `spy` source.
+    #[arg(long = "wormhole-spy-addr")]
+    #[arg(env = "WORMHOLE_SPY_ADDR")]
+    pub spy_addr: Option<String>,
+
+    /// Addresses of the guardian REST endpoints used by the `http` source (separated by comma).
+    ///
+    /// They are tried in order until one of them responds.
+    #[arg(long = "wormhole-guardian-http-addrs")]
+    #[arg(value_delimiter = ',')]
+    #[arg(env = "WORMHOLE_GUARDIAN_HTTP_ADDRS")]
+    pub guardian_http_addrs: Vec<Url>,
+
+    /// How often the `http` source polls the guardian REST endpoints, e.g. "500ms" or "2s".
+    #[arg(long = "wormhole-guardian-http-poll-interval")]
+    #[arg(default_value = DEFAULT_GUARDIAN_HTTP_POLL_INTERVAL)]
+    #[arg(env = "WORMHOLE_GUARDIAN_HTTP_POLL_INTERVAL")]
+    pub guardian_http_poll_interval: humantime::Duration,
+
+    /// Sequence of the first VAA the `http` source fetches.
+    ///
+    /// Only needed when it is the only source, as it otherwise continues from the latest VAA
+    /// observed from the other sources.
+    #[arg(long = "wormhole-guardian-http-start-sequence")]
+    #[arg(env = "WORMHOLE_GUARDIAN_HTTP_START_SEQUENCE")]
+    pub guardian_http_start_sequence: Option<u64>,
 }
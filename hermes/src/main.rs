@@ -1,8 +1,22 @@
 #![feature(never_type)]
 #![feature(btree_cursors)]
+#![cfg_attr(test, feature(test))]
+
+#[cfg(test)]
+extern crate test;
 
 use {
-    crate::state::State,
+    crate::{
+        replay::Recorder,
+        state::{
+            cache::{
+                disk::DiskCache,
+                AggregateCache,
+                Cache,
+            },
+            State,
+        },
+    },
     anyhow::Result,
     clap::{
         CommandFactory,
@@ -16,11 +30,14 @@ use {
     tokio::spawn,
 };
 
+mod admin;
 mod aggregate;
 mod api;
 mod config;
 mod doc_examples;
+mod metrics;
 mod network;
+mod replay;
 mod serde;
 mod state;
 mod wormhole;
@@ -33,6 +50,16 @@ mod wormhole;
 // we don't rely on global state for anything else.
 pub(crate) static SHOULD_EXIT: AtomicBool = AtomicBool::new(false);
 
+/// Listen for Ctrl+C so we can set the exit flag and wait for a graceful shutdown.
+fn spawn_shutdown_handler() {
+    spawn(async move {
+        tracing::info!("Registered shutdown signal handler...");
+        tokio::signal::ctrl_c().await.unwrap();
+        tracing::info!("Shut down signal received, waiting for tasks...");
+        SHOULD_EXIT.store(true, std::sync::atomic::Ordering::Release);
+    });
+}
+
 /// Initialize the Application. This can be invoked either by real main, or by the Geyser plugin.
 #[tracing::instrument]
 async fn init() -> Result<()> {
@@ -47,23 +74,49 @@ async fn init() -> Result<()> {
             // The update channel is used to send store update notifications to the public API.
             let (update_tx, update_rx) = tokio::sync::mpsc::channel(1000);
 
-            // Initialize a cache store with a 1000 element circular buffer.
-            let store = State::new(update_tx.clone(), 1000, opts.benchmarks.endpoint.clone());
-
-            // Listen for Ctrl+C so we can set the exit flag and wait for a graceful shutdown.
-            spawn(async move {
-                tracing::info!("Registered shutdown signal handler...");
-                tokio::signal::ctrl_c().await.unwrap();
-                tracing::info!("Shut down signal received, waiting for tasks...");
-                SHOULD_EXIT.store(true, std::sync::atomic::Ordering::Release);
-            });
+            // Initialize a cache store with a 1000 element circular buffer, backed by an on-disk
+            // database if a storage path is configured.
+            let cache: Box<dyn AggregateCache + Send + Sync> = match opts.storage.path {
+                Some(ref path) => Box::new(
+                    DiskCache::open(
+                        path,
+                        1000,
+                        opts.storage.retention.into(),
+                        opts.storage.max_size,
+                    )
+                    .await?,
+                ),
+                None => Box::new(Cache::new(1000)),
+            };
+            // Append every received update to a recording if a record path is configured.
+            let recorder = match opts.record.path {
+                Some(ref path) => Some(Recorder::open(path).await?),
+                None => None,
+            };
+
+            let store = State::new(
+                update_tx.clone(),
+                cache,
+                opts.benchmarks.endpoint.clone(),
+                recorder,
+            );
+
+            spawn_shutdown_handler();
 
             // Spawn all worker tasks, and wait for all to complete (which will happen if a shutdown
             // signal has been observed).
             let tasks = join_all([
-                Box::pin(spawn(network::p2p::spawn(opts.clone(), store.clone()))),
+                Box::pin(spawn(network::spawn_vaa_sources(
+                    opts.clone(),
+                    store.clone(),
+                ))),
                 Box::pin(spawn(network::pythnet::spawn(opts.clone(), store.clone()))),
-                Box::pin(spawn(api::run(opts.clone(), store.clone(), update_rx))),
+                Box::pin(spawn(api::run(opts.rpc.clone(), store.clone(), update_rx))),
+                Box::pin(spawn(metrics::run(opts.rpc.clone(), store.clone()))),
+                Box::pin(spawn(aggregate::feed_health::run(
+                    opts.feed_health.clone(),
+                    store.clone(),
+                ))),
             ])
             .await;
 
@@ -72,6 +125,12 @@ async fn init() -> Result<()> {
             }
         }
 
+        config::Options::Replay(opts) => {
+            tracing::info!("Replaying {}...", opts.path.display());
+            spawn_shutdown_handler();
+            replay::run(opts).await?;
+        }
+
         config::Options::ShowEnv(opts) => {
             // For each subcommand, scan for arguments that allow overriding with an ENV variable
             // and print that variable.
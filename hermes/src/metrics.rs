@@ -0,0 +1,404 @@
+//! Prometheus metrics for Hermes.
+//!
+//! All metrics live in a single `Metrics` struct that is owned by the application `State`. Most of
+//! them are updated in place by the code they measure, the ones that are cheap to derive from the
+//! state (such as the cache size per feed) are refreshed on every scrape instead.
+
+use {
+    crate::{
+        config::rpc,
+        state::{
+            cache::AggregateCache,
+            State,
+        },
+    },
+    anyhow::Result,
+    axum::{
+        extract::State as AxumState,
+        http::{
+            header,
+            StatusCode,
+        },
+        response::IntoResponse,
+        routing::get,
+        Router,
+    },
+    prometheus_client::{
+        encoding::{
+            text::encode,
+            EncodeLabelSet,
+            EncodeLabelValue,
+        },
+        metrics::{
+            counter::Counter,
+            family::Family,
+            gauge::Gauge,
+            histogram::{
+                exponential_buckets,
+                Histogram,
+            },
+        },
+        registry::Registry,
+    },
+    std::sync::{
+        atomic::Ordering,
+        Arc,
+    },
+    tokio::signal,
+};
+
+const METRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum VaaRejectionReason {
+    /// The VAA could not be deserialized.
+    Malformed,
+    /// The VAA has already been observed.
+    Duplicate,
+    /// The VAA signatures could not be verified against a known guardian set.
+    InvalidSignature,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct VaaRejectionLabels {
+    pub reason: VaaRejectionReason,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum SlotOrder {
+    New,
+    OutOfOrder,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CompletedSlotLabels {
+    pub order: SlotOrder,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RestRouteLabels {
+    pub route:  String,
+    pub status: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CacheSizeLabels {
+    pub feed_id:      String,
+    pub message_type: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct PythnetEndpointLabels {
+    pub endpoint: String,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum QuarantineAlertKind {
+    /// Sources delivered different accumulator messages for a slot.
+    Conflict,
+    /// No accumulator messages of a slot match the root signed by the guardians.
+    Mismatch,
+    /// Accumulator messages matching the signed root replaced the quarantined ones.
+    Resolved,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct QuarantineAlertLabels {
+    pub kind: QuarantineAlertKind,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ApiKeyLabels {
+    /// The name of the client the API key belongs to, never the key itself.
+    pub api_key: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum ApiKeyRequestOutcome {
+    Allowed,
+    RateLimited,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ApiKeyRequestLabels {
+    pub api_key: String,
+    pub outcome: ApiKeyRequestOutcome,
+}
+
+type HistogramConstructor = fn() -> Histogram;
+
+pub struct Metrics {
+    registry: Registry,
+
+    /// VAAs from the accumulator emitter seen on the Wormhole network.
+    pub vaas_observed:             Counter,
+    /// VAAs that passed signature verification.
+    pub vaas_verified:             Counter,
+    /// VAAs that were dropped, labeled by the reason.
+    pub vaas_rejected:             Family<VaaRejectionLabels, Counter>,
+    /// Accumulator messages received from Pythnet.
+    pub accumulator_updates:       Counter,
+    /// Slots for which both the accumulator messages and the VAA have been received.
+    pub completed_slots:           Family<CompletedSlotLabels, Counter>,
+    /// Difference between the latest observed slot and the latest completed slot.
+    pub slot_lag:                  Gauge,
+    /// Number of live WebSocket subscribers.
+    pub ws_subscribers:            Gauge,
+    /// Number of live Server-Sent Events subscribers.
+    pub sse_subscribers:           Gauge,
+    /// Number of live gRPC price feed subscribers.
+    pub grpc_subscribers:          Gauge,
+    /// Latency of REST requests in seconds, labeled by route and status code.
+    pub rest_request_duration:     Family<RestRouteLabels, Histogram, HistogramConstructor>,
+    /// Number of message states kept in the cache, labeled by feed and message type.
+    pub cache_size:                Family<CacheSizeLabels, Gauge>,
+    /// Whether each Pythnet endpoint is connected and delivering updates (1) or not (0).
+    pub pythnet_endpoint_healthy:  Family<PythnetEndpointLabels, Gauge>,
+    /// Difference between the latest slot seen on any Pythnet endpoint and on each endpoint.
+    pub pythnet_endpoint_slot_lag: Family<PythnetEndpointLabels, Gauge>,
+    /// Quarantine alerts about accumulator messages, labeled by kind.
+    pub quarantine_alerts:         Family<QuarantineAlertLabels, Counter>,
+    /// Requests made with each API key, labeled by whether they were allowed or rate limited.
+    pub api_key_requests:          Family<ApiKeyRequestLabels, Counter>,
+    /// Number of live WebSocket, Server-Sent Events and gRPC subscriptions of each API key.
+    pub api_key_subscriptions:     Family<ApiKeyLabels, Gauge>,
+    /// Number of price feeds that have not published within their staleness threshold.
+    pub stale_feeds:               Gauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("hermes");
+
+        let vaas_observed = Counter::default();
+        let vaas_verified = Counter::default();
+        let vaas_rejected = Family::<VaaRejectionLabels, Counter>::default();
+        let accumulator_updates = Counter::default();
+        let completed_slots = Family::<CompletedSlotLabels, Counter>::default();
+        let slot_lag = Gauge::default();
+        let ws_subscribers = Gauge::default();
+        let sse_subscribers = Gauge::default();
+        let grpc_subscribers = Gauge::default();
+        let rest_request_duration =
+            Family::<RestRouteLabels, Histogram, HistogramConstructor>::new_with_constructor(
+                || Histogram::new(exponential_buckets(0.0005, 2.0, 16)),
+            );
+        let cache_size = Family::<CacheSizeLabels, Gauge>::default();
+        let pythnet_endpoint_healthy = Family::<PythnetEndpointLabels, Gauge>::default();
+        let pythnet_endpoint_slot_lag = Family::<PythnetEndpointLabels, Gauge>::default();
+        let quarantine_alerts = Family::<QuarantineAlertLabels, Counter>::default();
+        let api_key_requests = Family::<ApiKeyRequestLabels, Counter>::default();
+        let api_key_subscriptions = Family::<ApiKeyLabels, Gauge>::default();
+        let stale_feeds = Gauge::default();
+
+        registry.register(
+            "vaas_observed",
+            "Number of accumulator VAAs observed",
+            vaas_observed.clone(),
+        );
+        registry.register(
+            "vaas_verified",
+            "Number of accumulator VAAs that passed verification",
+            vaas_verified.clone(),
+        );
+        registry.register(
+            "vaas_rejected",
+            "Number of accumulator VAAs rejected, by reason",
+            vaas_rejected.clone(),
+        );
+        registry.register(
+            "accumulator_updates",
+            "Number of accumulator messages received from Pythnet",
+            accumulator_updates.clone(),
+        );
+        registry.register(
+            "completed_slots",
+            "Number of slots with both accumulator messages and a VAA",
+            completed_slots.clone(),
+        );
+        registry.register(
+            "slot_lag",
+            "Latest observed slot minus latest completed slot",
+            slot_lag.clone(),
+        );
+        registry.register(
+            "ws_subscribers",
+            "Number of live WebSocket subscribers",
+            ws_subscribers.clone(),
+        );
+        registry.register(
+            "sse_subscribers",
+            "Number of live Server-Sent Events subscribers",
+            sse_subscribers.clone(),
+        );
+        registry.register(
+            "grpc_subscribers",
+            "Number of live gRPC price feed subscribers",
+            grpc_subscribers.clone(),
+        );
+        registry.register(
+            "rest_request_duration_seconds",
+            "Latency of REST requests, by route and status code",
+            rest_request_duration.clone(),
+        );
+        registry.register(
+            "cache_size",
+            "Number of message states in the cache, by feed and message type",
+            cache_size.clone(),
+        );
+        registry.register(
+            "pythnet_endpoint_healthy",
+            "Whether a Pythnet endpoint is delivering updates, by endpoint",
+            pythnet_endpoint_healthy.clone(),
+        );
+        registry.register(
+            "pythnet_endpoint_slot_lag",
+            "Latest slot seen on any Pythnet endpoint minus latest slot seen on an endpoint",
+            pythnet_endpoint_slot_lag.clone(),
+        );
+        registry.register(
+            "quarantine_alerts",
+            "Number of quarantine alerts about accumulator messages, by kind",
+            quarantine_alerts.clone(),
+        );
+        registry.register(
+            "api_key_requests",
+            "Number of API requests, by API key and outcome",
+            api_key_requests.clone(),
+        );
+        registry.register(
+            "api_key_subscriptions",
+            "Number of live subscriptions, by API key",
+            api_key_subscriptions.clone(),
+        );
+        registry.register(
+            "stale_feeds",
+            "Number of price feeds past their staleness threshold",
+            stale_feeds.clone(),
+        );
+
+        Self {
+            registry,
+            vaas_observed,
+            vaas_verified,
+            vaas_rejected,
+            accumulator_updates,
+            completed_slots,
+            slot_lag,
+            ws_subscribers,
+            sse_subscribers,
+            grpc_subscribers,
+            rest_request_duration,
+            cache_size,
+            pythnet_endpoint_healthy,
+            pythnet_endpoint_slot_lag,
+            quarantine_alerts,
+            api_key_requests,
+            api_key_subscriptions,
+            stale_feeds,
+        }
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry)?;
+        Ok(buffer)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Refresh the metrics that are derived from the state rather than updated in place.
+async fn refresh(state: &State) {
+    state.metrics.cache_size.clear();
+    for (key, size) in state.message_state_counts().await {
+        state
+            .metrics
+            .cache_size
+            .get_or_create(&CacheSizeLabels {
+                feed_id:      hex::encode(key.feed_id),
+                message_type: key.type_.to_string(),
+            })
+            .set(size as i64);
+    }
+}
+
+async fn metrics(AxumState(state): AxumState<Arc<State>>) -> impl IntoResponse {
+    refresh(&state).await;
+
+    match state.metrics.encode() {
+        Ok(body) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to encode metrics.");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Serve the metrics and the admin endpoints on their own address so they are not exposed with the
+/// public API.
+#[tracing::instrument(skip(opts, state))]
+pub async fn run(opts: rpc::Options, state: Arc<State>) -> Result<()> {
+    tracing::info!(endpoint = %opts.metrics_addr, "Starting Metrics Server.");
+
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .merge(crate::admin::routes())
+        .with_state(state);
+
+    axum::Server::try_bind(&opts.metrics_addr)?
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            let _ = signal::ctrl_c().await;
+            crate::SHOULD_EXIT.store(true, Ordering::Release);
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::{
+            cache::test::create_and_store_dummy_price_feed_message_state,
+            test::setup_state,
+        },
+    };
+
+    #[tokio::test]
+    pub async fn test_metrics_are_encoded() {
+        let (state, _) = setup_state(2).await;
+
+        state.metrics.vaas_observed.inc();
+        state
+            .metrics
+            .vaas_rejected
+            .get_or_create(&VaaRejectionLabels {
+                reason: VaaRejectionReason::Duplicate,
+            })
+            .inc();
+
+        create_and_store_dummy_price_feed_message_state(&*state, [1; 32], 10, 5).await;
+        create_and_store_dummy_price_feed_message_state(&*state, [1; 32], 11, 6).await;
+        refresh(&state).await;
+
+        let encoded = state.metrics.encode().unwrap();
+        assert!(encoded.contains("hermes_vaas_observed_total 1\n"));
+        assert!(encoded.contains("hermes_vaas_rejected_total{reason=\"Duplicate\"} 1\n"));
+        assert!(encoded.contains(&format!(
+            "hermes_cache_size{{feed_id=\"{}\",message_type=\"PriceFeedMessage\"}} 2\n",
+            hex::encode([1; 32])
+        )));
+    }
+}
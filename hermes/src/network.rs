@@ -1,2 +1,58 @@
+use {
+    crate::{
+        config::{
+            wormhole::VaaSourceKind,
+            RunOptions,
+        },
+        state::State,
+        wormhole::{
+            forward_vaas_from_sources,
+            VaaSource,
+        },
+    },
+    anyhow::{
+        anyhow,
+        Result,
+    },
+    std::sync::Arc,
+};
+
+pub mod guardian_http;
 pub mod p2p;
 pub mod pythnet;
+pub mod spy;
+
+/// Start the VAA sources enabled in the options and forward the VAAs they receive in the
+/// background.
+#[tracing::instrument(skip(opts, state))]
+pub async fn spawn_vaa_sources(opts: RunOptions, state: Arc<State>) -> Result<()> {
+    let mut sources: Vec<Box<dyn VaaSource>> = vec![];
+    for kind in &opts.wormhole.vaa_sources {
+        sources.push(match kind {
+            VaaSourceKind::P2p => Box::new(p2p::P2pVaaSource::new(opts.wormhole.clone())),
+            VaaSourceKind::Spy => Box::new(spy::SpyVaaSource::new(
+                opts.wormhole
+                    .spy_addr
+                    .clone()
+                    .ok_or(anyhow!("The spy VAA source requires --wormhole-spy-addr."))?,
+            )),
+            VaaSourceKind::Http => {
+                if opts.wormhole.guardian_http_addrs.is_empty() {
+                    return Err(anyhow!(
+                        "The http VAA source requires --wormhole-guardian-http-addrs."
+                    ));
+                }
+                Box::new(guardian_http::GuardianHttpVaaSource::new(
+                    opts.wormhole.guardian_http_addrs.clone(),
+                    state.clone(),
+                    opts.wormhole.guardian_http_poll_interval.into(),
+                    opts.wormhole.guardian_http_start_sequence,
+                )?)
+            }
+        });
+    }
+
+    tokio::spawn(forward_vaas_from_sources(state, sources));
+
+    Ok(())
+}
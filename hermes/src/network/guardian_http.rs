@@ -0,0 +1,329 @@
+//! This module polls guardian REST endpoints for the signed VAAs of the accumulator emitter.
+//!
+//! VAAs of an emitter have consecutive sequence numbers, so the poller fetches them one after the
+//! other, starting after the latest sequence observed from any source. It also fetches the
+//! sequences missing in between the observed ones, which fills the gaps left by the other sources.
+
+use {
+    crate::{
+        state::State,
+        wormhole::{
+            VaaBytes,
+            VaaSource,
+        },
+    },
+    anyhow::{
+        anyhow,
+        Result,
+    },
+    base64::{
+        engine::general_purpose::STANDARD as base64_standard_engine,
+        Engine as _,
+    },
+    reqwest::{
+        StatusCode,
+        Url,
+    },
+    std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::Ordering,
+            Arc,
+        },
+        time::Duration,
+    },
+    tokio::sync::mpsc,
+};
+
+/// The Wormhole chain id of Pythnet.
+const PYTHNET_CHAIN_ID: u16 = 26;
+
+/// The maximum number of missing sequences fetched per poll, so that a large gap does not delay
+/// following the new VAAs for too long.
+const MAX_BACKFILL_PER_POLL: usize = 100;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignedVaaResponse {
+    vaa_bytes: String,
+}
+
+/// The sequences missing in between the observed ones, oldest first.
+fn missing_sequences(observed: &BTreeSet<u64>, limit: usize) -> Vec<u64> {
+    let (first, last) = match (observed.first(), observed.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return vec![],
+    };
+
+    (first..last)
+        .filter(|sequence| !observed.contains(sequence))
+        .take(limit)
+        .collect()
+}
+
+/// Polls guardian REST endpoints for the VAAs of the accumulator emitter.
+pub struct GuardianHttpVaaSource {
+    client:        reqwest::Client,
+    endpoints:     Vec<Url>,
+    state:         Arc<State>,
+    poll_interval: Duration,
+    /// The next sequence to fetch, if it is known yet.
+    next_sequence: Option<u64>,
+}
+
+impl GuardianHttpVaaSource {
+    pub fn new(
+        endpoints: Vec<Url>,
+        state: Arc<State>,
+        poll_interval: Duration,
+        start_sequence: Option<u64>,
+    ) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()?,
+            endpoints,
+            state,
+            poll_interval,
+            next_sequence: start_sequence,
+        })
+    }
+
+    /// Fetch the VAA with the given sequence, trying the endpoints in order until one of them
+    /// returns it. Returns `None` if every endpoint reports that the VAA has not been signed yet.
+    async fn fetch_vaa(&self, sequence: u64) -> Result<Option<VaaBytes>> {
+        let path = format!(
+            "v1/signed_vaa/{}/{}/{}",
+            PYTHNET_CHAIN_ID,
+            hex::encode(pythnet_sdk::ACCUMULATOR_EMITTER_ADDRESS),
+            sequence
+        );
+
+        // A guardian that is behind the others does not have the VAA yet, so a 404 only means
+        // that the VAA is not signed yet if all the endpoints agree.
+        let mut all_not_found = true;
+        for endpoint in &self.endpoints {
+            let response = match self.client.get(endpoint.join(&path)?).send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    tracing::warn!(%endpoint, error = ?e, "Failed to query guardian endpoint.");
+                    all_not_found = false;
+                    continue;
+                }
+            };
+
+            if response.status() == StatusCode::NOT_FOUND {
+                continue;
+            }
+
+            match response.error_for_status() {
+                Ok(response) => {
+                    let response = response.json::<SignedVaaResponse>().await?;
+                    return Ok(Some(base64_standard_engine.decode(response.vaa_bytes)?));
+                }
+                Err(e) => {
+                    tracing::warn!(%endpoint, error = ?e, "Failed to query guardian endpoint.");
+                    all_not_found = false;
+                }
+            }
+        }
+
+        if all_not_found {
+            return Ok(None);
+        }
+        Err(anyhow!("No guardian endpoint returned the VAA."))
+    }
+
+    /// Fetch the missing and the new VAAs once.
+    async fn poll(&mut self, vaa_sender: &mpsc::Sender<VaaBytes>) -> Result<()> {
+        let observed = self.state.observed_vaa_seqs.read().await.clone();
+
+        // A gap that cannot be fetched right now is retried on the next poll, it must not keep
+        // the new VAAs from being followed.
+        for sequence in missing_sequences(&observed, MAX_BACKFILL_PER_POLL) {
+            match self.fetch_vaa(sequence).await {
+                Ok(Some(vaa)) => {
+                    tracing::info!(sequence, "Backfilled missing VAA.");
+                    vaa_sender.send(vaa).await?;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(sequence, error = ?e, "Failed to backfill missing VAA.");
+                }
+            }
+        }
+
+        let mut next_sequence = match (self.next_sequence, observed.last()) {
+            (Some(next), Some(last)) => next.max(last + 1),
+            (next, last) => match next.or(last.map(|last| last + 1)) {
+                Some(next) => next,
+                // Nothing to follow yet.
+                None => return Ok(()),
+            },
+        };
+
+        while let Some(vaa) = self.fetch_vaa(next_sequence).await? {
+            vaa_sender.send(vaa).await?;
+            next_sequence += 1;
+            self.next_sequence = Some(next_sequence);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl VaaSource for GuardianHttpVaaSource {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    #[tracing::instrument(skip(self, vaa_sender))]
+    async fn run(mut self: Box<Self>, vaa_sender: mpsc::Sender<VaaBytes>) -> Result<()> {
+        let mut interval = tokio::time::interval(self.poll_interval);
+
+        while !crate::SHOULD_EXIT.load(Ordering::Acquire) && !vaa_sender.is_closed() {
+            interval.tick().await;
+            if let Err(e) = self.poll(&vaa_sender).await {
+                tracing::warn!(error = ?e, "Failed to poll guardian endpoints.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::test::setup_state,
+        axum::{
+            extract::Path,
+            routing::get,
+            Json,
+            Router,
+        },
+        std::ops::RangeInclusive,
+    };
+
+    #[test]
+    fn test_missing_sequences_works() {
+        assert_eq!(missing_sequences(&BTreeSet::new(), 10), Vec::<u64>::new());
+        assert_eq!(
+            missing_sequences(&[1, 2, 5, 6, 9].into_iter().collect(), 10),
+            vec![3, 4, 7, 8]
+        );
+        assert_eq!(
+            missing_sequences(&[1, 9].into_iter().collect(), 3),
+            vec![2, 3, 4]
+        );
+    }
+
+    /// Serve the VAAs with sequences in `sequences`, the VAA with a sequence is the sequence
+    /// itself.
+    async fn serve_signed_vaas(sequences: RangeInclusive<u64>) -> Url {
+        let app = Router::new().route(
+            "/v1/signed_vaa/:chain/:emitter/:sequence",
+            get(move |Path((_, _, sequence)): Path<(u16, String, u64)>| {
+                let signed = sequences.contains(&sequence);
+                async move {
+                    if signed {
+                        Ok(Json(serde_json::json!({
+                            "vaaBytes": base64_standard_engine.encode([sequence as u8]),
+                        })))
+                    } else {
+                        Err(StatusCode::NOT_FOUND)
+                    }
+                }
+            }),
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        format!("http://{addr}/").parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_poll_backfills_gaps_and_follows_new_vaas() {
+        let (state, _) = setup_state(10).await;
+        state.observed_vaa_seqs.write().await.extend([5, 7]);
+
+        // The first endpoint is behind and does not have the VAAs after sequence 6 yet.
+        let mut source = GuardianHttpVaaSource::new(
+            vec![
+                serve_signed_vaas(5..=6).await,
+                serve_signed_vaas(5..=8).await,
+            ],
+            state,
+            Duration::from_secs(1),
+            None,
+        )
+        .unwrap();
+
+        let (vaa_sender, mut vaa_receiver) = mpsc::channel(10);
+        source.poll(&vaa_sender).await.unwrap();
+        drop(vaa_sender);
+
+        let mut received = vec![];
+        while let Some(vaa) = vaa_receiver.recv().await {
+            received.push(vaa);
+        }
+        assert_eq!(received, vec![vec![6], vec![8]]);
+        assert_eq!(source.next_sequence, Some(9));
+    }
+
+    #[tokio::test]
+    async fn test_poll_follows_new_vaas_when_backfill_fails() {
+        let (state, _) = setup_state(10).await;
+        state.observed_vaa_seqs.write().await.extend([4, 6]);
+
+        // Sequence 5 cannot be fetched: the first endpoint is unreachable and the second one
+        // does not have it.
+        let unreachable: Url = "http://127.0.0.1:1/".parse().unwrap();
+        let mut source = GuardianHttpVaaSource::new(
+            vec![unreachable, serve_signed_vaas(6..=8).await],
+            state,
+            Duration::from_secs(1),
+            None,
+        )
+        .unwrap();
+
+        let (vaa_sender, mut vaa_receiver) = mpsc::channel(10);
+        // Following stops with an error at sequence 9 for the same reason.
+        assert!(source.poll(&vaa_sender).await.is_err());
+        drop(vaa_sender);
+
+        let mut received = vec![];
+        while let Some(vaa) = vaa_receiver.recv().await {
+            received.push(vaa);
+        }
+        assert_eq!(received, vec![vec![7], vec![8]]);
+        assert_eq!(source.next_sequence, Some(9));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_vaa_fails_over_unreachable_endpoints() {
+        let (state, _) = setup_state(10).await;
+
+        let unreachable: Url = "http://127.0.0.1:1/".parse().unwrap();
+        let source = GuardianHttpVaaSource::new(
+            vec![unreachable, serve_signed_vaas(5..=8).await],
+            state,
+            Duration::from_secs(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(source.fetch_vaa(5).await.unwrap(), Some(vec![5]));
+        // The unreachable endpoint might have the VAA, so it is not reported as unsigned.
+        assert!(source.fetch_vaa(9).await.is_err());
+    }
+}
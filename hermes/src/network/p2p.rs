@@ -11,27 +11,27 @@
 
 use {
     crate::{
-        config::RunOptions,
-        state::State,
+        config::wormhole,
         wormhole::{
-            forward_vaa,
             VaaBytes,
+            VaaSource,
         },
     },
-    anyhow::Result,
+    anyhow::{
+        anyhow,
+        Result,
+    },
     libp2p::Multiaddr,
     std::{
         ffi::{
             c_char,
             CString,
         },
-        sync::{
-            atomic::Ordering,
-            Arc,
-        },
+        sync::atomic::Ordering,
     },
     tokio::sync::{
         mpsc::{
+            self,
             Receiver,
             Sender,
         },
@@ -135,27 +135,37 @@ pub fn bootstrap(
     Ok(())
 }
 
-// Spawn's the P2P layer as a separate thread via Go.
-#[tracing::instrument(skip(opts, state))]
-pub async fn spawn(opts: RunOptions, state: Arc<State>) -> Result<()> {
-    tracing::info!(listeners = ?opts.wormhole.listen_addrs, "Starting P2P Server");
-
-    std::thread::spawn(|| {
-        if bootstrap(
-            opts.wormhole.network_id,
-            opts.wormhole.bootstrap_addrs,
-            opts.wormhole.listen_addrs,
-        )
-        .is_err()
-        {
-            tracing::error!("Failed to bootstrap P2P server.");
-            crate::SHOULD_EXIT.store(true, Ordering::Release);
-        }
-    });
+/// Receives VAAs from the Wormhole gossip network through the Go libp2p runtime.
+pub struct P2pVaaSource {
+    opts: wormhole::Options,
+}
+
+impl P2pVaaSource {
+    pub fn new(opts: wormhole::Options) -> Self {
+        Self { opts }
+    }
+}
 
-    tokio::spawn(async move {
-        // Listen in the background for new VAA's from the p2p layer
-        // and update the state accordingly.
+#[async_trait::async_trait]
+impl VaaSource for P2pVaaSource {
+    fn name(&self) -> &'static str {
+        "p2p"
+    }
+
+    // Spawn's the P2P layer as a separate thread via Go.
+    #[tracing::instrument(skip(self, vaa_sender))]
+    async fn run(self: Box<Self>, vaa_sender: mpsc::Sender<VaaBytes>) -> Result<()> {
+        tracing::info!(listeners = ?self.opts.listen_addrs, "Starting P2P Server");
+
+        let opts = self.opts;
+        std::thread::spawn(|| {
+            if bootstrap(opts.network_id, opts.bootstrap_addrs, opts.listen_addrs).is_err() {
+                tracing::error!("Failed to bootstrap P2P server.");
+                crate::SHOULD_EXIT.store(true, Ordering::Release);
+            }
+        });
+
+        // Listen in the background for new VAA's from the p2p layer and pass them on.
         while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
             let vaa_bytes = {
                 let mut observation = OBSERVATIONS.1.lock().await;
@@ -167,17 +177,18 @@ pub async fn spawn(opts: RunOptions, state: Arc<State>) -> Result<()> {
                         // application as it is unrecoverable.
                         tracing::error!("Failed to receive p2p observation. Channel closed.");
                         crate::SHOULD_EXIT.store(true, Ordering::Release);
-                        return Err(anyhow::anyhow!("Failed to receive p2p observation."));
+                        return Err(anyhow!("Failed to receive p2p observation."));
                     }
                 }
             };
 
-            forward_vaa(state.clone(), vaa_bytes).await;
+            vaa_sender
+                .send(vaa_bytes)
+                .await
+                .map_err(|_| anyhow!("Observation channel closed."))?;
         }
 
         tracing::info!("Shutting down P2P server...");
-        Ok::<(), anyhow::Error>(())
-    });
-
-    Ok(())
+        Ok(())
+    }
 }
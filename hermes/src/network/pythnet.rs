@@ -6,10 +6,23 @@ use {
     crate::{
         aggregate::{
             AccumulatorMessages,
+            Slot,
             Update,
         },
         config::RunOptions,
-        state::State,
+        metrics::{
+            Metrics,
+            PythnetEndpointLabels,
+        },
+        state::{
+            feed_metadata::{
+                build_catalogue,
+                load_overrides,
+                FeedMetadata,
+                FeedMetadataCatalogue,
+            },
+            State,
+        },
         wormhole::{
             update_guardian_set,
             BridgeData,
@@ -22,7 +35,15 @@ use {
         Result,
     },
     borsh::BorshDeserialize,
-    futures::stream::StreamExt,
+    futures::{
+        future::join_all,
+        stream::StreamExt,
+    },
+    pyth_sdk::PriceIdentifier,
+    pythnet_sdk::hashers::{
+        keccak256::Keccak256,
+        Hasher,
+    },
     solana_account_decoder::UiAccountEncoding,
     solana_client::{
         nonblocking::{
@@ -38,6 +59,10 @@ use {
             MemcmpEncodedBytes,
             RpcFilterType,
         },
+        rpc_response::{
+            Response as RpcResponse,
+            RpcKeyedAccount,
+        },
     },
     solana_sdk::{
         account::Account,
@@ -46,15 +71,173 @@ use {
         system_program,
     },
     std::{
+        collections::BTreeSet,
         sync::{
             atomic::Ordering,
             Arc,
         },
         time::Duration,
     },
-    tokio::time::Instant,
+    tokio::{
+        sync::RwLock,
+        time::Instant,
+    },
 };
 
+/// An endpoint that has not delivered an accumulator update for this long is considered unhealthy.
+const ENDPOINT_STALENESS_THRESHOLD: Duration = Duration::from_secs(10);
+
+/// How often the health of the endpoints is checked and reported.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The number of slots behind the latest one for which seen updates are remembered. Copies of an
+/// update older than this are dropped regardless, as the aggregate has moved on by then.
+const DEDUPLICATION_WINDOW: Slot = 1000;
+
+/// How often the metadata of the price feeds is read again from the product accounts.
+const FEED_METADATA_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// The magic number at the start of every Pyth oracle account.
+const ORACLE_ACCOUNT_MAGIC: u32 = 0xa1b2c3d4;
+
+/// The type of the product accounts in the header of the Pyth oracle accounts.
+const PRODUCT_ACCOUNT_TYPE: u32 = 2;
+
+/// The size of the header of a product account, made of the magic number, version, account type
+/// and used size as `u32`s followed by the key of its first price account.
+const PRODUCT_ACCOUNT_HEADER_SIZE: usize = 48;
+
+#[derive(Clone, Debug, Default)]
+struct EndpointStatus {
+    connected:      bool,
+    latest_slot:    Option<Slot>,
+    last_update_at: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndpointHealth {
+    pub endpoint: String,
+    pub healthy:  bool,
+    /// Slots behind the most advanced endpoint, if the endpoint has delivered any update yet.
+    pub slot_lag: Option<Slot>,
+}
+
+/// The set of Pythnet endpoints that Hermes subscribes to at once.
+///
+/// Every endpoint delivers the same accumulator updates, so only the first copy of each update is
+/// passed on to the aggregate. Copies with different contents are all passed on, so that the
+/// aggregate can quarantine them and pick the one matching the signed root. It also keeps track of
+/// how each endpoint is doing, so that a stalled endpoint can be spotted while the others keep
+/// Hermes going.
+pub struct PythnetSources {
+    endpoints: Vec<String>,
+    statuses:  RwLock<Vec<EndpointStatus>>,
+    /// Recently seen updates by slot, ring index and digest of the account data.
+    seen:      RwLock<BTreeSet<(Slot, u32, [u8; 32])>>,
+}
+
+impl PythnetSources {
+    pub fn new(endpoints: Vec<String>) -> Self {
+        Self {
+            statuses: RwLock::new(vec![EndpointStatus::default(); endpoints.len()]),
+            endpoints,
+            seen: RwLock::new(BTreeSet::new()),
+        }
+    }
+
+    async fn set_connected(&self, endpoint: usize, connected: bool) {
+        self.statuses.write().await[endpoint].connected = connected;
+    }
+
+    /// Record an update received from an endpoint. Returns true if this is the first copy of the
+    /// update with these contents, which is the one that should be stored.
+    async fn observe(&self, endpoint: usize, slot: Slot, ring_index: u32, data: &[u8]) -> bool {
+        {
+            let mut statuses = self.statuses.write().await;
+            let status = &mut statuses[endpoint];
+            status.latest_slot = status.latest_slot.max(Some(slot));
+            status.last_update_at = Some(Instant::now());
+        }
+
+        let mut seen = self.seen.write().await;
+        if let Some((latest_slot, ..)) = seen.last() {
+            if slot.saturating_add(DEDUPLICATION_WINDOW) <= *latest_slot {
+                return false;
+            }
+        }
+
+        let is_new = seen.insert((slot, ring_index, Keccak256::hashv(&[data])));
+
+        // Forget the updates that fell out of the window.
+        if let Some((latest_slot, ..)) = seen.last().cloned() {
+            let cutoff = latest_slot.saturating_sub(DEDUPLICATION_WINDOW);
+            while matches!(seen.first(), Some((seen_slot, ..)) if *seen_slot < cutoff) {
+                seen.pop_first();
+            }
+        }
+
+        is_new
+    }
+
+    pub async fn health(&self) -> Vec<EndpointHealth> {
+        let statuses = self.statuses.read().await;
+        let latest_slot = statuses
+            .iter()
+            .filter_map(|status| status.latest_slot)
+            .max();
+
+        self.endpoints
+            .iter()
+            .zip(statuses.iter())
+            .map(|(endpoint, status)| EndpointHealth {
+                endpoint: endpoint.clone(),
+                healthy:  status.connected
+                    && status
+                        .last_update_at
+                        .map(|at| at.elapsed() < ENDPOINT_STALENESS_THRESHOLD)
+                        .unwrap_or(false),
+                slot_lag: status
+                    .latest_slot
+                    .zip(latest_slot)
+                    .map(|(slot, latest_slot)| latest_slot - slot),
+            })
+            .collect()
+    }
+
+    /// Log unhealthy endpoints and export the health of every endpoint as metrics.
+    async fn report_health(&self, metrics: &Metrics) {
+        let health = self.health().await;
+
+        for endpoint in health.iter() {
+            let labels = PythnetEndpointLabels {
+                endpoint: endpoint.endpoint.clone(),
+            };
+            metrics
+                .pythnet_endpoint_healthy
+                .get_or_create(&labels)
+                .set(endpoint.healthy as i64);
+            if let Some(slot_lag) = endpoint.slot_lag {
+                metrics
+                    .pythnet_endpoint_slot_lag
+                    .get_or_create(&labels)
+                    .set(slot_lag as i64);
+            }
+
+            if !endpoint.healthy {
+                tracing::warn!(
+                    endpoint = endpoint.endpoint,
+                    slot_lag = endpoint.slot_lag,
+                    "Pythnet endpoint is unhealthy."
+                );
+            }
+        }
+
+        if !health.iter().any(|endpoint| endpoint.healthy) {
+            tracing::error!("No healthy Pythnet endpoint.");
+        }
+    }
+}
+
 /// Using a Solana RPC endpoint, fetches the target GuardianSet based on an index.
 async fn fetch_guardian_set(
     client: &RpcClient,
@@ -85,7 +268,10 @@ async fn fetch_guardian_set(
     // extract the new Signer set.
     match GuardianSetData::deserialize(&mut guardian_set.data.as_ref()) {
         Ok(guardian_set) => Ok(GuardianSet {
-            keys: guardian_set.keys,
+            keys:            guardian_set.keys,
+            // An expiration time of 0 means the set does not expire, as it is the current one.
+            expiration_time: (guardian_set.expiration_time != 0)
+                .then_some(guardian_set.expiration_time),
         }),
 
         Err(err) => Err(anyhow!(
@@ -127,8 +313,132 @@ async fn fetch_bridge_data(
     }
 }
 
-pub async fn run(store: Arc<State>, pythnet_ws_endpoint: String) -> Result<()> {
-    let client = PubsubClient::new(pythnet_ws_endpoint.as_ref()).await?;
+/// Read a string prefixed by its length as a `u8` from the attributes of a product account.
+fn read_product_attribute(attributes: &mut &[u8]) -> Result<String> {
+    let (&len, rest) = attributes
+        .split_first()
+        .ok_or_else(|| anyhow!("Truncated product attributes"))?;
+    let value = rest
+        .get(..len as usize)
+        .ok_or_else(|| anyhow!("Truncated product attributes"))?;
+    *attributes = &rest[len as usize..];
+    Ok(String::from_utf8_lossy(value).into_owned())
+}
+
+/// Parse the metadata of a price feed from a Pyth product account.
+///
+/// The product account points to the price account whose key is the id of the feed, and holds
+/// the metadata as a list of key value pairs, each string being prefixed by its length as a `u8`.
+fn parse_product_account(data: &[u8]) -> Result<FeedMetadata> {
+    let read_u32 = |offset: usize| -> Result<u32> {
+        Ok(u32::from_le_bytes(
+            data.get(offset..offset + 4)
+                .ok_or_else(|| anyhow!("Product account is too short"))?
+                .try_into()?,
+        ))
+    };
+
+    if read_u32(0)? != ORACLE_ACCOUNT_MAGIC {
+        return Err(anyhow!("Invalid oracle account magic number"));
+    }
+    if read_u32(8)? != PRODUCT_ACCOUNT_TYPE {
+        return Err(anyhow!("Not a product account"));
+    }
+
+    let size = read_u32(12)? as usize;
+    let price_account: [u8; 32] = data
+        .get(16..PRODUCT_ACCOUNT_HEADER_SIZE)
+        .ok_or_else(|| anyhow!("Product account is too short"))?
+        .try_into()?;
+    if price_account == [0; 32] {
+        return Err(anyhow!("Product account has no price account"));
+    }
+
+    let mut metadata = FeedMetadata::new(PriceIdentifier::new(price_account));
+    let mut attributes = data
+        .get(PRODUCT_ACCOUNT_HEADER_SIZE..size)
+        .ok_or_else(|| anyhow!("Invalid product account size"))?;
+    while !attributes.is_empty() {
+        let key = read_product_attribute(&mut attributes)?;
+        let value = read_product_attribute(&mut attributes)?;
+        match key.as_str() {
+            "symbol" => metadata.symbol = Some(value),
+            "asset_type" => metadata.asset_type = Some(value),
+            "base" => metadata.base = Some(value),
+            "quote_currency" => metadata.quote = Some(value),
+            "description" => metadata.description = Some(value),
+            _ => {}
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Using a Solana RPC endpoint, fetches the metadata of the price feeds from the product accounts
+/// of the Pyth oracle program.
+async fn fetch_feed_metadata(
+    client: &RpcClient,
+    oracle_program_addr: &Pubkey,
+) -> Result<Vec<FeedMetadata>> {
+    let config = RpcProgramAccountsConfig {
+        account_config: RpcAccountInfoConfig {
+            commitment: Some(CommitmentConfig::confirmed()),
+            encoding: Some(UiAccountEncoding::Base64Zstd),
+            ..Default::default()
+        },
+        filters:        Some(vec![RpcFilterType::Memcmp(Memcmp {
+            offset:   8,
+            bytes:    MemcmpEncodedBytes::Bytes(PRODUCT_ACCOUNT_TYPE.to_le_bytes().to_vec()),
+            encoding: None,
+        })]),
+        with_context:   None,
+    };
+
+    let accounts = client
+        .get_program_accounts_with_config(oracle_program_addr, config)
+        .await?;
+
+    Ok(accounts
+        .into_iter()
+        .filter_map(
+            |(pubkey, account)| match parse_product_account(&account.data) {
+                Ok(metadata) => Some(metadata),
+                Err(err) => {
+                    tracing::debug!(%pubkey, error = ?err, "Skipping product account.");
+                    None
+                }
+            },
+        )
+        .collect())
+}
+
+/// Fetch the metadata of the price feeds from the first HTTP endpoint that responds.
+async fn fetch_feed_metadata_with_failover(
+    pythnet_http_endpoints: &[String],
+    oracle_program_addr: &Pubkey,
+) -> Result<Vec<FeedMetadata>> {
+    for pythnet_http_endpoint in pythnet_http_endpoints {
+        let client = RpcClient::new(pythnet_http_endpoint.clone());
+        match fetch_feed_metadata(&client, oracle_program_addr).await {
+            Ok(metadata) => return Ok(metadata),
+            Err(err) => {
+                tracing::warn!(
+                    endpoint = pythnet_http_endpoint,
+                    error = ?err,
+                    "Failed to fetch feed metadata, trying the next endpoint."
+                );
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Failed to fetch feed metadata from any Pythnet endpoint"
+    ))
+}
+
+/// Listen to the accumulator updates of a single endpoint of `sources`.
+pub async fn run(store: Arc<State>, sources: Arc<PythnetSources>, endpoint: usize) -> Result<()> {
+    let client = PubsubClient::new(sources.endpoints[endpoint].as_ref()).await?;
 
     let config = RpcProgramAccountsConfig {
         account_config: RpcAccountInfoConfig {
@@ -149,6 +459,18 @@ pub async fn run(store: Arc<State>, pythnet_ws_endpoint: String) -> Result<()> {
         .program_subscribe(&system_program::id(), Some(config))
         .await?;
 
+    sources.set_connected(endpoint, true).await;
+    let result = listen(&store, &sources, endpoint, &mut notif).await;
+    sources.set_connected(endpoint, false).await;
+    result
+}
+
+async fn listen(
+    store: &Arc<State>,
+    sources: &PythnetSources,
+    endpoint: usize,
+    notif: &mut (impl futures::Stream<Item = RpcResponse<RpcKeyedAccount>> + Unpin),
+) -> Result<()> {
     while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
         match notif.next().await {
             Some(update) => {
@@ -172,6 +494,20 @@ pub async fn run(store: Arc<State>, pythnet_ws_endpoint: String) -> Result<()> {
                         );
 
                         if candidate.to_string() == update.value.pubkey {
+                            // The other endpoints deliver the same update, only the first copy
+                            // of each version of it is stored.
+                            if !sources
+                                .observe(
+                                    endpoint,
+                                    accumulator_messages.slot,
+                                    accumulator_messages.ring_index(),
+                                    &account.data,
+                                )
+                                .await
+                            {
+                                continue;
+                            }
+
                             let store = store.clone();
                             tokio::spawn(async move {
                                 if let Err(err) = crate::aggregate::store_update(
@@ -180,7 +516,10 @@ pub async fn run(store: Arc<State>, pythnet_ws_endpoint: String) -> Result<()> {
                                 )
                                 .await
                                 {
-                                    tracing::error!(error = ?err, "Failed to store accumulator messages.");
+                                    tracing::error!(
+                                        error = ?err,
+                                        "Failed to store accumulator messages."
+                                    );
                                 }
                             });
                         } else {
@@ -218,6 +557,10 @@ async fn fetch_existing_guardian_sets(
 ) -> Result<()> {
     let client = RpcClient::new(pythnet_http_endpoint.to_string());
     let bridge = fetch_bridge_data(&client, &wormhole_contract_addr).await?;
+    state.guardian_set_expiration_time.store(
+        bridge.config.guardian_set_expiration_time,
+        Ordering::Release,
+    );
 
     // Fetch the current GuardianSet we know is valid for signing.
     let current =
@@ -253,43 +596,107 @@ async fn fetch_existing_guardian_sets(
     Ok(())
 }
 
+/// Fetch the existing GuardianSet accounts from the first HTTP endpoint that responds.
+async fn fetch_existing_guardian_sets_with_failover(
+    state: Arc<State>,
+    pythnet_http_endpoints: &[String],
+    wormhole_contract_addr: Pubkey,
+) -> Result<()> {
+    for pythnet_http_endpoint in pythnet_http_endpoints {
+        match fetch_existing_guardian_sets(
+            state.clone(),
+            pythnet_http_endpoint.clone(),
+            wormhole_contract_addr,
+        )
+        .await
+        {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                tracing::warn!(
+                    endpoint = pythnet_http_endpoint,
+                    error = ?err,
+                    "Failed to fetch guardian sets, trying the next endpoint."
+                );
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Failed to fetch guardian sets from any Pythnet endpoint"
+    ))
+}
+
 #[tracing::instrument(skip(opts, state))]
 pub async fn spawn(opts: RunOptions, state: Arc<State>) -> Result<()> {
     tracing::info!(
-        endpoint = opts.pythnet.ws_endpoint,
+        endpoints = ?opts.pythnet.ws_endpoints,
         "Started Pythnet Listener."
     );
 
-    fetch_existing_guardian_sets(
+    fetch_existing_guardian_sets_with_failover(
         state.clone(),
-        opts.pythnet.http_endpoint.clone(),
+        &opts.pythnet.http_endpoints,
         opts.wormhole.contract_addr,
     )
     .await?;
 
-    let task_listener = {
-        let store = state.clone();
-        let pythnet_ws_endpoint = opts.pythnet.ws_endpoint.clone();
-        tokio::spawn(async move {
-            while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
-                let current_time = Instant::now();
+    // The overrides are served even if the product accounts cannot be read.
+    let feed_metadata_overrides = match opts.pythnet.feed_metadata_path {
+        Some(ref path) => load_overrides(path)?,
+        None => vec![],
+    };
+    state
+        .store_feed_metadata(build_catalogue(vec![], &feed_metadata_overrides))
+        .await;
 
-                if let Err(ref e) = run(store.clone(), pythnet_ws_endpoint.clone()).await {
-                    tracing::error!(error = ?e, "Error in Pythnet network listener.");
-                    if current_time.elapsed() < Duration::from_secs(30) {
-                        tracing::error!("Pythnet listener restarting too quickly. Sleep 1s.");
-                        tokio::time::sleep(Duration::from_secs(1)).await;
+    let sources = Arc::new(PythnetSources::new(opts.pythnet.ws_endpoints.clone()));
+
+    // Each endpoint gets its own listener that reconnects independently of the others.
+    let task_listeners = (0..opts.pythnet.ws_endpoints.len())
+        .map(|endpoint| {
+            let store = state.clone();
+            let sources = sources.clone();
+            tokio::spawn(async move {
+                while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
+                    let current_time = Instant::now();
+
+                    if let Err(ref e) = run(store.clone(), sources.clone(), endpoint).await {
+                        tracing::error!(
+                            endpoint = sources.endpoints[endpoint],
+                            error = ?e,
+                            "Error in Pythnet network listener."
+                        );
+                        if current_time.elapsed() < Duration::from_secs(30) {
+                            tracing::error!("Pythnet listener restarting too quickly. Sleep 1s.");
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        }
                     }
                 }
+
+                tracing::info!(
+                    endpoint = sources.endpoints[endpoint],
+                    "Shutting down Pythnet listener..."
+                );
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let task_health_reporter = {
+        let store = state.clone();
+        let sources = sources.clone();
+        tokio::spawn(async move {
+            while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
+                tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+                sources.report_health(&store.metrics).await;
             }
 
-            tracing::info!("Shutting down Pythnet listener...");
+            tracing::info!("Shutting down Pythnet health reporter...");
         })
     };
 
     let task_guadian_watcher = {
         let store = state.clone();
-        let pythnet_http_endpoint = opts.pythnet.http_endpoint.clone();
+        let pythnet_http_endpoints = opts.pythnet.http_endpoints.clone();
         tokio::spawn(async move {
             while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
                 // Poll for new guardian sets every 60 seconds. We use a short wait time so we can
@@ -302,9 +709,9 @@ pub async fn spawn(opts: RunOptions, state: Arc<State>) -> Result<()> {
                     tokio::time::sleep(Duration::from_secs(1)).await;
                 }
 
-                match fetch_existing_guardian_sets(
+                match fetch_existing_guardian_sets_with_failover(
                     store.clone(),
-                    pythnet_http_endpoint.clone(),
+                    &pythnet_http_endpoints,
                     opts.wormhole.contract_addr,
                 )
                 .await
@@ -320,6 +727,315 @@ pub async fn spawn(opts: RunOptions, state: Arc<State>) -> Result<()> {
         })
     };
 
-    let _ = tokio::join!(task_listener, task_guadian_watcher);
+    let task_feed_metadata_fetcher = {
+        let store = state.clone();
+        let pythnet_http_endpoints = opts.pythnet.http_endpoints.clone();
+        let oracle_program_addr = opts.pythnet.oracle_program_addr;
+        tokio::spawn(async move {
+            while !crate::SHOULD_EXIT.load(Ordering::Acquire) {
+                match fetch_feed_metadata_with_failover(
+                    &pythnet_http_endpoints,
+                    &oracle_program_addr,
+                )
+                .await
+                {
+                    Ok(metadata) => {
+                        tracing::info!(num_feeds = metadata.len(), "Fetched feed metadata.");
+                        store
+                            .store_feed_metadata(build_catalogue(
+                                metadata,
+                                &feed_metadata_overrides,
+                            ))
+                            .await;
+                    }
+                    Err(err) => {
+                        tracing::error!(error = ?err, "Failed to fetch feed metadata.")
+                    }
+                }
+
+                // Sleep in short steps so we can properly exit if a quit signal was received.
+                for _ in 0..FEED_METADATA_REFRESH_INTERVAL.as_secs() {
+                    if crate::SHOULD_EXIT.load(Ordering::Acquire) {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+
+            tracing::info!("Shutting down Pythnet feed metadata fetcher...");
+        })
+    };
+
+    let _ = tokio::join!(
+        join_all(task_listeners),
+        task_health_reporter,
+        task_guadian_watcher,
+        task_feed_metadata_fetcher
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::{
+            cache::AggregateCache,
+            test::setup_state,
+        },
+        axum::{
+            extract::ws::{
+                Message,
+                WebSocket,
+                WebSocketUpgrade,
+            },
+            routing::get,
+            Router,
+        },
+        borsh::BorshSerialize,
+        solana_account_decoder::UiAccount,
+        solana_client::rpc_response::RpcResponseContext,
+    };
+
+    fn create_notification(accumulator_messages: &AccumulatorMessages) -> serde_json::Value {
+        let (pubkey, _) = Pubkey::find_program_address(
+            &[
+                b"AccumulatorState",
+                &accumulator_messages.ring_index().to_be_bytes(),
+            ],
+            &system_program::id(),
+        );
+        let account = Account {
+            lamports:   0,
+            data:       accumulator_messages.try_to_vec().unwrap(),
+            owner:      system_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        serde_json::to_value(RpcResponse {
+            context: RpcResponseContext {
+                slot:        accumulator_messages.slot,
+                api_version: None,
+            },
+            value:   RpcKeyedAccount {
+                pubkey:  pubkey.to_string(),
+                account: UiAccount::encode(
+                    &pubkey,
+                    &account,
+                    UiAccountEncoding::Base64,
+                    None,
+                    None,
+                ),
+            },
+        })
+        .unwrap()
+    }
+
+    async fn serve_mock_pubsub(mut socket: WebSocket, notifications: Vec<serde_json::Value>) {
+        // Acknowledge the program subscription.
+        while let Some(Ok(message)) = socket.recv().await {
+            if let Message::Text(text) = message {
+                let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if request["method"] == "programSubscribe" {
+                    let response = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "result": 1,
+                        "id": request["id"],
+                    });
+                    socket
+                        .send(Message::Text(response.to_string()))
+                        .await
+                        .unwrap();
+                    break;
+                }
+            }
+        }
+
+        for notification in notifications {
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "programNotification",
+                "params": {
+                    "result": notification,
+                    "subscription": 1,
+                },
+            });
+            socket
+                .send(Message::Text(notification.to_string()))
+                .await
+                .unwrap();
+        }
+
+        // Keep the connection open without sending anything else, like a stalled node.
+        while let Some(Ok(_)) = socket.recv().await {}
+    }
+
+    /// Spawn a mock Pythnet pubsub server that sends the given updates to every program
+    /// subscription and returns its address.
+    fn spawn_mock_pubsub_server(updates: Vec<AccumulatorMessages>) -> String {
+        let notifications: Vec<serde_json::Value> =
+            updates.iter().map(create_notification).collect();
+        let app = Router::new().route(
+            "/",
+            get(move |ws: WebSocketUpgrade| {
+                let notifications = notifications.clone();
+                async move { ws.on_upgrade(move |socket| serve_mock_pubsub(socket, notifications)) }
+            }),
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        format!("ws://{}", addr)
+    }
+
+    fn create_accumulator_messages(slot: Slot) -> AccumulatorMessages {
+        AccumulatorMessages {
+            magic: *b"PAS1",
+            slot,
+            ring_size: 10_000,
+            raw_messages: vec![],
+        }
+    }
+
+    #[tokio::test]
+    pub async fn test_updates_from_multiple_endpoints_are_deduplicated() {
+        let (state, _update_rx) = setup_state(10).await;
+
+        // The first endpoint stalls after slot 11 while the second one keeps going.
+        let stalled_endpoint = spawn_mock_pubsub_server(vec![
+            create_accumulator_messages(10),
+            create_accumulator_messages(11),
+        ]);
+        let healthy_endpoint = spawn_mock_pubsub_server(vec![
+            create_accumulator_messages(10),
+            create_accumulator_messages(11),
+            create_accumulator_messages(12),
+        ]);
+
+        let sources = Arc::new(PythnetSources::new(vec![
+            stalled_endpoint.clone(),
+            healthy_endpoint.clone(),
+        ]));
+        let listeners = (0..2)
+            .map(|endpoint| tokio::spawn(run(state.clone(), sources.clone(), endpoint)))
+            .collect::<Vec<_>>();
+
+        // Wait until every update of both endpoints has been received and stored.
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let health = sources.health().await;
+                if health[0].slot_lag == Some(1)
+                    && health[1].slot_lag == Some(0)
+                    && state.metrics.accumulator_updates.get() == 3
+                {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // Each slot is stored once even though most of them were received twice.
+        for slot in [10, 11, 12] {
+            assert!(state
+                .fetch_accumulator_messages(slot)
+                .await
+                .unwrap()
+                .is_some());
+        }
+        assert_eq!(state.metrics.accumulator_updates.get(), 3);
+
+        assert_eq!(
+            sources.health().await,
+            vec![
+                EndpointHealth {
+                    endpoint: stalled_endpoint,
+                    healthy:  true,
+                    slot_lag: Some(1),
+                },
+                EndpointHealth {
+                    endpoint: healthy_endpoint,
+                    healthy:  true,
+                    slot_lag: Some(0),
+                },
+            ]
+        );
+
+        for listener in listeners {
+            listener.abort();
+        }
+    }
+
+    fn create_product_account(price_account: [u8; 32], attributes: &[(&str, &str)]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&ORACLE_ACCOUNT_MAGIC.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&PRODUCT_ACCOUNT_TYPE.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&price_account);
+        for (key, value) in attributes {
+            data.push(key.len() as u8);
+            data.extend_from_slice(key.as_bytes());
+            data.push(value.len() as u8);
+            data.extend_from_slice(value.as_bytes());
+        }
+        let size = data.len() as u32;
+        data[12..16].copy_from_slice(&size.to_le_bytes());
+
+        // Product accounts are allocated larger than they need to be.
+        data.resize(512, 0);
+        data
+    }
+
+    #[test]
+    fn test_parse_product_account() {
+        let data = create_product_account(
+            [7; 32],
+            &[
+                ("asset_type", "Crypto"),
+                ("base", "BTC"),
+                ("description", "BITCOIN / US DOLLAR"),
+                ("generic_symbol", "BTCUSD"),
+                ("quote_currency", "USD"),
+                ("symbol", "Crypto.BTC/USD"),
+            ],
+        );
+
+        assert_eq!(
+            parse_product_account(&data).unwrap(),
+            FeedMetadata {
+                id:          PriceIdentifier::new([7; 32]),
+                symbol:      Some("Crypto.BTC/USD".to_owned()),
+                asset_type:  Some("Crypto".to_owned()),
+                base:        Some("BTC".to_owned()),
+                quote:       Some("USD".to_owned()),
+                description: Some("BITCOIN / US DOLLAR".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_product_account() {
+        // No price account.
+        let data = create_product_account([0; 32], &[("symbol", "Crypto.BTC/USD")]);
+        assert!(parse_product_account(&data).is_err());
+
+        // An attribute that goes past the used size.
+        let mut data = create_product_account([7; 32], &[("symbol", "Crypto.BTC/USD")]);
+        data[PRODUCT_ACCOUNT_HEADER_SIZE + 7] = 100;
+        assert!(parse_product_account(&data).is_err());
+
+        // A price account.
+        let mut data = create_product_account([7; 32], &[]);
+        data[8..12].copy_from_slice(&3u32.to_le_bytes());
+        assert!(parse_product_account(&data).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! This module streams signed VAAs from a Wormhole spy.
+//!
+//! A spy is a lightweight Wormhole node that only listens to the gossip network and serves what it
+//! sees over gRPC. Subscribing to it is an alternative to joining the gossip network ourselves.
+
+use {
+    crate::wormhole::{
+        VaaBytes,
+        VaaSource,
+        GOVERNANCE_EMITTER_ADDRESS,
+    },
+    anyhow::Result,
+    proto::{
+        publicrpc::v1::ChainId,
+        spy::v1::{
+            filter_entry::Filter,
+            spy_rpc_service_client::SpyRpcServiceClient,
+            EmitterFilter,
+            FilterEntry,
+            SubscribeSignedVaaRequest,
+        },
+    },
+    std::{
+        sync::atomic::Ordering,
+        time::Duration,
+    },
+    tokio::sync::mpsc,
+};
+
+/// The code generated from the spy protobuf definitions vendored in `proto`.
+pub mod proto {
+    #![allow(clippy::derive_partial_eq_without_eq)]
+
+    pub mod publicrpc {
+        pub mod v1 {
+            tonic::include_proto!("publicrpc.v1");
+        }
+    }
+
+    pub mod spy {
+        pub mod v1 {
+            tonic::include_proto!("spy.v1");
+        }
+    }
+}
+
+/// The delay before reconnecting after the stream failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// A filter on the VAAs of an emitter. The spy expects the emitter address in hex.
+fn emitter_filter(chain_id: ChainId, emitter_address: &[u8]) -> FilterEntry {
+    FilterEntry {
+        filter: Some(Filter::EmitterFilter(EmitterFilter {
+            chain_id:        chain_id as i32,
+            emitter_address: hex::encode(emitter_address),
+        })),
+    }
+}
+
+/// Receives the VAAs of the accumulator emitter from a Wormhole spy.
+pub struct SpyVaaSource {
+    addr: String,
+}
+
+impl SpyVaaSource {
+    pub fn new(addr: String) -> Self {
+        Self { addr }
+    }
+
+    /// Subscribe to the spy and send the VAAs it streams until the stream ends.
+    async fn subscribe(&self, vaa_sender: &mpsc::Sender<VaaBytes>) -> Result<()> {
+        let mut client = SpyRpcServiceClient::connect(self.addr.clone()).await?;
+
+        // Subscribe to the governance VAAs as well to follow the guardian set upgrades.
+        let request = SubscribeSignedVaaRequest {
+            filters: vec![
+                emitter_filter(ChainId::Pythnet, &pythnet_sdk::ACCUMULATOR_EMITTER_ADDRESS),
+                emitter_filter(ChainId::Solana, &GOVERNANCE_EMITTER_ADDRESS),
+            ],
+        };
+
+        let mut stream = client.subscribe_signed_vaa(request).await?.into_inner();
+        tracing::info!(addr = self.addr, "Subscribed to Wormhole spy.");
+
+        while let Some(response) = stream.message().await? {
+            if vaa_sender.send(response.vaa_bytes).await.is_err() {
+                break;
+            }
+
+            if crate::SHOULD_EXIT.load(Ordering::Acquire) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl VaaSource for SpyVaaSource {
+    fn name(&self) -> &'static str {
+        "spy"
+    }
+
+    #[tracing::instrument(skip(self, vaa_sender))]
+    async fn run(self: Box<Self>, vaa_sender: mpsc::Sender<VaaBytes>) -> Result<()> {
+        while !crate::SHOULD_EXIT.load(Ordering::Acquire) && !vaa_sender.is_closed() {
+            match self.subscribe(&vaa_sender).await {
+                Ok(()) => tracing::warn!(addr = self.addr, "Wormhole spy stream ended."),
+                Err(e) => {
+                    tracing::error!(addr = self.addr, error = ?e, "Wormhole spy stream failed.")
+                }
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::{
+            proto::spy::v1::{
+                spy_rpc_service_server::{
+                    SpyRpcService,
+                    SpyRpcServiceServer,
+                },
+                SubscribeSignedVaaResponse,
+            },
+            *,
+        },
+        futures::Stream,
+        std::{
+            pin::Pin,
+            sync::{
+                Arc,
+                Mutex,
+            },
+        },
+        tokio::net::TcpListener,
+        tonic::{
+            transport::Server,
+            Request,
+            Response,
+            Status,
+        },
+    };
+
+    /// A spy that streams the same VAAs to every subscriber and then ends the stream.
+    #[derive(Clone)]
+    struct MockSpy {
+        vaas:     Vec<VaaBytes>,
+        requests: Arc<Mutex<Vec<SubscribeSignedVaaRequest>>>,
+    }
+
+    #[tonic::async_trait]
+    impl SpyRpcService for MockSpy {
+        type SubscribeSignedVAAStream =
+            Pin<Box<dyn Stream<Item = Result<SubscribeSignedVaaResponse, Status>> + Send>>;
+
+        async fn subscribe_signed_vaa(
+            &self,
+            request: Request<SubscribeSignedVaaRequest>,
+        ) -> Result<Response<Self::SubscribeSignedVAAStream>, Status> {
+            self.requests.lock().unwrap().push(request.into_inner());
+            let responses = self
+                .vaas
+                .iter()
+                .map(|vaa_bytes| {
+                    Ok(SubscribeSignedVaaResponse {
+                        vaa_bytes: vaa_bytes.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            Ok(Response::new(Box::pin(futures::stream::iter(responses))))
+        }
+    }
+
+    /// Serve the mock spy on a local port and return its address.
+    async fn serve_mock_spy(spy: MockSpy) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = futures::stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _)| stream);
+            Some((stream, listener))
+        });
+        tokio::spawn(
+            Server::builder()
+                .add_service(SpyRpcServiceServer::new(spy))
+                .serve_with_incoming(incoming),
+        );
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_spy_source_streams_vaas_and_reconnects() {
+        let spy = MockSpy {
+            vaas:     vec![vec![1], vec![2]],
+            requests: Default::default(),
+        };
+        let addr = serve_mock_spy(spy.clone()).await;
+
+        let (vaa_sender, mut vaa_receiver) = mpsc::channel(10);
+        let source = tokio::spawn(Box::new(SpyVaaSource::new(addr)).run(vaa_sender));
+
+        // The mock ends the stream after two VAAs, so the last two come from a new subscription.
+        let mut received = vec![];
+        for _ in 0..4 {
+            received.push(vaa_receiver.recv().await.unwrap());
+        }
+        assert_eq!(received, vec![vec![1], vec![2], vec![1], vec![2]]);
+
+        let requests = spy.requests.lock().unwrap().clone();
+        assert!(requests.len() >= 2);
+        assert_eq!(
+            requests[0].filters,
+            vec![
+                emitter_filter(ChainId::Pythnet, &pythnet_sdk::ACCUMULATOR_EMITTER_ADDRESS),
+                emitter_filter(ChainId::Solana, &GOVERNANCE_EMITTER_ADDRESS),
+            ]
+        );
+        assert_eq!(requests[0], requests[1]);
+
+        source.abort();
+    }
+}
@@ -0,0 +1,323 @@
+//! Recording and replay of the updates Hermes receives.
+//!
+//! In record mode every update passed to `store_update` is appended to a file along with the time
+//! it was received. `hermes replay` feeds such a recording back through the aggregation with the
+//! recorded receive times while serving the API, so that incidents can be reproduced offline. The
+//! clock of the state follows the recorded receive times, so the readiness and the feed health are
+//! judged by the time of the recording rather than the time of the replay.
+//!
+//! A recording is a sequence of records, each made of its length as a little-endian `u32` followed
+//! by the Borsh encoding of a `RecordedUpdate`.
+
+use {
+    crate::{
+        aggregate::{
+            store_update_received_at,
+            Update,
+        },
+        config::{
+            ReplayOptions,
+            ReplaySpeed,
+        },
+        state::{
+            cache::Cache,
+            State,
+        },
+    },
+    anyhow::Result,
+    borsh::{
+        BorshDeserialize,
+        BorshSerialize,
+    },
+    futures::future::join_all,
+    std::{
+        io::ErrorKind,
+        path::Path,
+        sync::atomic::Ordering,
+        time::Duration,
+    },
+    tokio::{
+        fs::{
+            File,
+            OpenOptions,
+        },
+        io::{
+            AsyncReadExt,
+            AsyncWriteExt,
+            BufReader,
+        },
+        sync::Mutex,
+        time::Instant,
+    },
+};
+
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+struct RecordedUpdate {
+    /// When the update was received, in milliseconds since the unix epoch.
+    received_at_ms: u64,
+    update:         Update,
+}
+
+/// Appends the received updates to a recording.
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    pub async fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        tracing::info!(path = %path.display(), "Recording updates.");
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub async fn record(&self, received_at: Duration, update: &Update) -> Result<()> {
+        let mut record = vec![0; 4];
+        (received_at.as_millis() as u64).serialize(&mut record)?;
+        update.serialize(&mut record)?;
+        let len = (record.len() - 4) as u32;
+        record[..4].copy_from_slice(&len.to_le_bytes());
+
+        // Records are written at once so that a crash cannot interleave them.
+        self.file.lock().await.write_all(&record).await?;
+        Ok(())
+    }
+}
+
+/// Reads the updates of a recording in order.
+pub struct Recording {
+    reader: BufReader<File>,
+}
+
+impl Recording {
+    pub async fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path).await?),
+        })
+    }
+
+    /// Read the next update and the time it was received since the unix epoch. Returns `None` at
+    /// the end of the recording, including when the last record was only partially written.
+    pub async fn next(&mut self) -> Result<Option<(Duration, Update)>> {
+        let len = match self.reader.read_u32_le().await {
+            Ok(len) => len,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut record = vec![0; len as usize];
+        match self.reader.read_exact(&mut record).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                tracing::warn!("Ignoring the truncated last record of the recording.");
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let record = RecordedUpdate::try_from_slice(&record)?;
+        Ok(Some((
+            Duration::from_millis(record.received_at_ms),
+            record.update,
+        )))
+    }
+}
+
+/// Feed the updates of a recording to the aggregation, either with the delays they were received
+/// with or as fast as possible. Returns the number of updates replayed.
+async fn replay(state: &State, mut recording: Recording, speed: ReplaySpeed) -> Result<usize> {
+    // The receive time of the first update and when it was replayed.
+    let mut start: Option<(Duration, Instant)> = None;
+    let mut num_updates = 0;
+
+    while let Some((received_at, update)) = recording.next().await? {
+        if crate::SHOULD_EXIT.load(Ordering::Acquire) {
+            break;
+        }
+
+        if speed == ReplaySpeed::Original {
+            let (first_received_at, started_at) =
+                *start.get_or_insert_with(|| (received_at, Instant::now()));
+            tokio::time::sleep_until(started_at + received_at.saturating_sub(first_received_at))
+                .await;
+        }
+
+        state.clock.replayed(received_at);
+        if let Err(e) = store_update_received_at(state, update, received_at).await {
+            tracing::warn!(error = ?e, "Failed to replay update.");
+        }
+        num_updates += 1;
+    }
+
+    Ok(num_updates)
+}
+
+/// Replay a recording while serving the API, which keeps being served once the replay is over
+/// until Hermes is shut down.
+#[tracing::instrument(skip(opts))]
+pub async fn run(opts: ReplayOptions) -> Result<()> {
+    let (update_tx, update_rx) = tokio::sync::mpsc::channel(1000);
+    let state = State::new(update_tx, Box::new(Cache::new(1000)), None, None);
+
+    let servers = [
+        tokio::spawn(crate::api::run(opts.rpc.clone(), state.clone(), update_rx)),
+        tokio::spawn(crate::metrics::run(opts.rpc.clone(), state.clone())),
+        tokio::spawn(crate::aggregate::feed_health::run(
+            opts.feed_health.clone(),
+            state.clone(),
+        )),
+    ];
+
+    let recording = Recording::open(&opts.path).await?;
+    let num_updates = replay(&state, recording, opts.speed).await?;
+    tracing::info!(
+        num_updates,
+        "Replay finished, serving the API until shutdown."
+    );
+
+    for server in join_all(servers).await {
+        server??;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            aggregate::{
+                test::generate_update,
+                AccumulatorMessages,
+            },
+            state::test::setup_state,
+        },
+        pythnet_sdk::messages::Message,
+    };
+
+    fn recording_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hermes-recording-{}", rand::random::<u64>()))
+    }
+
+    #[tokio::test]
+    async fn test_recorded_updates_are_read_back() {
+        let path = recording_path();
+        let recorder = Recorder::open(&path).await.unwrap();
+
+        let accumulator_messages = AccumulatorMessages {
+            magic:        [1; 4],
+            slot:         10,
+            ring_size:    100,
+            raw_messages: vec![vec![1, 2, 3]],
+        };
+        recorder
+            .record(
+                Duration::from_millis(1_000),
+                &Update::AccumulatorMessages(accumulator_messages.clone()),
+            )
+            .await
+            .unwrap();
+        recorder
+            .record(Duration::from_millis(1_500), &Update::Vaa(vec![4, 5]))
+            .await
+            .unwrap();
+
+        // A partially written record at the end is ignored.
+        let mut file = OpenOptions::new().append(true).open(&path).await.unwrap();
+        file.write_all(&[10, 0, 0, 0, 1]).await.unwrap();
+
+        let mut recording = Recording::open(&path).await.unwrap();
+        match recording.next().await.unwrap() {
+            Some((received_at, Update::AccumulatorMessages(messages))) => {
+                assert_eq!(received_at, Duration::from_millis(1_000));
+                assert_eq!(messages, accumulator_messages);
+            }
+            other => panic!("Unexpected record: {:?}", other),
+        }
+        match recording.next().await.unwrap() {
+            Some((received_at, Update::Vaa(vaa))) => {
+                assert_eq!(received_at, Duration::from_millis(1_500));
+                assert_eq!(vaa, vec![4, 5]);
+            }
+            other => panic!("Unexpected record: {:?}", other),
+        }
+        assert!(recording.next().await.unwrap().is_none());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_keeps_the_recorded_receive_times() {
+        let path = recording_path();
+        let recorder = Recorder::open(&path).await.unwrap();
+        let message = crate::aggregate::test::create_dummy_price_feed_message(100, 10, 9);
+        for update in generate_update(vec![Message::PriceFeedMessage(message)], 10, 20) {
+            recorder
+                .record(Duration::from_secs(1_000), &update)
+                .await
+                .unwrap();
+        }
+
+        let (state, mut update_rx) = setup_state(10).await;
+        let recording = Recording::open(&path).await.unwrap();
+        assert_eq!(
+            replay(&state, recording, ReplaySpeed::Max).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            update_rx.recv().await,
+            Some(crate::aggregate::AggregationEvent::New { slot: 10 })
+        );
+
+        let price_feeds = crate::aggregate::get_price_feeds_with_update_data(
+            &*state,
+            vec![pyth_sdk::PriceIdentifier::new([100; 32])],
+            crate::aggregate::RequestTime::Latest,
+        )
+        .await
+        .unwrap();
+        assert_eq!(price_feeds.price_feeds[0].received_at, Some(1_000));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_judges_readiness_by_the_recorded_times() {
+        let path = recording_path();
+        let recorder = Recorder::open(&path).await.unwrap();
+        let message = crate::aggregate::test::create_dummy_price_feed_message(100, 10, 9);
+        for update in generate_update(vec![Message::PriceFeedMessage(message)], 10, 20) {
+            recorder
+                .record(Duration::from_secs(1_000), &update)
+                .await
+                .unwrap();
+        }
+        // The next slot never completes, so the recording ends a minute after the last completed
+        // slot.
+        let message = crate::aggregate::test::create_dummy_price_feed_message(100, 11, 10);
+        let update = generate_update(vec![Message::PriceFeedMessage(message)], 11, 21).remove(0);
+        recorder
+            .record(Duration::from_secs(1_060), &update)
+            .await
+            .unwrap();
+
+        let (state, _update_rx) = setup_state(10).await;
+        let recording = Recording::open(&path).await.unwrap();
+        assert_eq!(
+            replay(&state, recording, ReplaySpeed::Max).await.unwrap(),
+            3
+        );
+
+        // The replay itself takes no time, but Hermes was not ready at the end of the recording.
+        assert!(state.clock.now() >= Duration::from_secs(1_060));
+        assert!(!crate::aggregate::is_ready(&state).await);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
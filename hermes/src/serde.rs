@@ -81,3 +81,47 @@ pub mod hex {
         }
     }
 }
+
+/// Serialize integers as strings in human-readable formats to avoid precision loss in JSON
+/// clients, and as integers in binary formats which represent them exactly.
+pub mod as_string {
+    use {
+        serde::{
+            de::Error,
+            Deserialize,
+            Deserializer,
+            Serialize,
+            Serializer,
+        },
+        std::{
+            fmt::Display,
+            str::FromStr,
+        },
+    };
+
+    pub fn serialize<S, T>(value: &T, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Display + Serialize,
+    {
+        if s.is_human_readable() {
+            s.collect_str(value)
+        } else {
+            value.serialize(s)
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(d: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr + Deserialize<'de>,
+        <T as FromStr>::Err: Display,
+    {
+        if d.is_human_readable() {
+            let s: String = Deserialize::deserialize(d)?;
+            s.parse().map_err(D::Error::custom)
+        } else {
+            T::deserialize(d)
+        }
+    }
+}
@@ -1,21 +1,39 @@
 //! This module contains the global state of the application.
 
 use {
-    self::cache::Cache,
+    self::{
+        cache::AggregateCache,
+        clock::Clock,
+        feed_metadata::FeedMetadata,
+    },
     crate::{
         aggregate::{
+            feed_health::FeedHealth,
+            quarantine::Quarantine,
             AggregateState,
             AggregationEvent,
         },
-        wormhole::GuardianSet,
+        api::WsState,
+        metrics::Metrics,
+        replay::Recorder,
+        wormhole::{
+            verification::VaaVerifier,
+            GuardianSet,
+            DEFAULT_GUARDIAN_SET_EXPIRATION_TIME,
+        },
     },
+    pyth_sdk::PriceIdentifier,
     reqwest::Url,
     std::{
         collections::{
             BTreeMap,
             BTreeSet,
+            HashMap,
+        },
+        sync::{
+            atomic::AtomicU32,
+            Arc,
         },
-        sync::Arc,
     },
     tokio::sync::{
         mpsc::Sender,
@@ -25,11 +43,13 @@ use {
 
 pub mod benchmarks;
 pub mod cache;
+pub mod clock;
+pub mod feed_metadata;
 
 pub struct State {
-    /// Storage is a short-lived cache of the state of all the updates that have been passed to the
-    /// store.
-    pub cache: Cache,
+    /// Storage is a cache of the state of all the updates that have been passed to the store. It is
+    /// either kept in memory only or backed by an on-disk database.
+    pub cache: Box<dyn AggregateCache + Send + Sync>,
 
     /// Sequence numbers of lately observed Vaas. Store uses this set
     /// to ignore the previously observed Vaas as a performance boost.
@@ -38,29 +58,67 @@ pub struct State {
     /// Wormhole guardian sets. It is used to verify Vaas before using them.
     pub guardian_set: RwLock<BTreeMap<u32, GuardianSet>>,
 
+    /// How long a guardian set stays valid after it is replaced, in seconds.
+    pub guardian_set_expiration_time: AtomicU32,
+
+    /// Verifies the signatures of the VAAs and caches the results.
+    pub vaa_verifier: VaaVerifier,
+
     /// The sender to the channel between Store and Api to notify completed updates.
     pub api_update_tx: Sender<AggregationEvent>,
 
+    /// The WebSocket and Server-Sent Events subscribers the updates are fanned out to.
+    pub ws: Arc<WsState>,
+
     /// The aggregate module state.
     pub aggregate_state: RwLock<AggregateState>,
 
+    /// Accumulator messages that do not match the Merkle root signed by the guardians.
+    pub quarantine: Quarantine,
+
     /// Benchmarks endpoint
     pub benchmarks_endpoint: Option<Url>,
+
+    /// Metadata about the price feeds, such as their symbol.
+    pub feed_metadata: RwLock<HashMap<PriceIdentifier, FeedMetadata>>,
+
+    /// Publish times and staleness of the individual price feeds.
+    pub feed_health: FeedHealth,
+
+    /// Prometheus metrics.
+    pub metrics: Metrics,
+
+    /// Records the received updates when record mode is enabled.
+    pub recorder: Option<Recorder>,
+
+    /// The current time. This is the wall-clock time, except under `replay` where it follows the
+    /// receive times of the replayed updates.
+    pub clock: Clock,
 }
 
 impl State {
     pub fn new(
         update_tx: Sender<AggregationEvent>,
-        cache_size: u64,
+        cache: Box<dyn AggregateCache + Send + Sync>,
         benchmarks_endpoint: Option<Url>,
+        recorder: Option<Recorder>,
     ) -> Arc<Self> {
         Arc::new(Self {
-            cache: Cache::new(cache_size),
+            cache,
             observed_vaa_seqs: RwLock::new(Default::default()),
             guardian_set: RwLock::new(Default::default()),
+            guardian_set_expiration_time: AtomicU32::new(DEFAULT_GUARDIAN_SET_EXPIRATION_TIME),
+            vaa_verifier: VaaVerifier::new(),
             api_update_tx: update_tx,
+            ws: Arc::new(WsState::new()),
             aggregate_state: RwLock::new(AggregateState::new()),
+            quarantine: Quarantine::new(),
             benchmarks_endpoint,
+            feed_metadata: RwLock::new(Default::default()),
+            feed_health: FeedHealth::new(),
+            metrics: Metrics::new(),
+            recorder,
+            clock: Clock::default(),
         })
     }
 }
@@ -69,20 +127,24 @@ impl State {
 pub mod test {
     use {
         super::*,
-        crate::wormhole::update_guardian_set,
+        crate::{
+            state::cache::Cache,
+            wormhole::update_guardian_set,
+        },
         tokio::sync::mpsc::Receiver,
     };
 
     pub async fn setup_state(cache_size: u64) -> (Arc<State>, Receiver<AggregationEvent>) {
         let (update_tx, update_rx) = tokio::sync::mpsc::channel(1000);
-        let state = State::new(update_tx, cache_size, None);
+        let state = State::new(update_tx, Box::new(Cache::new(cache_size)), None, None);
 
         // Add an initial guardian set with public key 0
         update_guardian_set(
             &state,
             0,
             GuardianSet {
-                keys: vec![[0; 20]],
+                keys:            vec![[0; 20]],
+                expiration_time: None,
             },
         )
         .await;
@@ -27,6 +27,8 @@ use {
     tokio::sync::RwLock,
 };
 
+pub mod disk;
+
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct MessageStateKey {
     pub feed_id: FeedId,
@@ -151,6 +153,22 @@ fn retrieve_message_state(
     }
 }
 
+/// A range of message state times, as accepted by `BTreeMap::range`.
+pub type MessageStateTimeRange = (Bound<MessageStateTime>, Bound<MessageStateTime>);
+
+/// Whether a range cannot contain any time. `BTreeMap::range` panics on such ranges, so they have to
+/// be filtered out before.
+pub fn is_empty_range(range: &MessageStateTimeRange) -> bool {
+    match range {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) => start >= end,
+        _ => false,
+    }
+}
+
 impl Cache {
     pub fn new(cache_size: u64) -> Self {
         Self {
@@ -165,6 +183,7 @@ impl Cache {
 #[async_trait::async_trait]
 pub trait AggregateCache {
     async fn message_state_keys(&self) -> Vec<MessageStateKey>;
+    async fn message_state_counts(&self) -> Vec<(MessageStateKey, usize)>;
     async fn store_message_states(&self, message_states: Vec<MessageState>) -> Result<()>;
     async fn fetch_message_states(
         &self,
@@ -172,6 +191,14 @@ pub trait AggregateCache {
         request_time: RequestTime,
         filter: MessageStateFilter,
     ) -> Result<Vec<MessageState>>;
+    /// Message states of a single key within the time range, in ascending order and at most
+    /// `limit` of them.
+    async fn fetch_message_states_in_range(
+        &self,
+        key: MessageStateKey,
+        range: MessageStateTimeRange,
+        limit: usize,
+    ) -> Result<Vec<MessageState>>;
     async fn store_accumulator_messages(
         &self,
         accumulator_messages: AccumulatorMessages,
@@ -182,32 +209,41 @@ pub trait AggregateCache {
         wormhole_merkle_state: WormholeMerkleState,
     ) -> Result<()>;
     async fn fetch_wormhole_merkle_state(&self, slot: Slot) -> Result<Option<WormholeMerkleState>>;
+    /// The first `limit` slots after `slot` for which a wormhole merkle state is stored, in
+    /// ascending order.
+    async fn fetch_wormhole_merkle_state_slots_after(
+        &self,
+        slot: Slot,
+        limit: usize,
+    ) -> Result<Vec<Slot>>;
 }
 
 #[async_trait::async_trait]
-impl AggregateCache for crate::state::State {
+impl AggregateCache for Cache {
     async fn message_state_keys(&self) -> Vec<MessageStateKey> {
-        self.cache
-            .message_cache
+        self.message_cache
             .iter()
             .map(|entry| entry.key().clone())
             .collect::<Vec<_>>()
     }
 
+    async fn message_state_counts(&self) -> Vec<(MessageStateKey, usize)> {
+        self.message_cache
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().len()))
+            .collect::<Vec<_>>()
+    }
+
     async fn store_message_states(&self, message_states: Vec<MessageState>) -> Result<()> {
         for message_state in message_states {
             let key = message_state.key();
             let time = message_state.time();
-            let mut cache = self
-                .cache
-                .message_cache
-                .entry(key)
-                .or_insert_with(BTreeMap::new);
+            let mut cache = self.message_cache.entry(key).or_insert_with(BTreeMap::new);
 
             cache.insert(time, message_state);
 
             // Remove the earliest message states if the cache size is exceeded
-            while cache.len() > self.cache.cache_size as usize {
+            while cache.len() > self.cache_size as usize {
                 cache.pop_first();
             }
         }
@@ -233,27 +269,47 @@ impl AggregateCache for crate::state::State {
                         feed_id: id,
                         type_:   message_type,
                     };
-                    retrieve_message_state(&self.cache, key, request_time.clone())
+                    retrieve_message_state(self, key, request_time.clone())
                         .ok_or(anyhow!("Message not found"))
                 })
             })
             .collect()
     }
 
+    async fn fetch_message_states_in_range(
+        &self,
+        key: MessageStateKey,
+        range: MessageStateTimeRange,
+        limit: usize,
+    ) -> Result<Vec<MessageState>> {
+        if is_empty_range(&range) {
+            return Ok(vec![]);
+        }
+
+        Ok(match self.message_cache.get(&key) {
+            Some(key_cache) => key_cache
+                .range(range)
+                .take(limit)
+                .map(|(_, v)| v.clone())
+                .collect(),
+            None => vec![],
+        })
+    }
+
     async fn store_accumulator_messages(
         &self,
         accumulator_messages: AccumulatorMessages,
     ) -> Result<()> {
-        let mut cache = self.cache.accumulator_messages_cache.write().await;
+        let mut cache = self.accumulator_messages_cache.write().await;
         cache.insert(accumulator_messages.slot, accumulator_messages);
-        while cache.len() > self.cache.cache_size as usize {
+        while cache.len() > self.cache_size as usize {
             cache.pop_first();
         }
         Ok(())
     }
 
     async fn fetch_accumulator_messages(&self, slot: Slot) -> Result<Option<AccumulatorMessages>> {
-        let cache = self.cache.accumulator_messages_cache.read().await;
+        let cache = self.accumulator_messages_cache.read().await;
         Ok(cache.get(&slot).cloned())
     }
 
@@ -261,22 +317,109 @@ impl AggregateCache for crate::state::State {
         &self,
         wormhole_merkle_state: WormholeMerkleState,
     ) -> Result<()> {
-        let mut cache = self.cache.wormhole_merkle_state_cache.write().await;
+        let mut cache = self.wormhole_merkle_state_cache.write().await;
         cache.insert(wormhole_merkle_state.root.slot, wormhole_merkle_state);
-        while cache.len() > self.cache.cache_size as usize {
+        while cache.len() > self.cache_size as usize {
             cache.pop_first();
         }
         Ok(())
     }
 
     async fn fetch_wormhole_merkle_state(&self, slot: Slot) -> Result<Option<WormholeMerkleState>> {
-        let cache = self.cache.wormhole_merkle_state_cache.read().await;
+        let cache = self.wormhole_merkle_state_cache.read().await;
         Ok(cache.get(&slot).cloned())
     }
+
+    async fn fetch_wormhole_merkle_state_slots_after(
+        &self,
+        slot: Slot,
+        limit: usize,
+    ) -> Result<Vec<Slot>> {
+        let cache = self.wormhole_merkle_state_cache.read().await;
+        Ok(cache
+            .range((Bound::Excluded(slot), Bound::Unbounded))
+            .take(limit)
+            .map(|(slot, _)| *slot)
+            .collect())
+    }
+}
+
+/// The application state delegates to whichever cache backend it was constructed with.
+#[async_trait::async_trait]
+impl AggregateCache for crate::state::State {
+    async fn message_state_keys(&self) -> Vec<MessageStateKey> {
+        self.cache.message_state_keys().await
+    }
+
+    async fn message_state_counts(&self) -> Vec<(MessageStateKey, usize)> {
+        self.cache.message_state_counts().await
+    }
+
+    async fn store_message_states(&self, message_states: Vec<MessageState>) -> Result<()> {
+        self.cache.store_message_states(message_states).await
+    }
+
+    async fn fetch_message_states(
+        &self,
+        ids: Vec<FeedId>,
+        request_time: RequestTime,
+        filter: MessageStateFilter,
+    ) -> Result<Vec<MessageState>> {
+        self.cache
+            .fetch_message_states(ids, request_time, filter)
+            .await
+    }
+
+    async fn fetch_message_states_in_range(
+        &self,
+        key: MessageStateKey,
+        range: MessageStateTimeRange,
+        limit: usize,
+    ) -> Result<Vec<MessageState>> {
+        self.cache
+            .fetch_message_states_in_range(key, range, limit)
+            .await
+    }
+
+    async fn store_accumulator_messages(
+        &self,
+        accumulator_messages: AccumulatorMessages,
+    ) -> Result<()> {
+        self.cache
+            .store_accumulator_messages(accumulator_messages)
+            .await
+    }
+
+    async fn fetch_accumulator_messages(&self, slot: Slot) -> Result<Option<AccumulatorMessages>> {
+        self.cache.fetch_accumulator_messages(slot).await
+    }
+
+    async fn store_wormhole_merkle_state(
+        &self,
+        wormhole_merkle_state: WormholeMerkleState,
+    ) -> Result<()> {
+        self.cache
+            .store_wormhole_merkle_state(wormhole_merkle_state)
+            .await
+    }
+
+    async fn fetch_wormhole_merkle_state(&self, slot: Slot) -> Result<Option<WormholeMerkleState>> {
+        self.cache.fetch_wormhole_merkle_state(slot).await
+    }
+
+    async fn fetch_wormhole_merkle_state_slots_after(
+        &self,
+        slot: Slot,
+        limit: usize,
+    ) -> Result<Vec<Slot>> {
+        self.cache
+            .fetch_wormhole_merkle_state_slots_after(slot, limit)
+            .await
+    }
 }
 
 #[cfg(test)]
-mod test {
+pub mod test {
     use {
         super::*,
         crate::{
@@ -812,4 +955,47 @@ mod test {
             .unwrap()
             .is_none());
     }
+
+    #[tokio::test]
+    pub async fn test_fetch_wormhole_merkle_state_slots_after_works() {
+        // Initialize state with a cache size of 3 per key.
+        let (state, _) = setup_state(3).await;
+
+        for slot in [12, 10, 11] {
+            state
+                .store_wormhole_merkle_state(create_empty_wormhole_merkle_state_at_slot(slot))
+                .await
+                .unwrap();
+        }
+
+        // Slots are returned in ascending order regardless of the store order.
+        assert_eq!(
+            state
+                .fetch_wormhole_merkle_state_slots_after(9, 10)
+                .await
+                .unwrap(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(
+            state
+                .fetch_wormhole_merkle_state_slots_after(10, 10)
+                .await
+                .unwrap(),
+            vec![11, 12]
+        );
+        assert!(state
+            .fetch_wormhole_merkle_state_slots_after(12, 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // At most `limit` slots are returned, starting from the oldest.
+        assert_eq!(
+            state
+                .fetch_wormhole_merkle_state_slots_after(9, 2)
+                .await
+                .unwrap(),
+            vec![10, 11]
+        );
+    }
 }
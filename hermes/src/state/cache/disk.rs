@@ -0,0 +1,1065 @@
+//! An on-disk backend for the aggregate cache.
+//!
+//! `DiskCache` keeps the in-memory `Cache` in front of an embedded sled database. Every write goes
+//! to both, reads are served from memory first and fall back to disk for anything that has been
+//! evicted from memory. This allows Hermes to serve hours of history and to survive restarts
+//! without falling back to Benchmarks.
+//!
+//! Layout of the database:
+//!
+//! - `message_states`: `feed_id (32) | message type (1) | publish_time (8) | slot (8)` to a
+//!   `StoredMessageState`. The VAA is not stored per message as it is shared by all the messages
+//!   of a slot; it is read back from `wormhole_merkle_states` instead.
+//! - `message_state_slots`: `feed_id (32) | message type (1) | slot (8)` to the key of the message
+//!   state in `message_states`, to look message states up by slot.
+//! - `accumulator_messages`: `slot (8)` to Borsh encoded `AccumulatorMessages`.
+//! - `wormhole_merkle_states`: `slot (8)` to a `StoredWormholeMerkleState`.
+//! - `expiry`: `received_at (8) | tree (1) | key` to nothing. This index is ordered by time so the
+//!   oldest entries of all trees can be pruned without scanning them. There is one entry per stored
+//!   key, from when it was first stored, and the `message_state_slots` entry of a message state is
+//!   pruned with it.
+
+use {
+    super::{
+        is_empty_range,
+        retrieve_message_state,
+        AggregateCache,
+        Cache,
+        MessageState,
+        MessageStateFilter,
+        MessageStateKey,
+        MessageStateTime,
+        MessageStateTimeRange,
+    },
+    crate::{
+        aggregate::{
+            wormhole_merkle::{
+                WormholeMerkleMessageProof,
+                WormholeMerkleState,
+            },
+            AccumulatorMessages,
+            ProofSet,
+            RawMessage,
+            RequestTime,
+            Slot,
+            UnixTimestamp,
+        },
+        wormhole::VaaBytes,
+    },
+    anyhow::{
+        anyhow,
+        Result,
+    },
+    borsh::{
+        BorshDeserialize,
+        BorshSerialize,
+    },
+    byteorder::BigEndian,
+    pythnet_sdk::{
+        accumulators::merkle::MerklePath,
+        hashers::keccak256_160::Keccak160,
+        messages::{
+            FeedId,
+            Message,
+            MessageType,
+        },
+        wire::{
+            from_slice,
+            v1::WormholeMerkleRoot,
+        },
+    },
+    std::{
+        collections::{
+            BTreeMap,
+            HashMap,
+        },
+        ops::Bound,
+        path::Path,
+        sync::{
+            atomic::{
+                AtomicI64,
+                Ordering,
+            },
+            Arc,
+        },
+        time::{
+            Duration,
+            SystemTime,
+            UNIX_EPOCH,
+        },
+    },
+    strum::IntoEnumIterator,
+};
+
+/// Pruning walks the expiry index, so we only do it once in a while rather than on every write.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum number of entries removed in a single pruning round, for the retention and for the
+/// size limit each. This bounds the time a single store call can spend pruning; anything left over
+/// is picked up in the next round.
+const PRUNE_BATCH_SIZE: usize = 10_000;
+
+const MESSAGE_STATES_TREE: &str = "message_states";
+const MESSAGE_STATE_SLOTS_TREE: &str = "message_state_slots";
+const ACCUMULATOR_MESSAGES_TREE: &str = "accumulator_messages";
+const WORMHOLE_MERKLE_STATES_TREE: &str = "wormhole_merkle_states";
+const EXPIRY_TREE: &str = "expiry";
+
+/// Identifies which tree an expiry index entry points to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+enum TreeId {
+    MessageStates        = 0,
+    AccumulatorMessages  = 1,
+    WormholeMerkleStates = 2,
+}
+
+impl TryFrom<u8> for TreeId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(TreeId::MessageStates),
+            1 => Ok(TreeId::AccumulatorMessages),
+            2 => Ok(TreeId::WormholeMerkleStates),
+            _ => Err(anyhow!("Unknown tree id in expiry index: {}", value)),
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct StoredMessageState {
+    slot:        Slot,
+    raw_message: RawMessage,
+    proof:       Vec<[u8; 20]>,
+    received_at: UnixTimestamp,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct StoredWormholeMerkleState {
+    slot:      Slot,
+    ring_size: u32,
+    root:      [u8; 20],
+    vaa:       VaaBytes,
+}
+
+pub struct DiskCache {
+    /// Recent entries are kept in memory so the hot path never touches the disk.
+    memory: Cache,
+
+    disk: DiskStore,
+}
+
+/// The on-disk part of the cache. sled blocks the calling thread on disk IO, so all the database
+/// work is done on the blocking thread pool through `DiskStore::run` rather than on the async
+/// workers that also drive aggregation and the APIs. The handles are reference counted, which
+/// makes the store cheap to clone into a blocking task.
+#[derive(Clone)]
+struct DiskStore {
+    db:                     sled::Db,
+    message_states:         sled::Tree,
+    message_state_slots:    sled::Tree,
+    accumulator_messages:   sled::Tree,
+    wormhole_merkle_states: sled::Tree,
+    expiry:                 sled::Tree,
+
+    /// Entries received longer ago than this are removed from disk.
+    retention: Duration,
+
+    /// If set, the oldest entries are removed whenever the database grows beyond this many bytes.
+    /// This is a soft limit: a pruning round removes at most `PRUNE_BATCH_SIZE` entries, and sled
+    /// only reports a smaller size once it has reclaimed the space of the removed entries, so the
+    /// database can stay above it for a few rounds.
+    max_size: Option<u64>,
+
+    /// Unix timestamp of the last pruning round.
+    last_pruned_at: Arc<AtomicI64>,
+}
+
+fn now() -> UnixTimestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as UnixTimestamp)
+        .unwrap_or_default()
+}
+
+/// Encode a signed timestamp so that its big-endian bytes sort in the same order as the integer.
+fn encode_timestamp(timestamp: UnixTimestamp) -> [u8; 8] {
+    ((timestamp as u64) ^ (1 << 63)).to_be_bytes()
+}
+
+fn message_state_key_prefix(key: &MessageStateKey) -> Result<Vec<u8>> {
+    let type_index = MessageType::iter()
+        .position(|message_type| message_type == key.type_)
+        .ok_or_else(|| anyhow!("Unknown message type {}", key.type_))?;
+
+    let mut prefix = key.feed_id.to_vec();
+    prefix.push(type_index as u8);
+    Ok(prefix)
+}
+
+fn message_state_db_key(key: &MessageStateKey, time: &MessageStateTime) -> Result<Vec<u8>> {
+    let mut db_key = message_state_key_prefix(key)?;
+    db_key.extend_from_slice(&encode_timestamp(time.publish_time));
+    db_key.extend_from_slice(&time.slot.to_be_bytes());
+    Ok(db_key)
+}
+
+/// The key of the `message_state_slots` entry of the message state stored at `db_key`.
+fn message_state_slot_key(db_key: &[u8]) -> Result<Vec<u8>> {
+    match (db_key.get(..33), db_key.get(41..)) {
+        (Some(prefix), Some(slot)) => Ok([prefix, slot].concat()),
+        _ => Err(anyhow!("Malformed message state key")),
+    }
+}
+
+/// Returns the smallest key that is greater than every key starting with `prefix`.
+fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut successor = prefix.to_vec();
+    while let Some(last) = successor.pop() {
+        if last < u8::MAX {
+            successor.push(last + 1);
+            return Some(successor);
+        }
+    }
+    None
+}
+
+fn expiry_key(received_at: UnixTimestamp, tree: TreeId, key: &[u8]) -> Vec<u8> {
+    let mut expiry_key = encode_timestamp(received_at).to_vec();
+    expiry_key.push(tree as u8);
+    expiry_key.extend_from_slice(key);
+    expiry_key
+}
+
+impl DiskCache {
+    /// Open (or create) the database at `path` and reload the most recent state into memory.
+    pub async fn open(
+        path: impl AsRef<Path>,
+        cache_size: u64,
+        retention: Duration,
+        max_size: Option<u64>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let disk = tokio::task::spawn_blocking(move || -> Result<DiskStore> {
+            let db = sled::open(path)?;
+            let disk = DiskStore {
+                message_states: db.open_tree(MESSAGE_STATES_TREE)?,
+                message_state_slots: db.open_tree(MESSAGE_STATE_SLOTS_TREE)?,
+                accumulator_messages: db.open_tree(ACCUMULATOR_MESSAGES_TREE)?,
+                wormhole_merkle_states: db.open_tree(WORMHOLE_MERKLE_STATES_TREE)?,
+                expiry: db.open_tree(EXPIRY_TREE)?,
+                db,
+                retention,
+                max_size,
+                last_pruned_at: Arc::new(AtomicI64::new(0)),
+            };
+            disk.prune()?;
+            Ok(disk)
+        })
+        .await??;
+
+        let cache = Self {
+            memory: Cache::new(cache_size),
+            disk,
+        };
+        cache.reload().await?;
+        Ok(cache)
+    }
+
+    /// Load the latest `cache_size` entries of every tree into the in-memory cache.
+    async fn reload(&self) -> Result<()> {
+        let cache_size = self.memory.cache_size as usize;
+        let (accumulator_messages, wormhole_merkle_states, message_states) = self
+            .disk
+            .run(move |disk| {
+                let accumulator_messages = disk
+                    .accumulator_messages
+                    .iter()
+                    .rev()
+                    .take(cache_size)
+                    .map(|entry| -> Result<AccumulatorMessages> {
+                        Ok(AccumulatorMessages::try_from_slice(&entry?.1)?)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let wormhole_merkle_states = disk
+                    .wormhole_merkle_states
+                    .iter()
+                    .rev()
+                    .take(cache_size)
+                    .map(|entry| -> Result<WormholeMerkleState> {
+                        DiskStore::decode_wormhole_merkle_state(&entry?.1)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok((
+                    accumulator_messages,
+                    wormhole_merkle_states,
+                    disk.latest_message_states(cache_size)?,
+                ))
+            })
+            .await?;
+
+        for accumulator_messages in accumulator_messages {
+            self.memory
+                .store_accumulator_messages(accumulator_messages)
+                .await?;
+        }
+
+        for wormhole_merkle_state in wormhole_merkle_states {
+            self.memory
+                .store_wormhole_merkle_state(wormhole_merkle_state)
+                .await?;
+        }
+
+        let reloaded: usize = message_states.iter().map(Vec::len).sum();
+        for message_states in message_states {
+            self.memory.store_message_states(message_states).await?;
+        }
+
+        tracing::info!(message_states = reloaded, "Reloaded cache from disk.");
+        Ok(())
+    }
+
+    /// Remove entries that are past the retention period or that exceed the size limit.
+    pub async fn prune(&self) -> Result<()> {
+        self.disk.run(|disk| disk.prune()).await
+    }
+
+    /// Prune if the last pruning round is older than `PRUNE_INTERVAL`. The round is claimed
+    /// before pruning so that concurrent writes do not prune at the same time.
+    async fn maybe_prune(&self) -> Result<()> {
+        let last_pruned_at = self.disk.last_pruned_at.load(Ordering::Acquire);
+        let now = now();
+        if now.saturating_sub(last_pruned_at) < PRUNE_INTERVAL.as_secs() as UnixTimestamp {
+            return Ok(());
+        }
+
+        if self
+            .disk
+            .last_pruned_at
+            .compare_exchange(last_pruned_at, now, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.prune().await?;
+        }
+        Ok(())
+    }
+}
+
+impl DiskStore {
+    /// Run `f` on the blocking thread pool, see `DiskStore`.
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&DiskStore) -> Result<T> + Send + 'static,
+    {
+        let disk = self.clone();
+        tokio::task::spawn_blocking(move || f(&disk)).await?
+    }
+
+    fn tree(&self, tree: TreeId) -> &sled::Tree {
+        match tree {
+            TreeId::MessageStates => &self.message_states,
+            TreeId::AccumulatorMessages => &self.accumulator_messages,
+            TreeId::WormholeMerkleStates => &self.wormhole_merkle_states,
+        }
+    }
+
+    /// Store an entry and index it for expiry. Only the first store of a key is indexed, a
+    /// re-store keeps the expiry entry of the first one instead of adding another.
+    fn insert_with_expiry(
+        &self,
+        tree: TreeId,
+        db_key: &[u8],
+        value: Vec<u8>,
+        received_at: UnixTimestamp,
+    ) -> Result<()> {
+        if self.tree(tree).insert(db_key, value)?.is_none() {
+            self.expiry
+                .insert(expiry_key(received_at, tree, db_key), Vec::<u8>::new())?;
+        }
+        Ok(())
+    }
+
+    /// Read up to the `cache_size` latest message states of every key.
+    fn latest_message_states(&self, cache_size: usize) -> Result<Vec<Vec<MessageState>>> {
+        // Message states are grouped by key prefix. We jump from one prefix to the next and only
+        // read the tail of each group instead of scanning the whole tree.
+        let mut latest_message_states = vec![];
+        let mut next = self.message_states.first()?;
+        while let Some((db_key, _)) = next {
+            let prefix = db_key
+                .get(..33)
+                .ok_or_else(|| anyhow!("Malformed message state key"))?
+                .to_vec();
+
+            let mut message_states = vec![];
+            for entry in self.message_states.scan_prefix(&prefix).rev() {
+                if message_states.len() >= cache_size {
+                    break;
+                }
+
+                let (_, value) = entry?;
+                match self.decode_message_state(&value)? {
+                    Some(message_state) => message_states.push(message_state),
+                    // The proof of older messages is gone, so are all the messages before it.
+                    None => break,
+                }
+            }
+            latest_message_states.push(message_states);
+
+            next = match prefix_successor(&prefix) {
+                Some(successor) => self.message_states.range(successor..).next().transpose()?,
+                None => None,
+            };
+        }
+
+        Ok(latest_message_states)
+    }
+
+    /// Remove the entries past the retention and, if the database is larger than `max_size`, the
+    /// oldest entries, up to `PRUNE_BATCH_SIZE` each.
+    fn prune(&self) -> Result<()> {
+        let cutoff = now().saturating_sub(self.retention.as_secs() as UnixTimestamp);
+        let mut pruned = self.prune_expiry_range(Some(cutoff))?;
+
+        if let Some(max_size) = self.max_size {
+            if self.db.size_on_disk()? > max_size {
+                pruned += self.prune_expiry_range(None)?;
+            }
+        }
+
+        self.last_pruned_at.store(now(), Ordering::Release);
+
+        if pruned > 0 {
+            tracing::info!(pruned, "Pruned on-disk cache.");
+        }
+
+        Ok(())
+    }
+
+    /// Remove up to `PRUNE_BATCH_SIZE` of the oldest entries, stopping at `cutoff` if given.
+    fn prune_expiry_range(&self, cutoff: Option<UnixTimestamp>) -> Result<usize> {
+        let range = match cutoff {
+            Some(cutoff) => self.expiry.range(..encode_timestamp(cutoff).to_vec()),
+            None => self.expiry.iter(),
+        };
+
+        let mut pruned = 0;
+        for entry in range.take(PRUNE_BATCH_SIZE) {
+            let (index_key, _) = entry?;
+            let tree = match index_key.get(8).copied().map(TreeId::try_from) {
+                Some(Ok(tree)) => tree,
+                _ => {
+                    tracing::warn!(?index_key, "Dropping malformed expiry index entry.");
+                    self.expiry.remove(&index_key)?;
+                    continue;
+                }
+            };
+
+            let db_key = &index_key[9..];
+            if tree == TreeId::MessageStates {
+                // The slot may have been stored again with another publish time since.
+                let _ = self.message_state_slots.compare_and_swap(
+                    message_state_slot_key(db_key)?,
+                    Some(db_key),
+                    None::<Vec<u8>>,
+                )?;
+            }
+            self.tree(tree).remove(db_key)?;
+
+            self.expiry.remove(&index_key)?;
+            pruned += 1;
+        }
+
+        Ok(pruned)
+    }
+
+    fn decode_wormhole_merkle_state(value: &[u8]) -> Result<WormholeMerkleState> {
+        let stored = StoredWormholeMerkleState::try_from_slice(value)?;
+        Ok(WormholeMerkleState {
+            root: WormholeMerkleRoot {
+                slot:      stored.slot,
+                ring_size: stored.ring_size,
+                root:      stored.root,
+            },
+            vaa:  stored.vaa,
+        })
+    }
+
+    /// Decode a stored message state. Returns `None` if the VAA for its slot is no longer stored,
+    /// as the message state cannot be proven without it.
+    fn decode_message_state(&self, value: &[u8]) -> Result<Option<MessageState>> {
+        let stored = StoredMessageState::try_from_slice(value)?;
+
+        let vaa = match self.wormhole_merkle_states.get(stored.slot.to_be_bytes())? {
+            Some(wormhole_merkle_state) => {
+                Self::decode_wormhole_merkle_state(&wormhole_merkle_state)?.vaa
+            }
+            None => return Ok(None),
+        };
+
+        let message = from_slice::<BigEndian, Message>(stored.raw_message.as_ref())
+            .map_err(|e| anyhow!("Failed to deserialize message: {:?}", e))?;
+
+        Ok(Some(MessageState::new(
+            message,
+            stored.raw_message,
+            ProofSet {
+                wormhole_merkle_proof: WormholeMerkleMessageProof {
+                    proof: MerklePath::<Keccak160>::new(stored.proof),
+                    vaa,
+                },
+            },
+            stored.slot,
+            stored.received_at,
+        )))
+    }
+
+    /// Look up a message state on disk. This mirrors `retrieve_message_state` for the in-memory
+    /// cache.
+    fn retrieve_message_state_from_disk(
+        &self,
+        key: &MessageStateKey,
+        request_time: RequestTime,
+    ) -> Result<Option<MessageState>> {
+        let prefix = message_state_key_prefix(key)?;
+
+        let value = match request_time {
+            RequestTime::Latest => self.message_states.scan_prefix(&prefix).next_back(),
+            RequestTime::FirstAfter(time) => {
+                // If the requested time is before the oldest stored message, we are not sure that
+                // the oldest message is the closest one.
+                match self.message_states.scan_prefix(&prefix).next() {
+                    Some(oldest) => {
+                        let (oldest_key, _) = oldest?;
+                        let oldest_publish_time = &oldest_key[33..41];
+                        if encode_timestamp(time).as_slice() < oldest_publish_time {
+                            return Ok(None);
+                        }
+                    }
+                    None => return Ok(None),
+                }
+
+                let lookup_key = message_state_db_key(
+                    key,
+                    &MessageStateTime {
+                        publish_time: time,
+                        slot:         0,
+                    },
+                )?;
+
+                match prefix_successor(&prefix) {
+                    Some(successor) => self.message_states.range(lookup_key..successor).next(),
+                    None => self.message_states.range(lookup_key..).next(),
+                }
+            }
+            RequestTime::AtSlot(slot) => {
+                let slot_key = [prefix.as_slice(), &slot.to_be_bytes()].concat();
+                match self.message_state_slots.get(slot_key)? {
+                    Some(db_key) => self
+                        .message_states
+                        .get(&db_key)
+                        .transpose()
+                        .map(|entry| entry.map(|value| (db_key, value))),
+                    None => None,
+                }
+            }
+        };
+
+        match value {
+            Some(entry) => {
+                let (_, value) = entry?;
+                self.decode_message_state(&value)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AggregateCache for DiskCache {
+    async fn message_state_keys(&self) -> Vec<MessageStateKey> {
+        self.memory.message_state_keys().await
+    }
+
+    async fn message_state_counts(&self) -> Vec<(MessageStateKey, usize)> {
+        self.memory.message_state_counts().await
+    }
+
+    async fn store_message_states(&self, message_states: Vec<MessageState>) -> Result<()> {
+        let mut entries = vec![];
+        for message_state in message_states.iter() {
+            let db_key = message_state_db_key(&message_state.key(), &message_state.time())?;
+            let stored = StoredMessageState {
+                slot:        message_state.slot,
+                raw_message: message_state.raw_message.clone(),
+                proof:       message_state
+                    .proof_set
+                    .wormhole_merkle_proof
+                    .proof
+                    .to_bytes()
+                    .chunks_exact(20)
+                    .map(|hash| hash.try_into())
+                    .collect::<Result<Vec<[u8; 20]>, _>>()?,
+                received_at: message_state.received_at,
+            };
+
+            entries.push((db_key, stored.try_to_vec()?, message_state.received_at));
+        }
+
+        self.disk
+            .run(move |disk| {
+                for (db_key, value, received_at) in entries {
+                    disk.message_state_slots
+                        .insert(message_state_slot_key(&db_key)?, db_key.as_slice())?;
+                    disk.insert_with_expiry(TreeId::MessageStates, &db_key, value, received_at)?;
+                }
+                Ok(())
+            })
+            .await?;
+        self.memory.store_message_states(message_states).await?;
+        self.maybe_prune().await
+    }
+
+    async fn fetch_message_states(
+        &self,
+        ids: Vec<FeedId>,
+        request_time: RequestTime,
+        filter: MessageStateFilter,
+    ) -> Result<Vec<MessageState>> {
+        let message_types: Vec<MessageType> = match filter {
+            MessageStateFilter::All => MessageType::iter().collect(),
+            MessageStateFilter::Only(t) => vec![t],
+        };
+
+        let mut message_states = vec![];
+        for id in ids {
+            for message_type in message_types.iter() {
+                let key = MessageStateKey {
+                    feed_id: id,
+                    type_:   *message_type,
+                };
+
+                let message_state =
+                    match retrieve_message_state(&self.memory, key.clone(), request_time.clone()) {
+                        Some(message_state) => message_state,
+                        None => {
+                            let request_time = request_time.clone();
+                            self.disk
+                                .run(move |disk| {
+                                    disk.retrieve_message_state_from_disk(&key, request_time)
+                                })
+                                .await?
+                                .ok_or(anyhow!("Message not found"))?
+                        }
+                    };
+
+                message_states.push(message_state);
+            }
+        }
+
+        Ok(message_states)
+    }
+
+    async fn fetch_message_states_in_range(
+        &self,
+        key: MessageStateKey,
+        range: MessageStateTimeRange,
+        limit: usize,
+    ) -> Result<Vec<MessageState>> {
+        if is_empty_range(&range) {
+            return Ok(vec![]);
+        }
+
+        // The disk usually has everything the memory has, but the memory can still hold entries
+        // that are past the retention, so both are merged.
+        let mut message_states: BTreeMap<MessageStateTime, MessageState> = self
+            .memory
+            .fetch_message_states_in_range(key.clone(), range.clone(), limit)
+            .await?
+            .into_iter()
+            .map(|message_state| (message_state.time(), message_state))
+            .collect();
+
+        let prefix = message_state_key_prefix(&key)?;
+        let to_db_key = |bound: Bound<MessageStateTime>| -> Result<Bound<Vec<u8>>> {
+            Ok(match bound {
+                Bound::Included(time) => Bound::Included(message_state_db_key(&key, &time)?),
+                Bound::Excluded(time) => Bound::Excluded(message_state_db_key(&key, &time)?),
+                Bound::Unbounded => Bound::Unbounded,
+            })
+        };
+        let start = match to_db_key(range.0)? {
+            Bound::Unbounded => Bound::Included(prefix.clone()),
+            start => start,
+        };
+        let end = match to_db_key(range.1)? {
+            Bound::Unbounded => match prefix_successor(&prefix) {
+                Some(successor) => Bound::Excluded(successor),
+                None => Bound::Unbounded,
+            },
+            end => end,
+        };
+
+        let stored_message_states = self
+            .disk
+            .run(move |disk| {
+                let mut message_states = vec![];
+                for entry in disk.message_states.range((start, end)).take(limit) {
+                    let (_, value) = entry?;
+                    message_states.extend(disk.decode_message_state(&value)?);
+                }
+                Ok(message_states)
+            })
+            .await?;
+        for message_state in stored_message_states {
+            message_states
+                .entry(message_state.time())
+                .or_insert(message_state);
+        }
+
+        Ok(message_states.into_values().take(limit).collect())
+    }
+
+    async fn store_accumulator_messages(
+        &self,
+        accumulator_messages: AccumulatorMessages,
+    ) -> Result<()> {
+        let db_key = accumulator_messages.slot.to_be_bytes();
+        let value = accumulator_messages.try_to_vec()?;
+        self.disk
+            .run(move |disk| {
+                disk.insert_with_expiry(TreeId::AccumulatorMessages, &db_key, value, now())
+            })
+            .await?;
+        self.memory
+            .store_accumulator_messages(accumulator_messages)
+            .await
+    }
+
+    async fn fetch_accumulator_messages(&self, slot: Slot) -> Result<Option<AccumulatorMessages>> {
+        if let Some(accumulator_messages) = self.memory.fetch_accumulator_messages(slot).await? {
+            return Ok(Some(accumulator_messages));
+        }
+
+        self.disk
+            .run(
+                move |disk| match disk.accumulator_messages.get(slot.to_be_bytes())? {
+                    Some(value) => Ok(Some(AccumulatorMessages::try_from_slice(&value)?)),
+                    None => Ok(None),
+                },
+            )
+            .await
+    }
+
+    async fn store_wormhole_merkle_state(
+        &self,
+        wormhole_merkle_state: WormholeMerkleState,
+    ) -> Result<()> {
+        let db_key = wormhole_merkle_state.root.slot.to_be_bytes();
+        let stored = StoredWormholeMerkleState {
+            slot:      wormhole_merkle_state.root.slot,
+            ring_size: wormhole_merkle_state.root.ring_size,
+            root:      wormhole_merkle_state.root.root,
+            vaa:       wormhole_merkle_state.vaa.clone(),
+        };
+
+        let value = stored.try_to_vec()?;
+        self.disk
+            .run(move |disk| {
+                disk.insert_with_expiry(TreeId::WormholeMerkleStates, &db_key, value, now())
+            })
+            .await?;
+        self.memory
+            .store_wormhole_merkle_state(wormhole_merkle_state)
+            .await
+    }
+
+    async fn fetch_wormhole_merkle_state(&self, slot: Slot) -> Result<Option<WormholeMerkleState>> {
+        if let Some(wormhole_merkle_state) = self.memory.fetch_wormhole_merkle_state(slot).await? {
+            return Ok(Some(wormhole_merkle_state));
+        }
+
+        self.disk
+            .run(
+                move |disk| match disk.wormhole_merkle_states.get(slot.to_be_bytes())? {
+                    Some(value) => Ok(Some(DiskStore::decode_wormhole_merkle_state(&value)?)),
+                    None => Ok(None),
+                },
+            )
+            .await
+    }
+
+    async fn fetch_wormhole_merkle_state_slots_after(
+        &self,
+        slot: Slot,
+        limit: usize,
+    ) -> Result<Vec<Slot>> {
+        // Every state in memory is also on disk, so the disk alone has the full answer.
+        let slot = match slot.checked_add(1) {
+            Some(slot) => slot,
+            None => return Ok(vec![]),
+        };
+
+        self.disk
+            .run(move |disk| {
+                disk.wormhole_merkle_states
+                    .range(slot.to_be_bytes()..)
+                    .keys()
+                    .take(limit)
+                    .map(|db_key| -> Result<Slot> {
+                        Ok(Slot::from_be_bytes(db_key?.as_ref().try_into()?))
+                    })
+                    .collect()
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::cache::test::{
+            create_empty_accumulator_messages_at_slot,
+            create_empty_wormhole_merkle_state_at_slot,
+        },
+        pythnet_sdk::wire::to_vec,
+        std::path::PathBuf,
+    };
+
+    /// Message states read back from disk are re-parsed from their raw message, so unlike the
+    /// in-memory dummies the raw message has to be an actual encoding of the message.
+    fn create_dummy_price_feed_message_state(
+        feed_id: FeedId,
+        publish_time: i64,
+        slot: Slot,
+    ) -> MessageState {
+        let mut message_state = crate::state::cache::test::create_dummy_price_feed_message_state(
+            feed_id,
+            publish_time,
+            slot,
+        );
+        message_state.raw_message = to_vec::<_, BigEndian>(&message_state.message).unwrap();
+        message_state
+    }
+
+    fn temp_db_path() -> PathBuf {
+        std::env::temp_dir().join(format!("hermes-disk-cache-{}", rand::random::<u64>()))
+    }
+
+    async fn open_disk_cache(path: &Path, cache_size: u64) -> DiskCache {
+        DiskCache::open(path, cache_size, Duration::from_secs(u64::MAX / 2), None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn test_disk_cache_reloads_state_after_reopen() {
+        let path = temp_db_path();
+
+        let message_state = create_dummy_price_feed_message_state([1; 32], 10, 5);
+        let accumulator_messages = create_empty_accumulator_messages_at_slot(5);
+        let wormhole_merkle_state = create_empty_wormhole_merkle_state_at_slot(5);
+
+        {
+            let cache = open_disk_cache(&path, 2).await;
+            cache
+                .store_wormhole_merkle_state(wormhole_merkle_state.clone())
+                .await
+                .unwrap();
+            cache
+                .store_accumulator_messages(accumulator_messages.clone())
+                .await
+                .unwrap();
+            cache
+                .store_message_states(vec![message_state.clone()])
+                .await
+                .unwrap();
+        }
+
+        // Reopening the database should bring back all the stored state.
+        let cache = open_disk_cache(&path, 2).await;
+
+        assert_eq!(cache.message_state_keys().await, vec![message_state.key()]);
+        assert_eq!(
+            cache
+                .fetch_message_states(
+                    vec![[1; 32]],
+                    RequestTime::Latest,
+                    MessageStateFilter::Only(MessageType::PriceFeedMessage),
+                )
+                .await
+                .unwrap(),
+            vec![message_state]
+        );
+        assert_eq!(
+            cache.fetch_accumulator_messages(5).await.unwrap(),
+            Some(accumulator_messages)
+        );
+        assert_eq!(
+            cache.fetch_wormhole_merkle_state(5).await.unwrap(),
+            Some(wormhole_merkle_state)
+        );
+        assert_eq!(
+            cache
+                .fetch_wormhole_merkle_state_slots_after(4, 10)
+                .await
+                .unwrap(),
+            vec![5]
+        );
+
+        drop(cache);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_disk_cache_serves_message_states_evicted_from_memory() {
+        let path = temp_db_path();
+
+        // Initialize a disk cache that holds a single message state per key in memory.
+        let cache = open_disk_cache(&path, 1).await;
+
+        for (publish_time, slot) in [(10, 5), (13, 10), (20, 14)] {
+            cache
+                .store_wormhole_merkle_state(create_empty_wormhole_merkle_state_at_slot(slot))
+                .await
+                .unwrap();
+            cache
+                .store_message_states(vec![create_dummy_price_feed_message_state(
+                    [1; 32],
+                    publish_time,
+                    slot,
+                )])
+                .await
+                .unwrap();
+        }
+
+        // The message at time 10 is gone from memory but should still be served from disk.
+        let message_states = cache
+            .fetch_message_states(
+                vec![[1; 32]],
+                RequestTime::FirstAfter(10),
+                MessageStateFilter::Only(MessageType::PriceFeedMessage),
+            )
+            .await
+            .unwrap();
+        assert_eq!(message_states[0].slot, 5);
+
+        // Querying the first after pub time 11 should return the message at time 13.
+        let message_states = cache
+            .fetch_message_states(
+                vec![[1; 32]],
+                RequestTime::FirstAfter(11),
+                MessageStateFilter::Only(MessageType::PriceFeedMessage),
+            )
+            .await
+            .unwrap();
+        assert_eq!(message_states[0].slot, 10);
+
+        // Lookups by slot should work too.
+        let message_states = cache
+            .fetch_message_states(
+                vec![[1; 32]],
+                RequestTime::AtSlot(10),
+                MessageStateFilter::Only(MessageType::PriceFeedMessage),
+            )
+            .await
+            .unwrap();
+        assert_eq!(message_states[0].time().publish_time, 13);
+
+        // Slots without a message state are not found.
+        assert!(cache
+            .fetch_message_states(
+                vec![[1; 32]],
+                RequestTime::AtSlot(7),
+                MessageStateFilter::Only(MessageType::PriceFeedMessage),
+            )
+            .await
+            .is_err());
+
+        // Querying before the oldest stored message should still fail.
+        assert!(cache
+            .fetch_message_states(
+                vec![[1; 32]],
+                RequestTime::FirstAfter(9),
+                MessageStateFilter::Only(MessageType::PriceFeedMessage),
+            )
+            .await
+            .is_err());
+
+        drop(cache);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_disk_cache_prunes_entries_past_retention() {
+        let path = temp_db_path();
+
+        // With no retention everything received in the past is pruned.
+        let cache = DiskCache::open(&path, 1, Duration::ZERO, None)
+            .await
+            .unwrap();
+
+        // Dummy message states are received at their publish time, which is long ago.
+        for (publish_time, slot) in [(10, 5), (13, 10)] {
+            cache
+                .store_message_states(vec![create_dummy_price_feed_message_state(
+                    [1; 32],
+                    publish_time,
+                    slot,
+                )])
+                .await
+                .unwrap();
+        }
+
+        cache.prune().await.unwrap();
+        assert!(cache.disk.message_states.is_empty());
+        assert!(cache.disk.message_state_slots.is_empty());
+        assert!(cache.disk.expiry.is_empty());
+
+        // The latest message state is still kept in memory.
+        assert!(cache
+            .fetch_message_states(
+                vec![[1; 32]],
+                RequestTime::AtSlot(10),
+                MessageStateFilter::Only(MessageType::PriceFeedMessage),
+            )
+            .await
+            .is_ok());
+
+        drop(cache);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_disk_cache_restore_keeps_a_single_expiry_entry() {
+        let path = temp_db_path();
+        let cache = open_disk_cache(&path, 1).await;
+
+        for _ in 0..2 {
+            cache
+                .store_wormhole_merkle_state(create_empty_wormhole_merkle_state_at_slot(5))
+                .await
+                .unwrap();
+            cache
+                .store_accumulator_messages(create_empty_accumulator_messages_at_slot(5))
+                .await
+                .unwrap();
+            cache
+                .store_message_states(vec![create_dummy_price_feed_message_state([1; 32], 10, 5)])
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(cache.disk.message_states.len(), 1);
+        assert_eq!(cache.disk.message_state_slots.len(), 1);
+        assert_eq!(cache.disk.expiry.len(), 3);
+
+        drop(cache);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
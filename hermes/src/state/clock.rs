@@ -0,0 +1,46 @@
+//! The time Hermes runs at.
+//!
+//! This is the system time, except when replaying a recording where it follows the receive times
+//! of the replayed updates, so that the readiness and the feed health are judged as they were when
+//! the updates were recorded.
+
+#[cfg(test)]
+use mock_instant::{
+    Instant,
+    SystemTime,
+    UNIX_EPOCH,
+};
+#[cfg(not(test))]
+use std::time::{
+    Instant,
+    SystemTime,
+    UNIX_EPOCH,
+};
+use std::{
+    sync::Mutex,
+    time::Duration,
+};
+
+#[derive(Default)]
+pub struct Clock {
+    /// The receive time of the latest replayed update and when it was replayed.
+    replayed: Mutex<Option<(Duration, Instant)>>,
+}
+
+impl Clock {
+    /// The current time since the unix epoch.
+    pub fn now(&self) -> Duration {
+        match *self.replayed.lock().unwrap() {
+            Some((received_at, replayed_at)) => received_at + replayed_at.elapsed(),
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Move the clock to the receive time of a replayed update. Time keeps passing at the normal
+    /// pace from there until the next replayed update.
+    pub fn replayed(&self, received_at: Duration) {
+        *self.replayed.lock().unwrap() = Some((received_at, Instant::now()));
+    }
+}
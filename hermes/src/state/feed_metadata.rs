@@ -0,0 +1,265 @@
+//! A catalogue of metadata about the price feeds, such as their symbol, so that users can find the
+//! feeds they are interested in.
+//!
+//! The catalogue is built from the Pyth product accounts on Pythnet, and can be completed or
+//! corrected with a static JSON file of overrides, for example:
+//!
+//! ```json
+//! [
+//!     {
+//!         "id": "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+//!         "description": "Bitcoin / US Dollar"
+//!     }
+//! ]
+//! ```
+
+use {
+    anyhow::Result,
+    pyth_sdk::PriceIdentifier,
+    serde::Deserialize,
+    std::{
+        collections::HashMap,
+        path::Path,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeedMetadata {
+    pub id:          PriceIdentifier,
+    /// The symbol of the feed, e.g. `Crypto.BTC/USD`.
+    pub symbol:      Option<String>,
+    /// The type of asset the feed prices, e.g. `Crypto` or `FX`.
+    pub asset_type:  Option<String>,
+    pub base:        Option<String>,
+    pub quote:       Option<String>,
+    pub description: Option<String>,
+}
+
+impl FeedMetadata {
+    pub fn new(id: PriceIdentifier) -> Self {
+        Self {
+            id,
+            symbol: None,
+            asset_type: None,
+            base: None,
+            quote: None,
+            description: None,
+        }
+    }
+
+    /// Overwrite the fields that are set in the override.
+    fn apply(&mut self, metadata_override: &FeedMetadataOverride) {
+        let fields = [
+            (&mut self.symbol, &metadata_override.symbol),
+            (&mut self.asset_type, &metadata_override.asset_type),
+            (&mut self.base, &metadata_override.base),
+            (&mut self.quote, &metadata_override.quote),
+            (&mut self.description, &metadata_override.description),
+        ];
+        for (field, value) in fields {
+            if value.is_some() {
+                *field = value.clone();
+            }
+        }
+    }
+
+    fn matches(&self, query: &FeedMetadataQuery) -> bool {
+        let matches_asset_type = match &query.asset_type {
+            Some(asset_type) => self
+                .asset_type
+                .as_ref()
+                .map_or(false, |value| value.eq_ignore_ascii_case(asset_type)),
+            None => true,
+        };
+
+        let matches_query = match &query.query {
+            Some(text) => {
+                let text = text.to_lowercase();
+                [&self.symbol, &self.base, &self.quote, &self.description]
+                    .into_iter()
+                    .flatten()
+                    .any(|value| value.to_lowercase().contains(&text))
+            }
+            None => true,
+        };
+
+        matches_asset_type && matches_query
+    }
+}
+
+/// An entry of the overrides file, the fields that are not set are kept from Pythnet.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FeedMetadataOverride {
+    #[serde(with = "crate::serde::hex")]
+    id:          [u8; 32],
+    symbol:      Option<String>,
+    asset_type:  Option<String>,
+    base:        Option<String>,
+    quote:       Option<String>,
+    description: Option<String>,
+}
+
+pub fn load_overrides(path: &Path) -> Result<Vec<FeedMetadataOverride>> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Build the catalogue from the metadata read from Pythnet and the overrides, which take
+/// precedence. Overrides of feeds that are not on Pythnet add them to the catalogue.
+pub fn build_catalogue(
+    metadata: Vec<FeedMetadata>,
+    overrides: &[FeedMetadataOverride],
+) -> HashMap<PriceIdentifier, FeedMetadata> {
+    let mut catalogue: HashMap<_, _> = metadata
+        .into_iter()
+        .map(|metadata| (metadata.id, metadata))
+        .collect();
+
+    for metadata_override in overrides {
+        let id = PriceIdentifier::new(metadata_override.id);
+        catalogue
+            .entry(id)
+            .or_insert_with(|| FeedMetadata::new(id))
+            .apply(metadata_override);
+    }
+
+    catalogue
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FeedMetadataQuery {
+    /// Text to look for in the symbol, base, quote or description, ignoring case.
+    pub query:      Option<String>,
+    /// Asset type the feeds must have, ignoring case.
+    pub asset_type: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub trait FeedMetadataCatalogue {
+    async fn store_feed_metadata(&self, catalogue: HashMap<PriceIdentifier, FeedMetadata>);
+    /// The feeds matching the query, sorted by symbol.
+    async fn search_feed_metadata(&self, query: &FeedMetadataQuery) -> Vec<FeedMetadata>;
+}
+
+#[async_trait::async_trait]
+impl FeedMetadataCatalogue for crate::state::State {
+    async fn store_feed_metadata(&self, catalogue: HashMap<PriceIdentifier, FeedMetadata>) {
+        *self.feed_metadata.write().await = catalogue;
+    }
+
+    async fn search_feed_metadata(&self, query: &FeedMetadataQuery) -> Vec<FeedMetadata> {
+        let mut results: Vec<_> = self
+            .feed_metadata
+            .read()
+            .await
+            .values()
+            .filter(|metadata| metadata.matches(query))
+            .cloned()
+            .collect();
+        results.sort_by(|a, b| (&a.symbol, a.id).cmp(&(&b.symbol, b.id)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::test::setup_state,
+    };
+
+    fn metadata(seed: u8, symbol: &str, asset_type: &str, description: &str) -> FeedMetadata {
+        let (base, quote) = symbol.split_once('.').unwrap().1.split_once('/').unwrap();
+        FeedMetadata {
+            id:          PriceIdentifier::new([seed; 32]),
+            symbol:      Some(symbol.to_owned()),
+            asset_type:  Some(asset_type.to_owned()),
+            base:        Some(base.to_owned()),
+            quote:       Some(quote.to_owned()),
+            description: Some(description.to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let overrides: Vec<FeedMetadataOverride> = serde_json::from_str(&format!(
+            r#"[
+                {{"id": "{}", "description": "Bitcoin"}},
+                {{"id": "0x{}", "symbol": "Equity.US.AAPL/USD"}}
+            ]"#,
+            hex::encode([1; 32]),
+            hex::encode([2; 32]),
+        ))
+        .unwrap();
+
+        let catalogue = build_catalogue(
+            vec![metadata(
+                1,
+                "Crypto.BTC/USD",
+                "Crypto",
+                "BITCOIN / US DOLLAR",
+            )],
+            &overrides,
+        );
+
+        let btc = &catalogue[&PriceIdentifier::new([1; 32])];
+        assert_eq!(btc.description.as_deref(), Some("Bitcoin"));
+        assert_eq!(btc.symbol.as_deref(), Some("Crypto.BTC/USD"));
+
+        let aapl = &catalogue[&PriceIdentifier::new([2; 32])];
+        assert_eq!(aapl.symbol.as_deref(), Some("Equity.US.AAPL/USD"));
+        assert_eq!(aapl.asset_type, None);
+    }
+
+    #[tokio::test]
+    async fn test_search_feed_metadata() {
+        let (state, _) = setup_state(10).await;
+        state
+            .store_feed_metadata(build_catalogue(
+                vec![
+                    metadata(1, "Crypto.BTC/USD", "Crypto", "BITCOIN / US DOLLAR"),
+                    metadata(2, "Crypto.ETH/USD", "Crypto", "ETHEREUM / US DOLLAR"),
+                    metadata(3, "FX.EUR/USD", "FX", "EURO / US DOLLAR"),
+                    metadata(4, "Crypto.WBTC/BTC", "Crypto", "WRAPPED BITCOIN / BITCOIN"),
+                ],
+                &[],
+            ))
+            .await;
+
+        let symbols = |results: Vec<FeedMetadata>| {
+            results
+                .into_iter()
+                .map(|metadata| metadata.symbol.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            symbols(
+                state
+                    .search_feed_metadata(&FeedMetadataQuery {
+                        query:      Some("btc".to_owned()),
+                        asset_type: None,
+                    })
+                    .await
+            ),
+            vec!["Crypto.BTC/USD", "Crypto.WBTC/BTC"]
+        );
+        assert_eq!(
+            symbols(
+                state
+                    .search_feed_metadata(&FeedMetadataQuery {
+                        query:      Some("dollar".to_owned()),
+                        asset_type: Some("crypto".to_owned()),
+                    })
+                    .await
+            ),
+            vec!["Crypto.BTC/USD", "Crypto.ETH/USD"]
+        );
+        assert_eq!(
+            state
+                .search_feed_metadata(&FeedMetadataQuery::default())
+                .await
+                .len(),
+            4
+        );
+    }
+}
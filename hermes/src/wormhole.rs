@@ -1,28 +1,38 @@
 use {
     super::State,
-    crate::aggregate::Update,
+    crate::{
+        aggregate::Update,
+        metrics::{
+            VaaRejectionLabels,
+            VaaRejectionReason,
+        },
+    },
     anyhow::{
         anyhow,
         Result,
     },
+    byteorder::{
+        BigEndian,
+        ReadBytesExt,
+    },
+    futures::StreamExt,
     pythnet_sdk::wire::v1::{
         WormholeMessage,
         WormholePayload,
     },
-    secp256k1::{
-        ecdsa::{
-            RecoverableSignature,
-            RecoveryId,
-        },
-        Message,
-        Secp256k1,
-    },
     serde_wormhole::RawMessage,
-    sha3::{
-        Digest,
-        Keccak256,
+    std::{
+        io::Read,
+        sync::{
+            atomic::Ordering,
+            Arc,
+        },
+        time::{
+            SystemTime,
+            UNIX_EPOCH,
+        },
     },
-    std::sync::Arc,
+    tokio::sync::mpsc,
     tracing::trace,
     wormhole_sdk::{
         vaa::{
@@ -35,13 +45,48 @@ use {
     },
 };
 
+pub mod verification;
 
 pub type VaaBytes = Vec<u8>;
 const OBSERVED_CACHE_SIZE: usize = 1000;
+const VAA_CHANNEL_SIZE: usize = 1000;
+const MAX_CONCURRENT_VAAS: usize = 64;
+
+/// How long a replaced guardian set keeps signing valid VAAs, in seconds, until the value of the
+/// Wormhole bridge config is fetched.
+pub const DEFAULT_GUARDIAN_SET_EXPIRATION_TIME: u32 = 24 * 60 * 60;
+
+/// The emitter of the Wormhole core governance VAAs on Solana.
+pub const GOVERNANCE_EMITTER_ADDRESS: [u8; 32] = {
+    let mut address = [0; 32];
+    address[31] = 4;
+    address
+};
+
+/// The module of the Wormhole core contract in governance payloads, "Core" left-padded to 32
+/// bytes.
+const CORE_MODULE: [u8; 32] = {
+    let mut module = [0; 32];
+    module[28] = b'C';
+    module[29] = b'o';
+    module[30] = b'r';
+    module[31] = b'e';
+    module
+};
+
+const GUARDIAN_SET_UPGRADE_ACTION: u8 = 2;
 
 #[derive(Eq, PartialEq, Clone, Hash, Debug)]
 pub struct GuardianSet {
-    pub keys: Vec<[u8; 20]>,
+    pub keys:            Vec<[u8; 20]>,
+    /// The unix timestamp after which the set is no longer valid, if it has been replaced.
+    pub expiration_time: Option<u32>,
+}
+
+impl GuardianSet {
+    pub fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expiration_time, Some(expiration_time) if now > expiration_time as u64)
+    }
 }
 
 impl std::fmt::Display for GuardianSet {
@@ -70,9 +115,9 @@ pub struct BridgeData {
 
 /// BridgeConfig extracted from wormhole bridge account, due to no API.
 #[derive(borsh::BorshDeserialize)]
-#[allow(dead_code)]
 pub struct BridgeConfig {
     pub guardian_set_expiration_time: u32,
+    #[allow(dead_code)]
     pub fee:                          u64,
 }
 
@@ -85,6 +130,52 @@ pub struct GuardianSetData {
     pub expiration_time: u32,
 }
 
+/// A `GuardianSetUpgrade` governance action of the Wormhole core contract.
+#[derive(Debug, PartialEq)]
+pub struct GuardianSetUpgrade {
+    pub new_guardian_set_index: u32,
+    pub new_guardian_set:       GuardianSet,
+}
+
+impl GuardianSetUpgrade {
+    /// Parse the payload of a governance VAA. Returns `None` if it is another governance action.
+    pub fn try_from_payload(mut payload: &[u8]) -> Result<Option<Self>> {
+        let mut module = [0; 32];
+        payload.read_exact(&mut module)?;
+        let action = payload.read_u8()?;
+        if module != CORE_MODULE || action != GUARDIAN_SET_UPGRADE_ACTION {
+            return Ok(None);
+        }
+
+        // Guardian set upgrades apply to all chains.
+        let chain = payload.read_u16::<BigEndian>()?;
+        if chain != 0 {
+            return Err(anyhow!("Guardian set upgrade targets chain {}", chain));
+        }
+
+        let new_guardian_set_index = payload.read_u32::<BigEndian>()?;
+        let num_keys = payload.read_u8()?;
+        let mut keys = Vec::with_capacity(num_keys.into());
+        for _ in 0..num_keys {
+            let mut key = [0; 20];
+            payload.read_exact(&mut key)?;
+            keys.push(key);
+        }
+
+        Ok(Some(Self {
+            new_guardian_set_index,
+            new_guardian_set: GuardianSet {
+                keys,
+                expiration_time: None,
+            },
+        }))
+    }
+}
+
+fn unix_timestamp_now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
 /// Verifies a VAA to ensure it is signed by the Wormhole guardian set.
 pub async fn verify_vaa<'a>(
     state: &State,
@@ -92,15 +183,26 @@ pub async fn verify_vaa<'a>(
 ) -> Result<Vaa<&'a RawMessage>> {
     let (header, body): (Header, Body<&RawMessage>) = vaa.into();
     let digest = body.digest()?;
-    let guardian_set = state.guardian_set.read().await;
-    let guardian_set = guardian_set
-        .get(&header.guardian_set_index)
-        .ok_or_else(|| {
-            anyhow!(
-                "Message signed by an unknown guardian set: {}",
+    let keys = {
+        let guardian_set = state.guardian_set.read().await;
+        let guardian_set = guardian_set
+            .get(&header.guardian_set_index)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Message signed by an unknown guardian set: {}",
+                    header.guardian_set_index
+                )
+            })?;
+
+        if guardian_set.is_expired(unix_timestamp_now()?) {
+            return Err(anyhow!(
+                "Message signed by an expired guardian set: {}",
                 header.guardian_set_index
-            )
-        })?;
+            ));
+        }
+
+        guardian_set.keys.clone()
+    };
 
     // TODO: This check bypass checking the signatures on tests.
     // Ideally we need to test the signatures but currently Wormhole
@@ -108,72 +210,19 @@ pub async fn verify_vaa<'a>(
     let quorum = if cfg!(test) {
         0
     } else {
-        (guardian_set.keys.len() * 2) / 3 + 1
+        (keys.len() * 2) / 3 + 1
     };
 
-    let mut last_signer_id: Option<usize> = None;
-    let mut signatures = vec![];
-    for signature in header.signatures.into_iter() {
-        // Do not collect more signatures than necessary to reduce
-        // on-chain gas spent on signature verification.
-        if signatures.len() >= quorum {
-            break;
-        }
-
-        let signer_id: usize = signature.index.into();
-
-        if signer_id >= guardian_set.keys.len() {
-            return Err(anyhow!(
-                "Signer ID is out of range. Signer ID: {}, guardian set size: {}",
-                signer_id,
-                guardian_set.keys.len()
-            ));
-        }
-
-        if let Some(true) = last_signer_id.map(|v| v >= signer_id) {
-            return Err(anyhow!(
-                "Signatures are not sorted by signer ID. Last signer ID: {:?}, current signer ID: {}",
-                last_signer_id,
-                signer_id
-            ));
-        }
-
-        let sig = signature.signature;
-
-        // Recover the public key from ecdsa signature from [u8; 65] that has (v, r, s) format
-        let recid = RecoveryId::from_i32(sig[64].into())?;
-
-        let secp = Secp256k1::new();
-
-        // To get the address we need to use the uncompressed public key
-        let pubkey: &[u8; 65] = &secp
-            .recover_ecdsa(
-                &Message::from_slice(&digest.secp256k_hash)?,
-                &RecoverableSignature::from_compact(&sig[..64], recid)?,
-            )?
-            .serialize_uncompressed();
-
-        // The address is the last 20 bytes of the Keccak256 hash of the public key
-        let mut keccak = Keccak256::new();
-        keccak.update(&pubkey[1..]);
-        let address: [u8; 32] = keccak.finalize().into();
-        let address: [u8; 20] = address[address.len() - 20..].try_into()?;
-
-        if guardian_set.keys.get(signer_id) == Some(&address) {
-            signatures.push(signature);
-        }
-
-        last_signer_id = Some(signer_id);
-    }
-
-    // Check if we have enough correct signatures
-    if signatures.len() < quorum {
-        return Err(anyhow!(
-            "Not enough correct signatures. Expected {:?}, received {:?}",
+    let signatures = state
+        .vaa_verifier
+        .verify(
+            header.guardian_set_index,
+            keys,
             quorum,
-            signatures.len()
-        ));
-    }
+            digest.secp256k_hash,
+            header.signatures,
+        )
+        .await?;
 
     Ok((
         Header {
@@ -186,11 +235,78 @@ pub async fn verify_vaa<'a>(
 }
 
 /// Update the guardian set with the given ID in the state.
-pub async fn update_guardian_set(state: &State, id: u32, guardian_set: GuardianSet) {
+pub async fn update_guardian_set(state: &State, id: u32, mut guardian_set: GuardianSet) {
     let mut guardian_sets = state.guardian_set.write().await;
+
+    // A set replaced by a governance VAA expires before the RPC catches up with the upgrade, keep
+    // its expiration until then.
+    if let Some(existing) = guardian_sets.get(&id) {
+        guardian_set.expiration_time = guardian_set.expiration_time.or(existing.expiration_time);
+    }
+
     guardian_sets.insert(id, guardian_set);
 }
 
+/// Install the guardian set of a `GuardianSetUpgrade` governance VAA signed by the current guardian
+/// set. As in the Wormhole contracts, the replaced set stays valid until the guardian set
+/// expiration time elapses so that VAAs signed during the rotation are still accepted.
+pub async fn process_governance_vaa(state: &State, vaa: Vaa<&RawMessage>) -> Result<()> {
+    let upgrade = match GuardianSetUpgrade::try_from_payload(vaa.payload)? {
+        Some(upgrade) => upgrade,
+        None => return Ok(()),
+    };
+
+    let current_index = *state
+        .guardian_set
+        .read()
+        .await
+        .keys()
+        .last()
+        .ok_or_else(|| anyhow!("No guardian set is known yet"))?;
+
+    // Governance VAAs are gossiped again long after they were applied.
+    if upgrade.new_guardian_set_index <= current_index {
+        return Ok(());
+    }
+
+    if upgrade.new_guardian_set_index != current_index + 1 {
+        return Err(anyhow!(
+            "Guardian set upgrade skips from guardian set {} to {}",
+            current_index,
+            upgrade.new_guardian_set_index
+        ));
+    }
+
+    if vaa.guardian_set_index != current_index {
+        return Err(anyhow!(
+            "Guardian set upgrade is signed by guardian set {} instead of the current one {}",
+            vaa.guardian_set_index,
+            current_index
+        ));
+    }
+
+    verify_vaa(state, vaa).await?;
+
+    let expiration_time =
+        unix_timestamp_now()? + state.guardian_set_expiration_time.load(Ordering::Acquire) as u64;
+    let mut guardian_sets = state.guardian_set.write().await;
+    if guardian_sets.contains_key(&upgrade.new_guardian_set_index) {
+        return Ok(());
+    }
+    if let Some(current) = guardian_sets.get_mut(&current_index) {
+        current.expiration_time = Some(expiration_time.try_into()?);
+    }
+
+    tracing::info!(
+        guardian_set_index = upgrade.new_guardian_set_index,
+        guardian_set = %upgrade.new_guardian_set,
+        "Installed guardian set from governance VAA."
+    );
+    guardian_sets.insert(upgrade.new_guardian_set_index, upgrade.new_guardian_set);
+
+    Ok(())
+}
+
 /// Process a VAA from the Wormhole p2p and aggregate it if it is
 /// verified and is new and belongs to the Accumulator.
 pub async fn forward_vaa(state: Arc<State>, vaa_bytes: VaaBytes) {
@@ -199,10 +315,26 @@ pub async fn forward_vaa(state: Arc<State>, vaa_bytes: VaaBytes) {
         Ok(vaa) => vaa,
         Err(e) => {
             tracing::error!(error = ?e, "Failed to deserialize VAA.");
+            state
+                .metrics
+                .vaas_rejected
+                .get_or_create(&VaaRejectionLabels {
+                    reason: VaaRejectionReason::Malformed,
+                })
+                .inc();
             return;
         }
     };
 
+    if vaa.emitter_chain == Chain::Solana
+        && vaa.emitter_address == Address(GOVERNANCE_EMITTER_ADDRESS)
+    {
+        if let Err(e) = process_governance_vaa(&state, vaa).await {
+            tracing::warn!(error = ?e, "Failed to process governance VAA.");
+        }
+        return;
+    }
+
     if vaa.emitter_chain != Chain::Pythnet
         || vaa.emitter_address != Address(pythnet_sdk::ACCUMULATOR_EMITTER_ADDRESS)
     {
@@ -222,8 +354,18 @@ pub async fn forward_vaa(state: Arc<State>, vaa_bytes: VaaBytes) {
     let vaa_timestamp = chrono::NaiveDateTime::from_timestamp_opt(vaa_timestamp as i64, 0).unwrap();
     let vaa_timestamp = vaa_timestamp.format("%Y-%m-%dT%H:%M:%S.%fZ").to_string();
     tracing::info!(slot = slot, vaa_timestamp = vaa_timestamp, "Observed VAA");
+    state.metrics.vaas_observed.inc();
+
+    let reject = |reason| {
+        state
+            .metrics
+            .vaas_rejected
+            .get_or_create(&VaaRejectionLabels { reason })
+            .inc();
+    };
 
     if state.observed_vaa_seqs.read().await.contains(&vaa.sequence) {
+        reject(VaaRejectionReason::Duplicate);
         return; // Ignore VAA if we have already seen it
     }
 
@@ -231,9 +373,11 @@ pub async fn forward_vaa(state: Arc<State>, vaa_bytes: VaaBytes) {
         Ok(vaa) => vaa,
         Err(e) => {
             trace!(error = ?e, "Ignoring invalid VAA.");
+            reject(VaaRejectionReason::InvalidSignature);
             return;
         }
     };
+    state.metrics.vaas_verified.inc();
 
     {
         let mut observed_vaa_seqs = state.observed_vaa_seqs.write().await;
@@ -241,6 +385,7 @@ pub async fn forward_vaa(state: Arc<State>, vaa_bytes: VaaBytes) {
         // Check again if we have already seen the VAA. Due to concurrency
         // the above check might not catch all the cases.
         if observed_vaa_seqs.contains(&vaa.sequence) {
+            reject(VaaRejectionReason::Duplicate);
             return; // Ignore VAA if we have already seen it
         }
         observed_vaa_seqs.insert(vaa.sequence);
@@ -256,3 +401,177 @@ pub async fn forward_vaa(state: Arc<State>, vaa_bytes: VaaBytes) {
         }
     });
 }
+
+/// A source of VAAs from the Wormhole network, such as the gossip network or a spy.
+#[async_trait::async_trait]
+pub trait VaaSource: Send {
+    /// A short name of the source, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Receive VAAs and send them to `vaa_sender` until Hermes shuts down. Sources are expected to
+    /// recover from transient failures on their own, returning an error stops the source.
+    async fn run(self: Box<Self>, vaa_sender: mpsc::Sender<VaaBytes>) -> Result<()>;
+}
+
+/// Run the given sources and pass the VAAs they receive to `forward_vaa`. The same VAA can be
+/// received from multiple sources, it is only processed once as `forward_vaa` drops the VAAs
+/// that have already been observed. Up to `MAX_CONCURRENT_VAAS` VAAs are processed at the same
+/// time so that their signatures are verified in parallel.
+pub async fn forward_vaas_from_sources(state: Arc<State>, sources: Vec<Box<dyn VaaSource>>) {
+    let (vaa_sender, mut vaa_receiver) = mpsc::channel(VAA_CHANNEL_SIZE);
+    for source in sources {
+        let vaa_sender = vaa_sender.clone();
+        tokio::spawn(async move {
+            let name = source.name();
+            tracing::info!(source = name, "Starting VAA source.");
+            if let Err(e) = source.run(vaa_sender).await {
+                tracing::error!(source = name, error = ?e, "VAA source failed.");
+            }
+        });
+    }
+    drop(vaa_sender);
+
+    futures::stream::poll_fn(|cx| vaa_receiver.poll_recv(cx))
+        .for_each_concurrent(MAX_CONCURRENT_VAAS, |vaa_bytes| {
+            forward_vaa(state.clone(), vaa_bytes)
+        })
+        .await;
+
+    if !crate::SHOULD_EXIT.load(Ordering::Acquire) {
+        // All the sources failed, there is no way to receive VAAs anymore.
+        tracing::error!("All VAA sources stopped.");
+        crate::SHOULD_EXIT.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::state::test::setup_state,
+    };
+
+    fn guardian_set_upgrade_payload(new_guardian_set_index: u32, keys: &[[u8; 20]]) -> Vec<u8> {
+        let mut payload = CORE_MODULE.to_vec();
+        payload.push(GUARDIAN_SET_UPGRADE_ACTION);
+        payload.extend(0u16.to_be_bytes());
+        payload.extend(new_guardian_set_index.to_be_bytes());
+        payload.push(keys.len() as u8);
+        for key in keys {
+            payload.extend(key);
+        }
+        payload
+    }
+
+    fn governance_vaa(guardian_set_index: u32, payload: &[u8]) -> VaaBytes {
+        serde_wormhole::to_vec(&Vaa {
+            nonce: 0,
+            version: 1,
+            sequence: 0,
+            timestamp: 0,
+            signatures: vec![], // We are bypassing signature check now
+            guardian_set_index,
+            emitter_chain: Chain::Solana,
+            emitter_address: Address(GOVERNANCE_EMITTER_ADDRESS),
+            consistency_level: 0,
+            payload: RawMessage::new(payload),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_guardian_set_upgrade_is_parsed() {
+        let payload = guardian_set_upgrade_payload(3, &[[1; 20], [2; 20]]);
+        assert_eq!(
+            GuardianSetUpgrade::try_from_payload(&payload).unwrap(),
+            Some(GuardianSetUpgrade {
+                new_guardian_set_index: 3,
+                new_guardian_set:       GuardianSet {
+                    keys:            vec![[1; 20], [2; 20]],
+                    expiration_time: None,
+                },
+            })
+        );
+
+        // Other governance actions are ignored.
+        let mut payload = guardian_set_upgrade_payload(3, &[[1; 20]]);
+        payload[32] = 1;
+        assert_eq!(
+            GuardianSetUpgrade::try_from_payload(&payload).unwrap(),
+            None
+        );
+
+        // Truncated payloads are rejected.
+        let payload = guardian_set_upgrade_payload(3, &[[1; 20]]);
+        assert!(GuardianSetUpgrade::try_from_payload(&payload[..payload.len() - 1]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_governance_vaa_rotates_guardian_set() {
+        let (state, _) = setup_state(10).await;
+
+        // Upgrades that skip a guardian set are rejected.
+        forward_vaa(
+            state.clone(),
+            governance_vaa(0, &guardian_set_upgrade_payload(2, &[[2; 20]])),
+        )
+        .await;
+        assert_eq!(state.guardian_set.read().await.len(), 1);
+
+        forward_vaa(
+            state.clone(),
+            governance_vaa(0, &guardian_set_upgrade_payload(1, &[[1; 20]])),
+        )
+        .await;
+
+        let guardian_sets = state.guardian_set.read().await;
+        assert_eq!(
+            guardian_sets.get(&1),
+            Some(&GuardianSet {
+                keys:            vec![[1; 20]],
+                expiration_time: None,
+            })
+        );
+        let expiration_time = guardian_sets.get(&0).unwrap().expiration_time.unwrap() as u64;
+        let now = unix_timestamp_now().unwrap();
+        assert!(expiration_time > now);
+        assert!(expiration_time <= now + DEFAULT_GUARDIAN_SET_EXPIRATION_TIME as u64);
+    }
+
+    #[tokio::test]
+    async fn test_expired_guardian_set_is_rejected() {
+        let (state, _) = setup_state(10).await;
+        state
+            .guardian_set
+            .write()
+            .await
+            .get_mut(&0)
+            .unwrap()
+            .expiration_time = Some(1);
+
+        let vaa_bytes = governance_vaa(0, &[]);
+        let vaa = serde_wormhole::from_slice::<Vaa<&RawMessage>>(&vaa_bytes).unwrap();
+        assert!(verify_vaa(&state, vaa).await.is_err());
+
+        // The RPC not knowing about the expiration yet does not revive the set.
+        update_guardian_set(
+            &state,
+            0,
+            GuardianSet {
+                keys:            vec![[0; 20]],
+                expiration_time: None,
+            },
+        )
+        .await;
+        assert_eq!(
+            state
+                .guardian_set
+                .read()
+                .await
+                .get(&0)
+                .unwrap()
+                .expiration_time,
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Signature verification of VAAs.
+//!
+//! Recovering the signers of a VAA is CPU bound, so it runs on the blocking thread pool instead of
+//! the async tasks, with at most one verification per CPU at a time and a single secp256k1 context
+//! shared between them. The results are cached by the digest of the signed VAA and the guardian set
+//! it is verified against, so that the copies of a VAA received from every gossip peer, or a flood
+//! of invalid ones, are only verified once. Copies that arrive while the first one is still being
+//! verified wait for its result instead of verifying it again.
+
+use {
+    anyhow::{
+        anyhow,
+        Result,
+    },
+    secp256k1::{
+        ecdsa::{
+            RecoverableSignature,
+            RecoveryId,
+        },
+        Message,
+        Secp256k1,
+        Verification as SecpVerification,
+        VerifyOnly,
+    },
+    sha3::{
+        Digest,
+        Keccak256,
+    },
+    std::{
+        collections::{
+            HashMap,
+            VecDeque,
+        },
+        sync::{
+            Arc,
+            Mutex,
+            OnceLock,
+        },
+    },
+    tokio::sync::{
+        OnceCell,
+        Semaphore,
+    },
+    wormhole_sdk::vaa::Signature,
+};
+
+/// The maximum number of verification results kept in the cache.
+const VERIFICATION_CACHE_SIZE: usize = 10_000;
+
+/// A digest of everything the verification result depends on: the guardian set index, keys and
+/// quorum, the signatures and the digest of the signed body.
+type CacheKey = [u8; 32];
+
+/// The signatures that reached the quorum, or the reason the VAA was rejected.
+type Verification = Result<Vec<Signature>, String>;
+
+fn secp256k1() -> &'static Secp256k1<VerifyOnly> {
+    static SECP256K1: OnceLock<Secp256k1<VerifyOnly>> = OnceLock::new();
+    SECP256K1.get_or_init(Secp256k1::verification_only)
+}
+
+fn cache_key(
+    guardian_set_index: u32,
+    keys: &[[u8; 20]],
+    quorum: usize,
+    digest: &[u8; 32],
+    signatures: &[Signature],
+) -> CacheKey {
+    let mut keccak = Keccak256::new();
+    keccak.update(guardian_set_index.to_be_bytes());
+    // The number of keys is hashed too, so that the keys cannot be confused with the digest.
+    keccak.update((keys.len() as u64).to_be_bytes());
+    for key in keys {
+        keccak.update(key);
+    }
+    keccak.update((quorum as u64).to_be_bytes());
+    keccak.update(digest);
+    for signature in signatures {
+        keccak.update([signature.index]);
+        keccak.update(signature.signature);
+    }
+    keccak.finalize().into()
+}
+
+/// Recover the signers of `digest` and check that at least `quorum` of them are in `keys`.
+/// Returns the first `quorum` valid signatures.
+pub fn verify_signatures<C: SecpVerification>(
+    secp: &Secp256k1<C>,
+    keys: &[[u8; 20]],
+    quorum: usize,
+    digest: &[u8; 32],
+    signatures: Vec<Signature>,
+) -> Result<Vec<Signature>> {
+    let mut last_signer_id: Option<usize> = None;
+    let mut verified_signatures = vec![];
+    for signature in signatures.into_iter() {
+        // Do not collect more signatures than necessary to reduce
+        // on-chain gas spent on signature verification.
+        if verified_signatures.len() >= quorum {
+            break;
+        }
+
+        let signer_id: usize = signature.index.into();
+
+        if signer_id >= keys.len() {
+            return Err(anyhow!(
+                "Signer ID is out of range. Signer ID: {}, guardian set size: {}",
+                signer_id,
+                keys.len()
+            ));
+        }
+
+        if let Some(true) = last_signer_id.map(|v| v >= signer_id) {
+            return Err(anyhow!(
+                "Signatures are not sorted by signer ID. Last signer ID: {:?}, current signer ID: {}",
+                last_signer_id,
+                signer_id
+            ));
+        }
+
+        let sig = signature.signature;
+
+        // Recover the public key from ecdsa signature from [u8; 65] that has (v, r, s) format
+        let recid = RecoveryId::from_i32(sig[64].into())?;
+
+        // To get the address we need to use the uncompressed public key
+        let pubkey: &[u8; 65] = &secp
+            .recover_ecdsa(
+                &Message::from_slice(digest)?,
+                &RecoverableSignature::from_compact(&sig[..64], recid)?,
+            )?
+            .serialize_uncompressed();
+
+        // The address is the last 20 bytes of the Keccak256 hash of the public key
+        let mut keccak = Keccak256::new();
+        keccak.update(&pubkey[1..]);
+        let address: [u8; 32] = keccak.finalize().into();
+        let address: [u8; 20] = address[address.len() - 20..].try_into()?;
+
+        if keys.get(signer_id) == Some(&address) {
+            verified_signatures.push(signature);
+        }
+
+        last_signer_id = Some(signer_id);
+    }
+
+    // Check if we have enough correct signatures
+    if verified_signatures.len() < quorum {
+        return Err(anyhow!(
+            "Not enough correct signatures. Expected {:?}, received {:?}",
+            quorum,
+            verified_signatures.len()
+        ));
+    }
+
+    Ok(verified_signatures)
+}
+
+#[derive(Default)]
+struct VerificationCache {
+    results:   HashMap<CacheKey, Verification>,
+    /// The keys in insertion order, to evict the oldest results first.
+    order:     VecDeque<CacheKey>,
+    /// The verifications that are running, shared by the copies of a VAA that arrive meanwhile.
+    in_flight: HashMap<CacheKey, Arc<OnceCell<Verification>>>,
+    /// The number of verifications run, to check that duplicates are verified once.
+    #[cfg(test)]
+    verified:  usize,
+}
+
+impl VerificationCache {
+    fn insert(&mut self, key: CacheKey, verification: Verification) {
+        if self.results.insert(key, verification).is_none() {
+            self.order.push_back(key);
+        }
+
+        while self.order.len() > VERIFICATION_CACHE_SIZE {
+            if let Some(key) = self.order.pop_front() {
+                self.results.remove(&key);
+            }
+        }
+    }
+}
+
+pub struct VaaVerifier {
+    cache:   Mutex<VerificationCache>,
+    /// Limits the verifications running at the same time to the number of CPUs.
+    permits: Semaphore,
+}
+
+impl VaaVerifier {
+    pub fn new() -> Self {
+        let parallelism = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            cache:   Mutex::new(VerificationCache::default()),
+            permits: Semaphore::new(parallelism),
+        }
+    }
+
+    /// Verify the signatures of a VAA against the keys of the guardian set that signed it, see
+    /// `verify_signatures`. The result is served from the cache if the same VAA was verified
+    /// against the same guardian set before, or shared with the call that is verifying it.
+    pub async fn verify(
+        &self,
+        guardian_set_index: u32,
+        keys: Vec<[u8; 20]>,
+        quorum: usize,
+        digest: [u8; 32],
+        signatures: Vec<Signature>,
+    ) -> Result<Vec<Signature>> {
+        let key = cache_key(guardian_set_index, &keys, quorum, &digest, &signatures);
+        let in_flight = {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(verification) = cache.results.get(&key) {
+                return verification.clone().map_err(|e| anyhow!(e));
+            }
+            cache.in_flight.entry(key).or_default().clone()
+        };
+
+        // Only the first caller runs the verification. If it is cancelled or fails to run it, the
+        // next waiting caller runs it instead.
+        let verification = in_flight
+            .get_or_try_init(|| async move {
+                let _permit = self.permits.acquire().await?;
+                #[cfg(test)]
+                {
+                    self.cache.lock().unwrap().verified += 1;
+                }
+                let verification = tokio::task::spawn_blocking(move || {
+                    verify_signatures(secp256k1(), &keys, quorum, &digest, signatures)
+                        .map_err(|e| e.to_string())
+                })
+                .await?;
+                Ok::<_, anyhow::Error>(verification)
+            })
+            .await?
+            .clone();
+
+        // Every caller moves the result to the cache, so that it gets there even if the first one
+        // is dropped after the verification. Only the entry of this verification is removed, not
+        // one started after its result was evicted from the cache.
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(entry) = cache.in_flight.get(&key) {
+            if Arc::ptr_eq(entry, &in_flight) {
+                cache.in_flight.remove(&key);
+                cache.insert(key, verification.clone());
+            }
+        }
+        verification.map_err(|e| anyhow!(e))
+    }
+}
+
+impl Default for VaaVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        ::test::Bencher,
+        futures::future::join_all,
+        secp256k1::{
+            PublicKey,
+            SecretKey,
+        },
+    };
+
+    const NUM_GUARDIANS: usize = 19;
+    const QUORUM: usize = NUM_GUARDIANS * 2 / 3 + 1;
+    const NUM_VAAS: usize = 32;
+
+    fn guardian_address(secp: &Secp256k1<secp256k1::All>, secret_key: &SecretKey) -> [u8; 20] {
+        let pubkey = PublicKey::from_secret_key(secp, secret_key).serialize_uncompressed();
+        let mut keccak = Keccak256::new();
+        keccak.update(&pubkey[1..]);
+        let address: [u8; 32] = keccak.finalize().into();
+        address[12..].try_into().unwrap()
+    }
+
+    /// Create a guardian set and the signatures of all its guardians over a digest.
+    fn signed_digest(seed: u8) -> (Vec<[u8; 20]>, [u8; 32], Vec<Signature>) {
+        let secp = Secp256k1::new();
+        let digest = [seed; 32];
+        let mut keys = vec![];
+        let mut signatures = vec![];
+        for i in 0..NUM_GUARDIANS {
+            let secret_key = SecretKey::from_slice(&[i as u8 + 1; 32]).unwrap();
+            keys.push(guardian_address(&secp, &secret_key));
+
+            let (recid, compact) = secp
+                .sign_ecdsa_recoverable(&Message::from_slice(&digest).unwrap(), &secret_key)
+                .serialize_compact();
+            let mut signature = [0; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recid.to_i32() as u8;
+            signatures.push(Signature {
+                index: i as u8,
+                signature,
+            });
+        }
+        (keys, digest, signatures)
+    }
+
+    #[test]
+    fn test_verify_signatures_works() {
+        let (keys, digest, signatures) = signed_digest(1);
+
+        let verified =
+            verify_signatures(secp256k1(), &keys, QUORUM, &digest, signatures.clone()).unwrap();
+        assert_eq!(verified, signatures[..QUORUM].to_vec());
+
+        // Signatures of the wrong digest do not count towards the quorum.
+        assert!(
+            verify_signatures(secp256k1(), &keys, QUORUM, &[2; 32], signatures.clone()).is_err()
+        );
+
+        // Signatures must be sorted by signer.
+        let mut unsorted = signatures.clone();
+        unsorted.swap(0, 1);
+        assert!(verify_signatures(secp256k1(), &keys, QUORUM, &digest, unsorted).is_err());
+
+        // Not enough signatures.
+        assert!(verify_signatures(
+            secp256k1(),
+            &keys,
+            QUORUM,
+            &digest,
+            signatures[..QUORUM - 1].to_vec()
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verification_results_are_cached() {
+        let verifier = VaaVerifier::new();
+        let (keys, digest, signatures) = signed_digest(1);
+
+        let verified = verifier
+            .verify(0, keys.clone(), QUORUM, digest, signatures.clone())
+            .await
+            .unwrap();
+        assert_eq!(verified.len(), QUORUM);
+        assert!(verifier
+            .verify(0, keys.clone(), QUORUM, [2; 32], signatures.clone())
+            .await
+            .is_err());
+        assert_eq!(verifier.cache.lock().unwrap().results.len(), 2);
+
+        // Verifying the same VAAs again is served from the cache.
+        let verified_again = verifier
+            .verify(0, keys.clone(), QUORUM, digest, signatures.clone())
+            .await
+            .unwrap();
+        assert_eq!(verified_again, verified);
+        assert!(verifier
+            .verify(0, keys, QUORUM, [2; 32], signatures.clone())
+            .await
+            .is_err());
+        assert_eq!(verifier.cache.lock().unwrap().results.len(), 2);
+
+        // A cached result is not reused when the keys of the guardian set changed.
+        assert!(verifier
+            .verify(0, vec![[0; 20]; NUM_GUARDIANS], QUORUM, digest, signatures)
+            .await
+            .is_err());
+        assert_eq!(verifier.cache.lock().unwrap().results.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_duplicates_are_verified_once() {
+        let verifier = VaaVerifier::new();
+        let (keys, digest, signatures) = signed_digest(1);
+
+        let results = join_all(
+            (0..NUM_VAAS)
+                .map(|_| verifier.verify(0, keys.clone(), QUORUM, digest, signatures.clone())),
+        )
+        .await;
+        for result in results {
+            assert_eq!(result.unwrap(), signatures[..QUORUM].to_vec());
+        }
+
+        let cache = verifier.cache.lock().unwrap();
+        assert_eq!(cache.verified, 1);
+        assert_eq!(cache.results.len(), 1);
+        assert!(cache.in_flight.is_empty());
+    }
+
+    #[test]
+    fn test_verification_cache_is_bounded() {
+        let mut cache = VerificationCache::default();
+        for i in 0..VERIFICATION_CACHE_SIZE + 10 {
+            let mut key = [0; 32];
+            key[..8].copy_from_slice(&i.to_be_bytes());
+            cache.insert(key, Ok(vec![]));
+        }
+
+        assert_eq!(cache.results.len(), VERIFICATION_CACHE_SIZE);
+        assert_eq!(cache.order.len(), VERIFICATION_CACHE_SIZE);
+        assert!(!cache.results.contains_key(&[0; 32]));
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    /// The baseline: VAAs verified one after the other, each with a new secp256k1 context.
+    #[bench]
+    fn bench_verify_distinct_vaas_sequentially(b: &mut Bencher) {
+        let vaas: Vec<_> = (0..NUM_VAAS).map(|i| signed_digest(i as u8)).collect();
+        b.iter(|| {
+            for (keys, digest, signatures) in &vaas {
+                verify_signatures(&Secp256k1::new(), keys, QUORUM, digest, signatures.clone())
+                    .unwrap();
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_verify_distinct_vaas_in_parallel(b: &mut Bencher) {
+        let runtime = runtime();
+        let vaas: Vec<_> = (0..NUM_VAAS).map(|i| signed_digest(i as u8)).collect();
+        b.iter(|| {
+            let verifier = VaaVerifier::new();
+            runtime.block_on(join_all(vaas.iter().map(|(keys, digest, signatures)| {
+                verifier.verify(0, keys.clone(), QUORUM, *digest, signatures.clone())
+            })));
+        });
+    }
+
+    #[bench]
+    fn bench_verify_duplicate_vaas_sequentially(b: &mut Bencher) {
+        let (keys, digest, signatures) = signed_digest(1);
+        b.iter(|| {
+            for _ in 0..NUM_VAAS {
+                verify_signatures(
+                    &Secp256k1::new(),
+                    &keys,
+                    QUORUM,
+                    &digest,
+                    signatures.clone(),
+                )
+                .unwrap();
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_verify_duplicate_vaas_with_cache(b: &mut Bencher) {
+        let runtime = runtime();
+        let (keys, digest, signatures) = signed_digest(1);
+        b.iter(|| {
+            let verifier = VaaVerifier::new();
+            runtime.block_on(async {
+                for _ in 0..NUM_VAAS {
+                    verifier
+                        .verify(0, keys.clone(), QUORUM, digest, signatures.clone())
+                        .await
+                        .unwrap();
+                }
+            });
+        });
+    }
+
+    #[bench]
+    fn bench_verify_concurrent_duplicate_vaas(b: &mut Bencher) {
+        let runtime = runtime();
+        let (keys, digest, signatures) = signed_digest(1);
+        b.iter(|| {
+            let verifier = VaaVerifier::new();
+            runtime.block_on(join_all((0..NUM_VAAS).map(|_| {
+                verifier.verify(0, keys.clone(), QUORUM, digest, signatures.clone())
+            })));
+        });
+    }
+}
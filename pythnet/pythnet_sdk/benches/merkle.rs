@@ -0,0 +1,76 @@
+//! Measures building a MerkleTree the size of a Pythnet slot and producing proofs for it, comparing
+//! per-item `prove` with `prove_all` and an incremental `update` with rebuilding the tree.
+
+use {
+    criterion::{
+        black_box,
+        criterion_group,
+        criterion_main,
+        BatchSize,
+        Criterion,
+    },
+    pythnet_sdk::{
+        accumulators::{
+            merkle::MerkleTree,
+            Accumulator,
+        },
+        hashers::keccak256_160::Keccak160,
+    },
+};
+
+// Roughly the number of price messages Pythnet publishes in a slot.
+const TREE_SIZE: usize = 5000;
+const MESSAGE_SIZE: usize = 85;
+
+fn messages() -> Vec<Vec<u8>> {
+    (0..TREE_SIZE)
+        .map(|i| (i as u32).to_be_bytes().repeat(MESSAGE_SIZE / 4 + 1)[..MESSAGE_SIZE].to_vec())
+        .collect()
+}
+
+fn bench_merkle(c: &mut Criterion) {
+    let messages = messages();
+    let items: Vec<&[u8]> = messages.iter().map(|m| m.as_ref()).collect();
+    let tree = MerkleTree::<Keccak160>::new(&items).unwrap();
+
+    let mut group = c.benchmark_group(format!("merkle_{TREE_SIZE}"));
+    group.bench_function("new", |b| {
+        b.iter(|| MerkleTree::<Keccak160>::new(black_box(&items)).unwrap())
+    });
+    group.bench_function("prove_one", |b| {
+        b.iter(|| tree.prove(black_box(items[TREE_SIZE - 1])).unwrap())
+    });
+    group.bench_function("prove_each", |b| {
+        b.iter(|| {
+            items
+                .iter()
+                .map(|item| tree.prove(black_box(item)).unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("prove_all", |b| b.iter(|| black_box(&tree).prove_all()));
+
+    let updated = vec![0xffu8; MESSAGE_SIZE];
+    group.bench_function("update_one", |b| {
+        b.iter_batched_ref(
+            || tree.clone(),
+            |tree| assert!(tree.update(black_box(TREE_SIZE / 2), &updated)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("rebuild_one", |b| {
+        b.iter_batched(
+            || {
+                let mut items = items.clone();
+                items[TREE_SIZE / 2] = &updated;
+                items
+            },
+            |items| MerkleTree::<Keccak160>::new(black_box(&items)).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_merkle);
+criterion_main!(benches);
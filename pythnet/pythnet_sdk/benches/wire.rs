@@ -0,0 +1,124 @@
+//! Compares deserializing and verifying an `AccumulatorUpdateData` through the owned types with
+//! the borrowed `AccumulatorUpdateDataRef` view.
+
+use {
+    criterion::{
+        black_box,
+        criterion_group,
+        criterion_main,
+        Criterion,
+    },
+    pythnet_sdk::{
+        accumulators::{
+            merkle::{
+                MerkleRoot,
+                MerkleTree,
+            },
+            Accumulator,
+        },
+        hashers::keccak256_160::Keccak160,
+        wire::{
+            to_vec,
+            v1::{
+                AccumulatorUpdateData,
+                AccumulatorUpdateDataRef,
+                MerklePriceUpdate,
+                Proof,
+            },
+            PrefixedVec,
+        },
+    },
+};
+
+// Roughly the shape of a Hermes update: a few price messages out of a tree of several hundred,
+// sent along with a VAA signed by 13 guardians (header, signatures, body and a Merkle root
+// payload).
+const TREE_SIZE: usize = 500;
+const MESSAGE_SIZE: usize = 85;
+const UPDATE_SIZES: [usize; 3] = [1, 10, 50];
+const VAA_SIZE: usize = 6 + 13 * 66 + 51 + 37;
+
+fn setup(updates: usize) -> (MerkleRoot<Keccak160>, Vec<u8>, Vec<u8>) {
+    let messages: Vec<Vec<u8>> = (0..TREE_SIZE)
+        .map(|i| (i as u32).to_be_bytes().repeat(MESSAGE_SIZE / 4 + 1)[..MESSAGE_SIZE].to_vec())
+        .collect();
+    let items: Vec<&[u8]> = messages.iter().map(|m| m.as_ref()).collect();
+    let tree = MerkleTree::<Keccak160>::new(&items).unwrap();
+
+    let single = AccumulatorUpdateData::new(Proof::WormholeMerkle {
+        vaa:     PrefixedVec::from(vec![0u8; VAA_SIZE]),
+        updates: items[..updates]
+            .iter()
+            .map(|item| MerklePriceUpdate {
+                message: PrefixedVec::from(item.to_vec()),
+                proof:   tree.prove(item).unwrap(),
+            })
+            .collect(),
+    });
+
+    let (order, proof) = tree.prove_multi(&items[..updates]).unwrap();
+    let multi = AccumulatorUpdateData::new(Proof::WormholeMerkleMulti {
+        vaa:      PrefixedVec::from(vec![0u8; VAA_SIZE]),
+        messages: order
+            .iter()
+            .map(|i| PrefixedVec::from(items[*i].to_vec()))
+            .collect(),
+        hashes:   PrefixedVec::from(proof.hashes),
+        flags:    PrefixedVec::from(proof.flags),
+    });
+
+    (
+        tree.root,
+        to_vec::<_, byteorder::BE>(&single).unwrap(),
+        to_vec::<_, byteorder::BE>(&multi).unwrap(),
+    )
+}
+
+fn verify_owned(root: &MerkleRoot<Keccak160>, bytes: &[u8]) -> bool {
+    match AccumulatorUpdateData::try_from_slice(bytes).unwrap().proof {
+        Proof::WormholeMerkle { updates, .. } => updates
+            .into_iter()
+            .all(|update| root.check(update.proof, update.message.as_ref())),
+        Proof::WormholeMerkleMulti {
+            messages,
+            hashes,
+            flags,
+            ..
+        } => {
+            let messages: Vec<&[u8]> = messages.iter().map(|m| m.as_ref().as_slice()).collect();
+            root.check_multi_parts(hashes.as_ref(), flags.as_ref().iter().copied(), &messages)
+        }
+    }
+}
+
+fn verify_borrowed(root: &MerkleRoot<Keccak160>, bytes: &[u8]) -> bool {
+    AccumulatorUpdateDataRef::try_from_slice(bytes)
+        .unwrap()
+        .proof
+        .verify(root)
+}
+
+fn bench_wire(c: &mut Criterion) {
+    for updates in UPDATE_SIZES {
+        let (root, single, multi) = setup(updates);
+        for (proof, bytes) in [("single", &single), ("multi", &multi)] {
+            let mut group = c.benchmark_group(format!("{proof}_{updates}"));
+            group.bench_function("parse_owned", |b| {
+                b.iter(|| AccumulatorUpdateData::try_from_slice(black_box(bytes)).unwrap())
+            });
+            group.bench_function("parse_borrowed", |b| {
+                b.iter(|| AccumulatorUpdateDataRef::try_from_slice(black_box(bytes)).unwrap())
+            });
+            group.bench_function("verify_owned", |b| {
+                b.iter(|| assert!(verify_owned(&root, black_box(bytes))))
+            });
+            group.bench_function("verify_borrowed", |b| {
+                b.iter(|| assert!(verify_borrowed(&root, black_box(bytes))))
+            });
+            group.finish();
+        }
+    }
+}
+
+criterion_group!(benches, bench_wire);
+criterion_main!(benches);
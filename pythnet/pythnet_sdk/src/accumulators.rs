@@ -5,6 +5,7 @@
 //! proofs for account content.
 
 pub mod merkle;
+#[cfg(feature = "std")]
 pub mod mul;
 
 /// The Accumulator trait defines the interface for an accumulator.
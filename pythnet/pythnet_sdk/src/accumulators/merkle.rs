@@ -8,10 +8,18 @@ use {
             Hasher,
         },
     },
+    alloc::{
+        collections::{
+            BTreeSet,
+            VecDeque,
+        },
+        vec::Vec,
+    },
     borsh::{
         BorshDeserialize,
         BorshSerialize,
     },
+    core::cmp::Ordering,
     serde::{
         Deserialize,
         Serialize,
@@ -50,6 +58,32 @@ const NULL_PREFIX: &[u8] = &[2];
 )]
 pub struct MerklePath<H: Hasher>(Vec<H::Hash>);
 
+/// A MerkleMultiProof proves membership of several items in a tree at once.
+///
+/// Individual MerklePaths for items in the same tree share most of their hashes near the root, a
+/// multiproof only contains the sibling hashes that cannot be computed from the proven items
+/// themselves. Verification hashes nodes bottom-up, consuming the items first and then the nodes
+/// computed so far as a queue. Each flag states where the second child of the next node comes
+/// from: `true` for the queue and `false` for the next hash in `hashes`.
+///
+/// The items must be checked in the order returned by `MerkleTree::prove_multi`.
+#[derive(
+    Clone,
+    Default,
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct MerkleMultiProof<H: Hasher> {
+    pub hashes: Vec<H::Hash>,
+    pub flags:  Vec<bool>,
+}
+
 /// A MerkleRoot contains the root hash of a MerkleTree.
 #[derive(
     Clone,
@@ -66,6 +100,10 @@ pub struct MerklePath<H: Hasher>(Vec<H::Hash>);
 pub struct MerkleRoot<H: Hasher>(H::Hash);
 
 /// A MerkleTree is a binary tree where each node is the hash of its children.
+///
+/// Nodes are stored in a flat array with the root at index 1 and the children of node `i` at `2i`
+/// and `2i + 1`, so the leaves take up the second half of `nodes`. The leaf slots beyond the items
+/// the tree was built from hold the null hash.
 #[derive(
     Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Default,
 )]
@@ -75,6 +113,38 @@ pub struct MerkleTree<H: Hasher = Keccak256> {
     #[serde(skip)]
     #[borsh_skip]
     pub nodes: Vec<H::Hash>,
+
+    // Every non-null leaf hash paired with its position in `nodes`, so that leaves can be found
+    // without scanning the tree. Pairs are used rather than a map to allow for duplicate items.
+    #[serde(skip)]
+    #[borsh_skip]
+    leaves: BTreeSet<(LeafKey<H::Hash>, usize)>,
+}
+
+/// A leaf hash ordered by its bytes, which allows indexing leaves without requiring `Ord` from
+/// `Hasher::Hash`.
+#[derive(Clone, Copy, Debug)]
+struct LeafKey<T>(T);
+
+impl<T: AsRef<[u8]>> PartialEq for LeafKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_ref() == other.0.as_ref()
+    }
+}
+
+impl<T: AsRef<[u8]>> Eq for LeafKey<T> {
+}
+
+impl<T: AsRef<[u8]>> PartialOrd for LeafKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: AsRef<[u8]>> Ord for LeafKey<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.as_ref().cmp(other.0.as_ref())
+    }
 }
 
 /// Implements functionality for using standalone MerkleRoots.
@@ -86,13 +156,82 @@ impl<H: Hasher> MerkleRoot<H> {
 
     /// Given a item and corresponding MerklePath, check that it is a valid membership proof.
     pub fn check(&self, proof: MerklePath<H>, item: &[u8]) -> bool {
+        self.check_path(&proof.0, item)
+    }
+
+    /// Same as `check` but takes the proof as a slice of hashes, which allows checking a proof
+    /// borrowed straight from a serialized buffer.
+    pub fn check_path(&self, path: &[H::Hash], item: &[u8]) -> bool {
         let mut current: <H as Hasher>::Hash = MerkleTree::<H>::hash_leaf(item);
-        for hash in proof.0 {
-            current = MerkleTree::<H>::hash_node(&current, &hash);
+        for hash in path {
+            current = MerkleTree::<H>::hash_node(&current, hash);
         }
         current == self.0
     }
 
+    /// Given a list of items and a corresponding MerkleMultiProof, check that it is a valid
+    /// membership proof for all of them.
+    pub fn check_multi(&self, proof: &MerkleMultiProof<H>, items: &[&[u8]]) -> bool {
+        self.check_multi_parts(&proof.hashes, proof.flags.iter().copied(), items)
+    }
+
+    /// Same as `check_multi` but takes the hashes and flags of the proof separately, which allows
+    /// checking a proof borrowed straight from a serialized buffer.
+    pub fn check_multi_parts(
+        &self,
+        hashes: &[H::Hash],
+        flags: impl ExactSizeIterator<Item = bool>,
+        items: &[&[u8]],
+    ) -> bool {
+        if items.is_empty() || items.len() + hashes.len() != flags.len() + 1 {
+            return false;
+        }
+
+        let leaves: Vec<H::Hash> = items
+            .iter()
+            .map(|item| MerkleTree::<H>::hash_leaf(item))
+            .collect();
+
+        // Nodes are consumed in order from the leaves followed by the computed nodes, which makes
+        // the two of them a single queue.
+        let mut nodes: Vec<H::Hash> = Vec::with_capacity(flags.len());
+        let mut queue_position = 0;
+        let mut hashes = hashes.iter();
+
+        for flag in flags {
+            let mut pop = || {
+                let node = leaves
+                    .get(queue_position)
+                    .or_else(|| nodes.get(queue_position - leaves.len()))
+                    .copied();
+                queue_position += 1;
+                node
+            };
+
+            let left = match pop() {
+                Some(node) => node,
+                None => return false,
+            };
+
+            let right = match flag {
+                true => pop(),
+                false => hashes.next().copied(),
+            };
+
+            match right {
+                Some(right) => nodes.push(MerkleTree::<H>::hash_node(&left, &right)),
+                None => return false,
+            }
+        }
+
+        // All the hashes must have been used and only the root must be left in the queue.
+        if hashes.next().is_some() || queue_position + 1 != leaves.len() + nodes.len() {
+            return false;
+        }
+
+        nodes.last().or(leaves.last()) == Some(&self.0)
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         self.0.as_ref()
     }
@@ -125,8 +264,7 @@ impl<'a, H: Hasher + 'a> Accumulator<'a> for MerkleTree<H> {
 
     /// Prove an item is in the tree by returning a MerklePath.
     fn prove(&'a self, item: &[u8]) -> Option<Self::Proof> {
-        let item = MerkleTree::<H>::hash_leaf(item);
-        let index = self.nodes.iter().position(|i| i == &item)?;
+        let index = self.find_leaf(&MerkleTree::<H>::hash_leaf(item))?;
         Some(self.find_path(index))
     }
 
@@ -150,7 +288,7 @@ impl<H: Hasher> MerkleTree<H> {
         }
 
         let depth = items.len().next_power_of_two().trailing_zeros();
-        let mut tree: Vec<H::Hash> = vec![Default::default(); 1 << (depth + 1)];
+        let mut tree: Vec<H::Hash> = alloc::vec![Default::default(); 1 << (depth + 1)];
 
         // Filling the leaf hashes
         for i in 0..(1 << depth) {
@@ -171,12 +309,56 @@ impl<H: Hasher> MerkleTree<H> {
             }
         }
 
+        let leaves = (0..items.len())
+            .map(|i| (LeafKey(tree[(1 << depth) + i]), (1 << depth) + i))
+            .collect();
+
         Some(Self {
-            root:  MerkleRoot::new(tree[1]),
+            root: MerkleRoot::new(tree[1]),
             nodes: tree,
+            leaves,
         })
     }
 
+    /// Replace the item in leaf `index` and recompute the hashes on its path to the root, instead
+    /// of rebuilding the whole tree.
+    ///
+    /// `index` is the position of the item in the list the tree was built from. Indices past the
+    /// end of that list fill the empty leaves of the tree, up to the next power of two. Returns
+    /// `false` if `index` is out of range.
+    pub fn update(&mut self, index: usize, item: &[u8]) -> bool {
+        let leaves_start = self.nodes.len() / 2;
+        if index >= leaves_start {
+            return false;
+        }
+
+        let mut position = leaves_start + index;
+        self.leaves
+            .remove(&(LeafKey(self.nodes[position]), position));
+        self.nodes[position] = MerkleTree::<H>::hash_leaf(item);
+        self.leaves
+            .insert((LeafKey(self.nodes[position]), position));
+
+        while position > 1 {
+            position /= 2;
+            self.nodes[position] = MerkleTree::<H>::hash_node(
+                &self.nodes[position * 2],
+                &self.nodes[position * 2 + 1],
+            );
+        }
+
+        self.root = MerkleRoot::new(self.nodes[1]);
+        true
+    }
+
+    /// Find the position in `nodes` of the first leaf with the given hash.
+    fn find_leaf(&self, leaf: &H::Hash) -> Option<usize> {
+        self.leaves
+            .range((LeafKey(*leaf), 0)..=(LeafKey(*leaf), usize::MAX))
+            .next()
+            .map(|(_, position)| *position)
+    }
+
     /// Produces a Proof of membership for an index in the tree.
     pub fn find_path(&self, mut index: usize) -> MerklePath<H> {
         let mut path = Vec::new();
@@ -187,6 +369,66 @@ impl<H: Hasher> MerkleTree<H> {
         MerklePath::new(path)
     }
 
+    /// Produces a MerklePath for every leaf that holds an item, in the order of the leaves. For a
+    /// tree built with `new` this is one proof per item in the order they were given, which is
+    /// cheaper than calling `prove` for each of them as the items do not need to be hashed again.
+    pub fn prove_all(&self) -> Vec<MerklePath<H>> {
+        let mut positions: Vec<usize> = self.leaves.iter().map(|(_, position)| *position).collect();
+        positions.sort_unstable();
+        positions
+            .into_iter()
+            .map(|position| self.find_path(position))
+            .collect()
+    }
+
+    /// Produces a MerkleMultiProof of membership for a list of items in the tree.
+    ///
+    /// The proof can only be checked against the items in a specific order. Along with the proof,
+    /// this returns the indices into `items` in that order. Duplicate items are only proven once.
+    pub fn prove_multi(&self, items: &[&[u8]]) -> Option<(Vec<usize>, MerkleMultiProof<H>)> {
+        // Find the position of each item in the tree. The proof is built bottom-up from the
+        // deepest and rightmost nodes, which in our layout means in decreasing index order.
+        let mut positions = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| Some((self.find_leaf(&MerkleTree::<H>::hash_leaf(item))?, i)))
+            .collect::<Option<Vec<_>>>()?;
+        positions.sort_unstable_by(|a, b| b.cmp(a));
+        positions.dedup_by_key(|(position, _)| *position);
+
+        if positions.is_empty() {
+            return None;
+        }
+
+        let order = positions.iter().map(|(_, i)| *i).collect();
+        let mut queue: VecDeque<usize> = positions
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect();
+
+        let mut proof = MerkleMultiProof::default();
+        while let Some(index) = queue.pop_front() {
+            if index <= 1 {
+                break;
+            }
+
+            // With the queue sorted in decreasing order, a sibling that is part of the proof is
+            // always the next node in the queue.
+            let sibling = index ^ 1;
+            if queue.front() == Some(&sibling) {
+                queue.pop_front();
+                proof.flags.push(true);
+            } else {
+                proof.hashes.push(self.nodes[sibling]);
+                proof.flags.push(false);
+            }
+
+            queue.push_back(index / 2);
+        }
+
+        Some((order, proof))
+    }
+
     /// Check if a given MerklePath is a valid proof for a corresponding item.
     pub fn verify_path(&self, proof: MerklePath<H>, item: &[u8]) -> bool {
         self.root.check(proof, item)
@@ -225,7 +467,7 @@ impl<H: Hasher> MerkleTree<H> {
     /// TODO: This code does not belong to MerkleTree, we should be using the wire data types in
     /// calling code to wrap this value.
     pub fn serialize(&self, slot: u64, ring_size: u32) -> Vec<u8> {
-        let mut serialized = vec![];
+        let mut serialized = alloc::vec![];
         serialized.extend_from_slice(0x41555756u32.to_be_bytes().as_ref());
         serialized.extend_from_slice(0u8.to_be_bytes().as_ref());
         serialized.extend_from_slice(slot.to_be_bytes().as_ref());
@@ -355,6 +597,51 @@ mod test {
         assert!(!accumulator.verify_path(proof, &item_d));
     }
 
+    #[test]
+    fn test_merkle_update() {
+        let items: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 4]).collect();
+        let items: Vec<&[u8]> = items.iter().map(|i| i.as_ref()).collect();
+        let mut tree = MerkleTree::<Keccak256>::new(&items).unwrap();
+
+        // Replacing an item should give the same tree as building it with that item.
+        let mut updated = items.clone();
+        updated[2] = b"updated";
+        assert!(tree.update(2, b"updated"));
+        assert_eq!(tree, MerkleTree::<Keccak256>::new(&updated).unwrap());
+        assert!(tree.prove(items[2]).is_none());
+        let proof = tree.prove(b"updated").unwrap();
+        assert!(tree.verify_path(proof, b"updated"));
+
+        // Filling the first empty leaf is the same as building the tree with one more item.
+        updated.push(b"appended");
+        assert!(tree.update(5, b"appended"));
+        assert_eq!(tree, MerkleTree::<Keccak256>::new(&updated).unwrap());
+
+        // Leaves past the padded size of the tree cannot be updated.
+        assert!(!tree.update(8, b"out of range"));
+        assert!(!MerkleTree::<Keccak256>::default().update(0, b"empty"));
+
+        // Replacing one of two duplicate items must still allow the other to be proven.
+        let mut tree = MerkleTree::<Keccak256>::new(&[b"dup", b"dup", b"other"]).unwrap();
+        assert!(tree.update(0, b"new"));
+        let proof = tree.prove(b"dup").unwrap();
+        assert!(tree.verify_path(proof, b"dup"));
+    }
+
+    #[test]
+    fn test_merkle_prove_all() {
+        let items: Vec<Vec<u8>> = (0..37u8).map(|i| vec![i; 4]).collect();
+        let items: Vec<&[u8]> = items.iter().map(|i| i.as_ref()).collect();
+        let tree = MerkleTree::<Keccak256>::new(&items).unwrap();
+
+        let proofs = tree.prove_all();
+        assert_eq!(proofs.len(), items.len());
+        for (item, proof) in items.iter().zip(proofs) {
+            assert_eq!(tree.prove(item), Some(proof.clone()));
+            assert!(tree.verify_path(proof, item));
+        }
+    }
+
     #[test]
     // Note that this is testing proofs for trees size 2 and greater, as a size 1 tree the root is
     // its own proof and will always pass. This just checks the most obvious case that an empty or
@@ -453,13 +740,17 @@ mod test {
         // falsely prove `A` was in the original tree by tricking the implementation into performing
         // H(a || b) at the leaf.
         let faulty_accumulator = MerkleTree::<Keccak256> {
-            root:  accumulator.root,
-            nodes: vec![
+            root:   accumulator.root,
+            nodes:  vec![
                 accumulator.nodes[0],
                 accumulator.nodes[1], // Root Stays the Same
                 accumulator.nodes[2], // Left node hash becomes a leaf.
                 accumulator.nodes[3], // Right node hash becomes a leaf.
             ],
+            leaves: BTreeSet::from([
+                (LeafKey(accumulator.nodes[2]), 2),
+                (LeafKey(accumulator.nodes[3]), 3),
+            ]),
         };
 
         // `a || b` is the concatenation of a and b, which when hashed without pre-image fixes in
@@ -483,7 +774,109 @@ mod test {
         assert!(faulty_accumulator.verify_path(proof, fake_leaf));
     }
 
+    #[test]
+    fn test_merkle_multiproof() {
+        let items: Vec<[u8; 8]> = (0..7usize).map(|i| i.to_be_bytes()).collect();
+        let items: Vec<&[u8]> = items.iter().map(|i| i.as_ref()).collect();
+        let accumulator = MerkleTree::<Keccak256>::new(&items).unwrap();
+
+        // Proving neighbouring leaves needs fewer hashes than their individual paths.
+        let proven = [items[0], items[1], items[5]];
+        let (order, proof) = accumulator.prove_multi(&proven).unwrap();
+        let ordered: Vec<&[u8]> = order.iter().map(|i| proven[*i]).collect();
+        assert!(accumulator.root.check_multi(&proof, &ordered));
+        assert_eq!(proof.hashes.len(), 3);
+        assert!(
+            proof.hashes.len()
+                < proven
+                    .iter()
+                    .map(|item| accumulator.prove(item).unwrap().0.len())
+                    .sum()
+        );
+
+        // The items must be checked in the order given by the prover.
+        let mut reordered = ordered.clone();
+        reordered.reverse();
+        assert!(!accumulator.root.check_multi(&proof, &reordered));
+
+        // Items that are not in the tree cannot be proven or checked.
+        let outsider = 88usize.to_be_bytes();
+        assert!(accumulator.prove_multi(&[items[0], &outsider]).is_none());
+        let mut tampered = ordered.clone();
+        tampered[0] = &outsider;
+        assert!(!accumulator.root.check_multi(&proof, &tampered));
+
+        // Corrupting any hash or flag invalidates the proof.
+        for i in 0..proof.hashes.len() {
+            let mut corrupted = proof.clone();
+            corrupted.hashes[i] = Default::default();
+            assert!(!accumulator.root.check_multi(&corrupted, &ordered));
+        }
+        for i in 0..proof.flags.len() {
+            let mut corrupted = proof.clone();
+            corrupted.flags[i] = !corrupted.flags[i];
+            assert!(!accumulator.root.check_multi(&corrupted, &ordered));
+        }
+
+        // An empty proof proves nothing.
+        assert!(!accumulator
+            .root
+            .check_multi(&MerkleMultiProof::default(), &[]));
+    }
+
+    #[test]
+    fn test_merkle_multiproof_single_leaf_tree() {
+        let item = 88usize.to_be_bytes();
+        let accumulator = MerkleTree::<Keccak256>::new(&[&item]).unwrap();
+        let (order, proof) = accumulator.prove_multi(&[&item, &item]).unwrap();
+        assert_eq!(order, vec![1]);
+        assert_eq!(proof, MerkleMultiProof::default());
+        assert!(accumulator.root.check_multi(&proof, &[&item]));
+    }
+
     proptest! {
+        // Updating leaves one at a time must give the same tree as building it from scratch.
+        #[test]
+        fn test_merkle_update_matches_new(
+            v in any::<MerkleTreeDataWrapper>(),
+            updates in prop::collection::vec(
+                (any::<prop::sample::Index>(), prop::collection::vec(any::<u8>(), 1..=10)),
+                1..20,
+            ),
+        ) {
+            let mut data: Vec<Vec<u8>> = v.data.into_iter().collect();
+            let mut tree = v.accumulator;
+            for (index, item) in updates {
+                let index = index.index(data.len());
+                data[index] = item;
+                assert!(tree.update(index, &data[index]));
+
+                let items: Vec<&[u8]> = data.iter().map(|d| d.as_ref()).collect();
+                assert_eq!(tree, MerkleTree::new(&items).unwrap());
+            }
+        }
+
+        // Check multiproofs for arbitrary subsets of arbitrary trees.
+        #[test]
+        fn test_merkle_multiproof_subsets(
+            v in any::<MerkleTreeDataWrapper>(),
+            selection in prop::collection::vec(any::<bool>(), 101..=101),
+        ) {
+            let data: Vec<&[u8]> = v.data.iter().map(|d| d.as_ref()).collect();
+            let mut subset: Vec<&[u8]> = data
+                .iter()
+                .zip(selection.iter().cycle())
+                .filter_map(|(d, selected)| selected.then_some(*d))
+                .collect();
+            if subset.is_empty() {
+                subset.push(data[0]);
+            }
+
+            let (order, proof) = v.accumulator.prove_multi(&subset).unwrap();
+            let ordered: Vec<&[u8]> = order.iter().map(|i| subset[*i]).collect();
+            assert!(v.accumulator.root.check_multi(&proof, &ordered));
+        }
+
         // Use proptest to generate arbitrary Merkle trees as part of our fuzzing strategy. This
         // will help us identify any edge cases or unexpected behavior in the implementation.
         #[test]
@@ -1,17 +1,26 @@
-use thiserror::Error;
+use core::fmt;
 
-#[derive(Error, Debug)]
+#[derive(Debug)]
 pub enum Error {
-    #[error("Invalid Magic")]
     InvalidMagic,
-
-    #[error("Invalid Version")]
     InvalidVersion,
-
-    #[error("Deserialization error")]
     DeserializationError,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidMagic => write!(f, "Invalid Magic"),
+            Error::InvalidVersion => write!(f, "Invalid Version"),
+            Error::DeserializationError => write!(f, "Deserialization error"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+}
+
 #[macro_export]
 macro_rules! require {
     ($cond:expr, $err:expr) => {
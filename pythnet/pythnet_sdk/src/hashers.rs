@@ -3,15 +3,16 @@ use {
         BorshDeserialize,
         BorshSerialize,
     },
+    core::fmt::Debug,
     serde::{
         Deserialize,
         Serialize,
     },
-    std::fmt::Debug,
 };
 
 pub mod keccak256;
 pub mod keccak256_160;
+#[cfg(feature = "std")]
 pub mod prime;
 
 /// We provide `Hasher` as a small hashing abstraction.
@@ -33,7 +34,7 @@ where
         + Debug
         + Default
         + Eq
-        + std::hash::Hash
+        + core::hash::Hash
         + PartialOrd
         + PartialEq
         + Serialize
@@ -1,7 +1,13 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod accumulators;
 pub mod error;
 pub mod hashers;
 pub mod messages;
+#[cfg(feature = "verifier")]
+pub mod verifier;
 pub mod wire;
 pub mod wormhole;
 
@@ -0,0 +1,508 @@
+//! Verifier for Pyth accumulator updates.
+//!
+//! Accumulator updates (the `PNAU` blobs served by Hermes) carry a Wormhole VAA signing a Merkle
+//! root and the messages proven against that root. This module checks an update end to end
+//! without trusting whoever served it: the VAA must be signed by a quorum of the given guardian
+//! set and emitted by one of the allowed data sources, and every message must be proven against
+//! the signed root. Only then are the messages returned.
+//!
+//! This module is only available with the `verifier` feature.
+
+use {
+    crate::{
+        accumulators::merkle::{
+            MerkleMultiProof,
+            MerkleRoot,
+        },
+        hashers::{
+            keccak256::Keccak256,
+            keccak256_160::Keccak160,
+            Hasher,
+        },
+        messages::Message,
+        wire::{
+            from_slice,
+            v1::{
+                AccumulatorUpdateData,
+                Proof,
+                WormholeMessage,
+                WormholePayload,
+            },
+        },
+    },
+    byteorder::{
+        ByteOrder,
+        BE,
+    },
+    thiserror::Error,
+};
+
+/// The size of a guardian signature in a VAA: the guardian index followed by a 65 byte recoverable
+/// secp256k1 signature.
+const SIGNATURE_SIZE: usize = 66;
+
+/// The size of the VAA header before the signatures: version, guardian set index and number of
+/// signatures.
+const HEADER_SIZE: usize = 6;
+
+/// The size of the VAA body before the payload: timestamp, nonce, emitter chain, emitter address,
+/// sequence and consistency level.
+const BODY_HEADER_SIZE: usize = 51;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum VerifierError {
+    #[error("invalid accumulator update data")]
+    InvalidUpdateData,
+
+    #[error("invalid VAA")]
+    InvalidVaa,
+
+    #[error("VAA is signed by guardian set {actual}, expected {expected}")]
+    GuardianSetMismatch { expected: u32, actual: u32 },
+
+    #[error("VAA has {signatures} signatures, {quorum} are needed for quorum")]
+    NoQuorum {
+        signatures: usize,
+        quorum:     usize,
+    },
+
+    #[error("invalid signature of guardian {0}")]
+    InvalidSignature(u8),
+
+    #[error("VAA emitter is not an allowed data source")]
+    UnknownDataSource,
+
+    #[error("invalid Wormhole message")]
+    InvalidWormholeMessage,
+
+    #[error("invalid Merkle proof for message {0}")]
+    InvalidProof(usize),
+
+    #[error("invalid message {0}")]
+    InvalidMessage(usize),
+}
+
+/// The guardians whose signatures are accepted, by their Ethereum style address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuardianSet {
+    pub index: u32,
+    pub keys:  Vec<[u8; 20]>,
+}
+
+impl GuardianSet {
+    /// The number of signatures needed for a VAA to be valid, more than two thirds of the
+    /// guardians.
+    pub fn quorum(&self) -> usize {
+        self.keys.len() * 2 / 3 + 1
+    }
+}
+
+/// A Wormhole emitter whose accumulator updates are trusted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataSource {
+    pub chain:   u16,
+    pub emitter: [u8; 32],
+}
+
+/// The body of a VAA, which is what the guardians sign.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VaaBody<'a> {
+    pub timestamp:         u32,
+    pub nonce:             u32,
+    pub emitter_chain:     u16,
+    pub emitter_address:   [u8; 32],
+    pub sequence:          u64,
+    pub consistency_level: u8,
+    pub payload:           &'a [u8],
+}
+
+impl<'a> VaaBody<'a> {
+    fn parse(body: &'a [u8]) -> Result<Self, VerifierError> {
+        if body.len() < BODY_HEADER_SIZE {
+            return Err(VerifierError::InvalidVaa);
+        }
+
+        let mut emitter_address = [0u8; 32];
+        emitter_address.copy_from_slice(&body[10..42]);
+        Ok(Self {
+            timestamp: BE::read_u32(&body[0..4]),
+            nonce: BE::read_u32(&body[4..8]),
+            emitter_chain: BE::read_u16(&body[8..10]),
+            emitter_address,
+            sequence: BE::read_u64(&body[42..50]),
+            consistency_level: body[50],
+            payload: &body[BODY_HEADER_SIZE..],
+        })
+    }
+
+    pub fn data_source(&self) -> DataSource {
+        DataSource {
+            chain:   self.emitter_chain,
+            emitter: self.emitter_address,
+        }
+    }
+}
+
+/// The result of a successful verification.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedUpdate {
+    /// The Pythnet slot the messages were produced at.
+    pub slot:     u64,
+    /// The data source that emitted the VAA.
+    pub source:   DataSource,
+    /// The sequence number of the VAA.
+    pub sequence: u64,
+    /// The messages of the update, in the order of the update.
+    pub messages: Vec<Message>,
+}
+
+/// Verifies accumulator updates against a guardian set and a list of allowed data sources.
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    guardian_set: GuardianSet,
+    data_sources: Vec<DataSource>,
+}
+
+impl Verifier {
+    pub fn new(guardian_set: GuardianSet, data_sources: Vec<DataSource>) -> Self {
+        Self {
+            guardian_set,
+            data_sources,
+        }
+    }
+
+    /// Check that a VAA is signed by a quorum of the guardian set and emitted by an allowed data
+    /// source, and return its body.
+    pub fn verify_vaa<'a>(&self, vaa: &'a [u8]) -> Result<VaaBody<'a>, VerifierError> {
+        if vaa.len() < HEADER_SIZE || vaa[0] != 1 {
+            return Err(VerifierError::InvalidVaa);
+        }
+
+        let guardian_set_index = BE::read_u32(&vaa[1..5]);
+        if guardian_set_index != self.guardian_set.index {
+            return Err(VerifierError::GuardianSetMismatch {
+                expected: self.guardian_set.index,
+                actual:   guardian_set_index,
+            });
+        }
+
+        let num_signatures = vaa[5] as usize;
+        let body_start = HEADER_SIZE + num_signatures * SIGNATURE_SIZE;
+        if vaa.len() < body_start {
+            return Err(VerifierError::InvalidVaa);
+        }
+        let (signatures, body) = vaa[HEADER_SIZE..].split_at(num_signatures * SIGNATURE_SIZE);
+
+        let quorum = self.guardian_set.quorum();
+        if num_signatures < quorum {
+            return Err(VerifierError::NoQuorum {
+                signatures: num_signatures,
+                quorum,
+            });
+        }
+
+        // The guardians sign the hash of the hash of the body.
+        let digest = Keccak256::hashv(&[Keccak256::hashv(&[body])]);
+        let message = libsecp256k1::Message::parse(&digest);
+
+        // Signatures must be sorted by strictly increasing guardian index so that a guardian cannot
+        // be counted twice towards the quorum.
+        let mut last_guardian_index = None;
+        for signature in signatures.chunks_exact(SIGNATURE_SIZE) {
+            let guardian_index = signature[0];
+            if last_guardian_index >= Some(guardian_index) {
+                return Err(VerifierError::InvalidVaa);
+            }
+            last_guardian_index = Some(guardian_index);
+
+            let key = self
+                .guardian_set
+                .keys
+                .get(guardian_index as usize)
+                .ok_or(VerifierError::InvalidSignature(guardian_index))?;
+            if recover_guardian_key(&message, &signature[1..]) != Some(*key) {
+                return Err(VerifierError::InvalidSignature(guardian_index));
+            }
+        }
+
+        let body = VaaBody::parse(body)?;
+        if !self.data_sources.contains(&body.data_source()) {
+            return Err(VerifierError::UnknownDataSource);
+        }
+
+        Ok(body)
+    }
+
+    /// Verify an accumulator update and return its messages.
+    pub fn verify_update(&self, update_data: &[u8]) -> Result<VerifiedUpdate, VerifierError> {
+        let update = AccumulatorUpdateData::try_from_slice(update_data)
+            .map_err(|_| VerifierError::InvalidUpdateData)?;
+
+        let (vaa, messages): (_, Vec<&[u8]>) = match &update.proof {
+            Proof::WormholeMerkle { vaa, updates } => (
+                vaa,
+                updates
+                    .iter()
+                    .map(|update| update.message.as_ref().as_slice())
+                    .collect(),
+            ),
+            Proof::WormholeMerkleMulti { vaa, messages, .. } => (
+                vaa,
+                messages
+                    .iter()
+                    .map(|message| message.as_ref().as_slice())
+                    .collect(),
+            ),
+        };
+
+        let body = self.verify_vaa(vaa.as_ref())?;
+        let WormholePayload::Merkle(root) = WormholeMessage::try_from_bytes(body.payload)
+            .map_err(|_| VerifierError::InvalidWormholeMessage)?
+            .payload;
+        let merkle_root = MerkleRoot::<Keccak160>::new(root.root);
+
+        match &update.proof {
+            Proof::WormholeMerkle { updates, .. } => {
+                for (i, update) in updates.iter().enumerate() {
+                    if !merkle_root.check(update.proof.clone(), update.message.as_ref()) {
+                        return Err(VerifierError::InvalidProof(i));
+                    }
+                }
+            }
+            Proof::WormholeMerkleMulti { hashes, flags, .. } => {
+                let proof = MerkleMultiProof::<Keccak160> {
+                    hashes: hashes.as_ref().clone(),
+                    flags:  flags.as_ref().clone(),
+                };
+                if !merkle_root.check_multi(&proof, &messages) {
+                    return Err(VerifierError::InvalidProof(0));
+                }
+            }
+        }
+
+        let messages = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                from_slice::<BE, Message>(message).map_err(|_| VerifierError::InvalidMessage(i))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(VerifiedUpdate {
+            slot: root.slot,
+            source: body.data_source(),
+            sequence: body.sequence,
+            messages,
+        })
+    }
+}
+
+/// Recover the address of the guardian that made a 65 byte recoverable signature.
+fn recover_guardian_key(message: &libsecp256k1::Message, signature: &[u8]) -> Option<[u8; 20]> {
+    let recovery_id = libsecp256k1::RecoveryId::parse(signature[64]).ok()?;
+    let signature = libsecp256k1::Signature::parse_standard_slice(&signature[..64]).ok()?;
+    let key = libsecp256k1::recover(message, &signature, &recovery_id).ok()?;
+    Some(guardian_key(&key))
+}
+
+/// The Ethereum style address of a public key, the last 20 bytes of the hash of the key.
+fn guardian_key(key: &libsecp256k1::PublicKey) -> [u8; 20] {
+    let hash = Keccak256::hashv(&[&key.serialize()[1..]]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        crate::{
+            accumulators::merkle::MerkleTree,
+            messages::PriceFeedMessage,
+            wire::{
+                to_vec,
+                v1::{
+                    MerklePriceUpdate,
+                    WormholeMerkleRoot,
+                },
+            },
+        },
+    };
+
+    const SOURCE: DataSource = DataSource {
+        chain:   26,
+        emitter: crate::ACCUMULATOR_EMITTER_ADDRESS,
+    };
+
+    fn secret_keys() -> Vec<libsecp256k1::SecretKey> {
+        (1..=4u8)
+            .map(|i| libsecp256k1::SecretKey::parse(&[i; 32]).unwrap())
+            .collect()
+    }
+
+    fn guardian_set() -> GuardianSet {
+        GuardianSet {
+            index: 3,
+            keys:  secret_keys()
+                .iter()
+                .map(|key| guardian_key(&libsecp256k1::PublicKey::from_secret_key(key)))
+                .collect(),
+        }
+    }
+
+    fn messages() -> Vec<Vec<u8>> {
+        (1..=3u8)
+            .map(|i| {
+                to_vec::<_, BE>(&Message::PriceFeedMessage(PriceFeedMessage {
+                    feed_id:           [i; 32],
+                    price:             i as i64,
+                    conf:              i as u64,
+                    exponent:          -8,
+                    publish_time:      100,
+                    prev_publish_time: 99,
+                    ema_price:         i as i64,
+                    ema_conf:          i as u64,
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    /// Build a VAA signed by the given guardians.
+    fn vaa(source: DataSource, payload: &[u8], signers: &[u8]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&0u32.to_be_bytes());
+        body.extend_from_slice(&0u32.to_be_bytes());
+        body.extend_from_slice(&source.chain.to_be_bytes());
+        body.extend_from_slice(&source.emitter);
+        body.extend_from_slice(&7u64.to_be_bytes());
+        body.push(1);
+        body.extend_from_slice(payload);
+
+        let digest = Keccak256::hashv(&[Keccak256::hashv(&[&body])]);
+        let message = libsecp256k1::Message::parse(&digest);
+
+        let mut vaa = vec![1];
+        vaa.extend_from_slice(&3u32.to_be_bytes());
+        vaa.push(signers.len() as u8);
+        let secret_keys = secret_keys();
+        for &signer in signers {
+            let (signature, recovery_id) =
+                libsecp256k1::sign(&message, &secret_keys[signer as usize]);
+            vaa.push(signer);
+            vaa.extend_from_slice(&signature.serialize());
+            vaa.push(recovery_id.serialize());
+        }
+        vaa.extend_from_slice(&body);
+        vaa
+    }
+
+    fn update_data(source: DataSource, signers: &[u8], tamper: bool) -> Vec<u8> {
+        let messages = messages();
+        let tree = MerkleTree::<Keccak160>::new(
+            &messages.iter().map(|m| m.as_slice()).collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let payload = to_vec::<_, BE>(&WormholeMessage::new(WormholePayload::Merkle(
+            WormholeMerkleRoot {
+                slot:      42,
+                ring_size: 10,
+                root:      tree.root.as_bytes().try_into().unwrap(),
+            },
+        )))
+        .unwrap();
+
+        let updates = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut message = message.clone();
+                if tamper && i == 1 {
+                    message[10] ^= 1;
+                }
+                MerklePriceUpdate {
+                    message: message.into(),
+                    proof:   tree.find_path(tree.nodes.len() / 2 + i),
+                }
+            })
+            .collect();
+
+        to_vec::<_, BE>(&AccumulatorUpdateData::new(Proof::WormholeMerkle {
+            vaa: vaa(source, &payload, signers).into(),
+            updates,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify_update() {
+        let verifier = Verifier::new(guardian_set(), vec![SOURCE]);
+        let update = verifier
+            .verify_update(&update_data(SOURCE, &[0, 1, 3], false))
+            .unwrap();
+
+        assert_eq!(update.slot, 42);
+        assert_eq!(update.source, SOURCE);
+        assert_eq!(update.sequence, 7);
+        assert_eq!(update.messages.len(), 3);
+        assert_eq!(update.messages[2].feed_id(), [3; 32]);
+    }
+
+    #[test]
+    fn test_verify_update_rejects_invalid_updates() {
+        let verifier = Verifier::new(guardian_set(), vec![SOURCE]);
+
+        assert_eq!(
+            verifier.verify_update(&update_data(SOURCE, &[0, 1], false)),
+            Err(VerifierError::NoQuorum {
+                signatures: 2,
+                quorum:     3,
+            })
+        );
+        assert_eq!(
+            verifier.verify_update(&update_data(SOURCE, &[0, 0, 1], false)),
+            Err(VerifierError::InvalidVaa)
+        );
+        assert_eq!(
+            verifier.verify_update(&update_data(SOURCE, &[0, 1, 2], true)),
+            Err(VerifierError::InvalidProof(1))
+        );
+
+        let other_source = DataSource {
+            chain:   1,
+            emitter: [1; 32],
+        };
+        assert_eq!(
+            verifier.verify_update(&update_data(other_source, &[0, 1, 2], false)),
+            Err(VerifierError::UnknownDataSource)
+        );
+
+        let mut other_guardian_set = guardian_set();
+        other_guardian_set.index = 4;
+        assert_eq!(
+            Verifier::new(other_guardian_set, vec![SOURCE]).verify_update(&update_data(
+                SOURCE,
+                &[0, 1, 2],
+                false
+            )),
+            Err(VerifierError::GuardianSetMismatch {
+                expected: 4,
+                actual:   3,
+            })
+        );
+
+        // A signature from a guardian of another set does not count.
+        let mut other_keys = guardian_set();
+        other_keys.keys[1] = [0; 20];
+        assert_eq!(
+            Verifier::new(other_keys, vec![SOURCE]).verify_update(&update_data(
+                SOURCE,
+                &[0, 1, 2],
+                false
+            )),
+            Err(VerifierError::InvalidSignature(1))
+        );
+    }
+}
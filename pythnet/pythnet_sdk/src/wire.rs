@@ -35,11 +35,16 @@ pub mod v1 {
     use {
         super::*,
         crate::{
-            accumulators::merkle::MerklePath,
+            accumulators::merkle::{
+                MerklePath,
+                MerkleRoot,
+            },
             error::Error,
             hashers::keccak256_160::Keccak160,
             require,
         },
+        alloc::vec::Vec,
+        byteorder::ByteOrder,
         serde::{
             Deserialize,
             Serialize,
@@ -67,7 +72,7 @@ pub mod v1 {
                 magic: *PYTHNET_ACCUMULATOR_UPDATE_MAGIC,
                 major_version: 1,
                 minor_version: 0,
-                trailing: vec![],
+                trailing: alloc::vec![],
                 proof,
             }
         }
@@ -97,6 +102,17 @@ pub mod v1 {
             vaa:     PrefixedVec<u16, u8>,
             updates: Vec<MerklePriceUpdate>,
         },
+
+        /// All messages are proven against the VAA root by a single `MerkleMultiProof`, which
+        /// deduplicates the sibling hashes shared by their paths. The messages are in the order
+        /// the multiproof checks them. A multiproof can need more than 255 hashes or flags so both
+        /// are u16 prefixed.
+        WormholeMerkleMulti {
+            vaa:      PrefixedVec<u16, u8>,
+            messages: Vec<PrefixedVec<u16, u8>>,
+            hashes:   PrefixedVec<u16, Hash>,
+            flags:    PrefixedVec<u16, bool>,
+        },
     }
 
     #[derive(Clone, Debug, Hash, PartialEq, Serialize, Deserialize)]
@@ -143,19 +159,250 @@ pub mod v1 {
         pub ring_size: u32,
         pub root:      Hash,
     }
+
+    // Borrowed Transfer Format.
+    // --------------------------------------------------------------------------------
+    // Deserializing into the types above allocates for the VAA, every message and every proof,
+    // which is costly for on-chain verifiers. The following types are a view over an encoded
+    // `AccumulatorUpdateData` that borrow all of these from the original buffer instead.
+
+    /// A borrowed view of an encoded `AccumulatorUpdateData`.
+    ///
+    /// The whole buffer is validated by `try_from_slice`, so walking the updates afterwards cannot
+    /// fail. Like `AccumulatorUpdateData`, any bytes following the proof are ignored.
+    #[derive(Clone, Copy, Debug)]
+    pub struct AccumulatorUpdateDataRef<'a> {
+        pub proof: ProofRef<'a>,
+    }
+
+    impl<'a> AccumulatorUpdateDataRef<'a> {
+        // The minor version check is trivially true while `CURRENT_MINOR_VERSION` is 0.
+        #[allow(clippy::absurd_extreme_comparisons)]
+        pub fn try_from_slice(bytes: &'a [u8]) -> Result<Self, Error> {
+            let mut reader = Reader(bytes);
+            require!(
+                reader.bytes(4)? == PYTHNET_ACCUMULATOR_UPDATE_MAGIC,
+                Error::InvalidMagic
+            );
+            require!(reader.u8()? == 1, Error::InvalidVersion);
+            require!(reader.u8()? >= CURRENT_MINOR_VERSION, Error::InvalidVersion);
+
+            // Skip the trailing header bytes added by newer minor versions.
+            let trailing = reader.u8()?;
+            reader.bytes(trailing.into())?;
+
+            let proof = match reader.u8()? {
+                0 => {
+                    let vaa = reader.prefixed_bytes()?;
+                    let len = reader.u8()?;
+                    let updates = MerklePriceUpdateIter {
+                        reader,
+                        remaining: len.into(),
+                    };
+                    for _ in 0..updates.remaining {
+                        MerklePriceUpdateRef::read(&mut reader)?;
+                    }
+                    ProofRef::WormholeMerkle { vaa, updates }
+                }
+                1 => {
+                    let vaa = reader.prefixed_bytes()?;
+                    let len = reader.u8()?;
+                    let messages = MessageIter {
+                        reader,
+                        remaining: len.into(),
+                    };
+                    for _ in 0..messages.remaining {
+                        reader.prefixed_bytes()?;
+                    }
+                    let len = reader.u16()?;
+                    let hashes = reader.hashes(len.into())?;
+                    let len = reader.u16()?;
+                    let flags = reader.bytes(len.into())?;
+                    ProofRef::WormholeMerkleMulti {
+                        vaa,
+                        messages,
+                        hashes,
+                        flags,
+                    }
+                }
+                _ => return Err(Error::DeserializationError),
+            };
+
+            Ok(Self { proof })
+        }
+    }
+
+    /// A borrowed view of a `Proof`. Flags of a multiproof are kept as the encoded bytes, any
+    /// non-zero byte is `true`.
+    #[derive(Clone, Copy, Debug)]
+    pub enum ProofRef<'a> {
+        WormholeMerkle {
+            vaa:     &'a [u8],
+            updates: MerklePriceUpdateIter<'a>,
+        },
+
+        WormholeMerkleMulti {
+            vaa:      &'a [u8],
+            messages: MessageIter<'a>,
+            hashes:   &'a [Hash],
+            flags:    &'a [u8],
+        },
+    }
+
+    impl<'a> ProofRef<'a> {
+        pub fn vaa(&self) -> &'a [u8] {
+            match self {
+                ProofRef::WormholeMerkle { vaa, .. } => vaa,
+                ProofRef::WormholeMerkleMulti { vaa, .. } => vaa,
+            }
+        }
+
+        /// Check every message in the proof against `root`, which should come from the payload of
+        /// the VAA once its signatures have been verified.
+        pub fn verify(&self, root: &MerkleRoot<Keccak160>) -> bool {
+            match *self {
+                ProofRef::WormholeMerkle { mut updates, .. } => {
+                    updates.all(|update| update.verify(root))
+                }
+                ProofRef::WormholeMerkleMulti {
+                    messages,
+                    hashes,
+                    flags,
+                    ..
+                } => {
+                    let messages: Vec<&[u8]> = messages.collect();
+                    root.check_multi_parts(hashes, flags.iter().map(|flag| *flag != 0), &messages)
+                }
+            }
+        }
+    }
+
+    /// A borrowed view of a `MerklePriceUpdate`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct MerklePriceUpdateRef<'a> {
+        pub message: &'a [u8],
+        pub proof:   &'a [Hash],
+    }
+
+    impl<'a> MerklePriceUpdateRef<'a> {
+        fn read(reader: &mut Reader<'a>) -> Result<Self, Error> {
+            let message = reader.prefixed_bytes()?;
+            let len = reader.u8()?;
+            let proof = reader.hashes(len.into())?;
+            Ok(Self { message, proof })
+        }
+
+        pub fn verify(&self, root: &MerkleRoot<Keccak160>) -> bool {
+            root.check_path(self.proof, self.message)
+        }
+    }
+
+    /// Iterates over the updates of a `ProofRef::WormholeMerkle`.
+    #[derive(Clone, Copy, Debug)]
+    pub struct MerklePriceUpdateIter<'a> {
+        reader:    Reader<'a>,
+        remaining: usize,
+    }
+
+    impl<'a> Iterator for MerklePriceUpdateIter<'a> {
+        type Item = MerklePriceUpdateRef<'a>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.remaining = self.remaining.checked_sub(1)?;
+            MerklePriceUpdateRef::read(&mut self.reader).ok()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.remaining, Some(self.remaining))
+        }
+    }
+
+    impl ExactSizeIterator for MerklePriceUpdateIter<'_> {
+    }
+
+    /// Iterates over the messages of a `ProofRef::WormholeMerkleMulti`.
+    #[derive(Clone, Copy, Debug)]
+    pub struct MessageIter<'a> {
+        reader:    Reader<'a>,
+        remaining: usize,
+    }
+
+    impl<'a> Iterator for MessageIter<'a> {
+        type Item = &'a [u8];
+
+        fn next(&mut self) -> Option<Self::Item> {
+            self.remaining = self.remaining.checked_sub(1)?;
+            self.reader.prefixed_bytes().ok()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.remaining, Some(self.remaining))
+        }
+    }
+
+    impl ExactSizeIterator for MessageIter<'_> {
+    }
+
+    /// Reads big endian wire format values from the front of a buffer without copying them.
+    #[derive(Clone, Copy, Debug)]
+    struct Reader<'a>(&'a [u8]);
+
+    impl<'a> Reader<'a> {
+        fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+            require!(self.0.len() >= len, Error::DeserializationError);
+            let (head, tail) = self.0.split_at(len);
+            self.0 = tail;
+            Ok(head)
+        }
+
+        fn u8(&mut self) -> Result<u8, Error> {
+            Ok(self.bytes(1)?[0])
+        }
+
+        fn u16(&mut self) -> Result<u16, Error> {
+            Ok(byteorder::BE::read_u16(self.bytes(2)?))
+        }
+
+        /// Read a `PrefixedVec<u16, u8>`.
+        fn prefixed_bytes(&mut self) -> Result<&'a [u8], Error> {
+            let len = self.u16()?;
+            self.bytes(len.into())
+        }
+
+        /// Read `len` hashes, `Hash` has an alignment of 1 so the bytes can be cast in place.
+        fn hashes(&mut self, len: usize) -> Result<&'a [Hash], Error> {
+            let bytes = self.bytes(len * core::mem::size_of::<Hash>())?;
+            Ok(bytemuck::cast_slice(bytes))
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::wire::{
-        array,
-        v1::{
-            AccumulatorUpdateData,
-            Proof,
+    use {
+        crate::wire::{
+            array,
+            from_slice,
+            to_vec,
+            v1::{
+                AccumulatorUpdateData,
+                Proof,
+            },
+            Deserializer,
+            DeserializerError,
+            PrefixedVec,
+            Serializer,
+        },
+        byteorder::ByteOrder,
+        proptest::prelude::*,
+        serde::{
+            Deserialize,
+            Serialize,
+        },
+        std::{
+            collections::BTreeMap,
+            fmt::Debug,
         },
-        Deserializer,
-        PrefixedVec,
-        Serializer,
     };
 
     // Test the arbitrary fixed sized array serialization implementation.
@@ -368,6 +615,84 @@ mod tests {
         );
     }
 
+    // Golden test for the types that the golden structure above predates: options, floats, chars
+    // and maps. A little-endian serializer is used to show floats are big-endian regardless.
+    #[test]
+    fn test_pyth_serde_option_float_char_map() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct GoldenStruct {
+            t_none:   Option<u16>,
+            t_some:   Option<u16>,
+            t_nested: Option<Option<u8>>,
+            t_f32:    f32,
+            t_f64:    f64,
+            t_char:   char,
+            t_map:    BTreeMap<u8, u16>,
+        }
+
+        let golden_struct = GoldenStruct {
+            t_none:   None,
+            t_some:   Some(2),
+            t_nested: Some(None),
+            t_f32:    1.5,
+            t_f64:    -2.0,
+            t_char:   '€',
+            t_map:    BTreeMap::from([(3, 4), (5, 6)]),
+        };
+
+        let buffer = to_vec::<_, byteorder::LE>(&golden_struct).unwrap();
+        assert_eq!(
+            buffer,
+            [
+                0, // t_none
+                1, 2, 0, // t_some
+                1, 0, // t_nested
+                0x3f, 0xc0, 0, 0, // t_f32
+                0xc0, 0, 0, 0, 0, 0, 0, 0, // t_f64
+                0xac, 0x20, 0, 0, // t_char
+                2, 3, 4, 0, 5, 6, 0, // t_map
+            ]
+        );
+        assert_eq!(
+            golden_struct,
+            from_slice::<byteorder::LE, _>(&buffer).unwrap()
+        );
+
+        // Maps are encoded the same as a sequence of key value pairs.
+        assert_eq!(
+            to_vec::<_, byteorder::LE>(&golden_struct.t_map).unwrap(),
+            to_vec::<_, byteorder::LE>(&vec![(3u8, 4u16), (5, 6)]).unwrap()
+        );
+
+        // Option tags other than 0 and 1 and invalid chars are rejected.
+        assert!(matches!(
+            from_slice::<byteorder::LE, Option<u8>>(&[2, 0]),
+            Err(DeserializerError::InvalidEnumVariant)
+        ));
+        assert!(matches!(
+            from_slice::<byteorder::LE, char>(&0xd800u32.to_le_bytes()),
+            Err(DeserializerError::InvalidChar(0xd800))
+        ));
+    }
+
+    // Truncated input is reported the same way as by the `std::io::Read` based deserializer: as an
+    // `UnexpectedEof` io error for fixed size values and as `Eof` for the body of strings and bytes.
+    #[test]
+    fn test_truncated_input_errors() {
+        assert!(matches!(
+            from_slice::<byteorder::LE, u32>(&[1, 2, 3]),
+            Err(DeserializerError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            from_slice::<byteorder::LE, &str>(&[]),
+            Err(DeserializerError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            from_slice::<byteorder::LE, &str>(&[3, b'a']),
+            Err(DeserializerError::Eof)
+        ));
+    }
+
     #[test]
     #[rustfmt::skip]
     /// This method tests that our EnumAccess workaround does not violate any memory safety rules.
@@ -510,6 +835,201 @@ mod tests {
         assert_eq!(deserialized_update, empty_update);
     }
 
+    // Test that a multiproof survives a round trip through the wire format and still checks
+    // against the root of the tree it was built from.
+    #[test]
+    fn test_accumulator_update_data_multiproof_serde() {
+        use crate::{
+            accumulators::merkle::{
+                MerkleMultiProof,
+                MerkleTree,
+            },
+            hashers::keccak256_160::Keccak160,
+        };
+
+        let messages: Vec<Vec<u8>> = (0..2000u16).map(|i| i.to_be_bytes().to_vec()).collect();
+        let items: Vec<&[u8]> = messages.iter().map(|m| m.as_ref()).collect();
+        let tree = MerkleTree::<Keccak160>::new(&items).unwrap();
+
+        // Prove every eighth message so the proof needs more than 255 hashes and flags.
+        let proven: Vec<&[u8]> = items.iter().step_by(8).copied().collect();
+        let (order, proof) = tree.prove_multi(&proven).unwrap();
+        assert!(proof.hashes.len() > 255 && proof.flags.len() > 255);
+
+        let update = AccumulatorUpdateData::new(Proof::WormholeMerkleMulti {
+            vaa:      PrefixedVec::from(vec![]),
+            messages: order
+                .iter()
+                .map(|i| PrefixedVec::from(proven[*i].to_vec()))
+                .collect(),
+            hashes:   PrefixedVec::from(proof.hashes),
+            flags:    PrefixedVec::from(proof.flags),
+        });
+
+        let buffer = crate::wire::to_vec::<_, byteorder::BE>(&update).unwrap();
+        let deserialized_update = AccumulatorUpdateData::try_from_slice(&buffer).unwrap();
+        assert_eq!(deserialized_update, update);
+
+        match deserialized_update.proof {
+            Proof::WormholeMerkleMulti {
+                messages,
+                hashes,
+                flags,
+                ..
+            } => {
+                let messages: Vec<Vec<u8>> = messages.into_iter().map(Vec::from).collect();
+                let messages: Vec<&[u8]> = messages.iter().map(|m| m.as_ref()).collect();
+                let proof = MerkleMultiProof::<Keccak160> {
+                    hashes: hashes.into(),
+                    flags:  flags.into(),
+                };
+                assert!(tree.root.check_multi(&proof, &messages));
+            }
+            _ => panic!("Unexpected proof type"),
+        }
+    }
+
+    // Test that the borrowed view of an AccumulatorUpdateData reads the same data as the owned
+    // type for both proof types, borrows it from the input buffer, and verifies the proofs.
+    #[test]
+    fn test_accumulator_update_data_ref() {
+        use crate::{
+            accumulators::{
+                merkle::{
+                    MerklePath,
+                    MerkleRoot,
+                    MerkleTree,
+                },
+                Accumulator,
+            },
+            hashers::keccak256_160::Keccak160,
+            wire::v1::{
+                AccumulatorUpdateDataRef,
+                MerklePriceUpdate,
+                ProofRef,
+            },
+        };
+
+        let messages: Vec<Vec<u8>> = (0..100u16).map(|i| i.to_be_bytes().repeat(40)).collect();
+        let items: Vec<&[u8]> = messages.iter().map(|m| m.as_ref()).collect();
+        let tree = MerkleTree::<Keccak160>::new(&items).unwrap();
+        let wrong_root = MerkleRoot::<Keccak160>::new([0u8; 20]);
+        let vaa = vec![7u8; 300];
+
+        let update = AccumulatorUpdateData::new(Proof::WormholeMerkle {
+            vaa:     PrefixedVec::from(vaa.clone()),
+            updates: items
+                .iter()
+                .step_by(10)
+                .map(|item| MerklePriceUpdate {
+                    message: PrefixedVec::from(item.to_vec()),
+                    proof:   tree.prove(item).unwrap(),
+                })
+                .collect(),
+        });
+        let buffer = crate::wire::to_vec::<_, byteorder::BE>(&update).unwrap();
+        let view = AccumulatorUpdateDataRef::try_from_slice(&buffer).unwrap();
+        assert_eq!(view.proof.vaa(), &vaa[..]);
+        assert!(buffer.as_ptr_range().contains(&view.proof.vaa().as_ptr()));
+        assert!(view.proof.verify(&tree.root));
+        assert!(!view.proof.verify(&wrong_root));
+
+        let ProofRef::WormholeMerkle { updates, .. } = view.proof else {
+            panic!("Unexpected proof type");
+        };
+        let Proof::WormholeMerkle {
+            updates: expected, ..
+        } = update.proof
+        else {
+            panic!("Unexpected proof type");
+        };
+        assert_eq!(updates.len(), expected.len());
+        for (update, expected) in updates.zip(expected) {
+            assert_eq!(update.message, Vec::from(expected.message).as_slice());
+            assert_eq!(MerklePath::new(update.proof.to_vec()), expected.proof);
+            assert!(buffer.as_ptr_range().contains(&update.message.as_ptr()));
+        }
+
+        let proven: Vec<&[u8]> = items.iter().step_by(3).copied().collect();
+        let (order, proof) = tree.prove_multi(&proven).unwrap();
+        let update = AccumulatorUpdateData::new(Proof::WormholeMerkleMulti {
+            vaa:      PrefixedVec::from(vaa.clone()),
+            messages: order
+                .iter()
+                .map(|i| PrefixedVec::from(proven[*i].to_vec()))
+                .collect(),
+            hashes:   PrefixedVec::from(proof.hashes.clone()),
+            flags:    PrefixedVec::from(proof.flags.clone()),
+        });
+        let buffer = crate::wire::to_vec::<_, byteorder::BE>(&update).unwrap();
+        let view = AccumulatorUpdateDataRef::try_from_slice(&buffer).unwrap();
+        assert_eq!(view.proof.vaa(), &vaa[..]);
+        assert!(view.proof.verify(&tree.root));
+        assert!(!view.proof.verify(&wrong_root));
+
+        let ProofRef::WormholeMerkleMulti {
+            messages,
+            hashes,
+            flags,
+            ..
+        } = view.proof
+        else {
+            panic!("Unexpected proof type");
+        };
+        assert!(messages.eq(order.iter().map(|i| proven[*i])));
+        assert_eq!(hashes, &proof.hashes[..]);
+        assert!(flags.iter().map(|flag| *flag != 0).eq(proof.flags));
+    }
+
+    // A borrowed view must reject any truncated buffer, as walking it after construction cannot
+    // report errors.
+    #[test]
+    fn test_accumulator_update_data_ref_truncated() {
+        use crate::{
+            accumulators::{
+                merkle::MerkleTree,
+                Accumulator,
+            },
+            error::Error,
+            hashers::keccak256_160::Keccak160,
+            wire::v1::{
+                AccumulatorUpdateDataRef,
+                MerklePriceUpdate,
+            },
+        };
+
+        let items: Vec<&[u8]> = vec![b"a", b"b", b"c", b"d"];
+        let tree = MerkleTree::<Keccak160>::new(&items).unwrap();
+        let update = AccumulatorUpdateData::new(Proof::WormholeMerkle {
+            vaa:     PrefixedVec::from(vec![1, 2, 3]),
+            updates: items
+                .iter()
+                .map(|item| MerklePriceUpdate {
+                    message: PrefixedVec::from(item.to_vec()),
+                    proof:   tree.prove(item).unwrap(),
+                })
+                .collect(),
+        });
+        let mut buffer = crate::wire::to_vec::<_, byteorder::BE>(&update).unwrap();
+
+        for len in 0..buffer.len() {
+            assert!(AccumulatorUpdateDataRef::try_from_slice(&buffer[..len]).is_err());
+        }
+
+        // Bytes after the proof are ignored, the same as for the owned type.
+        buffer.extend_from_slice(&[0xff; 8]);
+        assert!(AccumulatorUpdateDataRef::try_from_slice(&buffer)
+            .unwrap()
+            .proof
+            .verify(&tree.root));
+
+        buffer[0] = b'X';
+        assert!(matches!(
+            AccumulatorUpdateDataRef::try_from_slice(&buffer),
+            Err(Error::InvalidMagic)
+        ));
+    }
+
     // Test if the AccumulatorUpdateData major and minor version increases work as expected
     #[test]
     fn test_accumulator_forward_compatibility() {
@@ -533,4 +1053,71 @@ mod tests {
         buffer[4] = 0x03;
         AccumulatorUpdateData::try_from_slice(&buffer).unwrap_err();
     }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct RoundTrip {
+        t_option:        Option<u64>,
+        t_option_nested: Option<Option<i32>>,
+        t_option_vec:    Vec<Option<bool>>,
+        t_f32:           f32,
+        t_f64:           f64,
+        t_char:          char,
+        t_map:           BTreeMap<u16, String>,
+        t_map_nested:    BTreeMap<char, Option<Vec<u8>>>,
+        t_tuple:         (u8, i128, String),
+    }
+
+    fn round_trip<B: ByteOrder, T>(value: &T) -> T
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        from_slice::<B, T>(&to_vec::<T, B>(value).unwrap()).unwrap()
+    }
+
+    proptest! {
+        // Check that arbitrary values of the supported types survive a round trip in both byte
+        // orders. NaNs are excluded here as they never compare equal, see the test below.
+        #[test]
+        fn test_pyth_serde_round_trip(
+            t_option in any::<Option<u64>>(),
+            t_option_nested in any::<Option<Option<i32>>>(),
+            t_option_vec in prop::collection::vec(any::<Option<bool>>(), 0..32),
+            t_f32 in any::<f32>().prop_filter("NaN", |f| !f.is_nan()),
+            t_f64 in any::<f64>().prop_filter("NaN", |f| !f.is_nan()),
+            t_char in any::<char>(),
+            t_map in prop::collection::btree_map(any::<u16>(), "\\PC{0,16}", 0..32),
+            t_map_nested in prop::collection::btree_map(
+                any::<char>(),
+                any::<Option<Vec<u8>>>(),
+                0..8,
+            ),
+            t_tuple in (any::<u8>(), any::<i128>(), "\\PC{0,16}"),
+        ) {
+            let value = RoundTrip {
+                t_option,
+                t_option_nested,
+                t_option_vec,
+                t_f32,
+                t_f64,
+                t_char,
+                t_map,
+                t_map_nested,
+                t_tuple,
+            };
+            assert_eq!(round_trip::<byteorder::BE, _>(&value), value);
+            assert_eq!(round_trip::<byteorder::LE, _>(&value), value);
+        }
+
+        // Floats, including NaNs, must round trip bit for bit and always be encoded big-endian.
+        #[test]
+        fn test_pyth_serde_float_bits(v32 in any::<f32>(), v64 in any::<f64>()) {
+            let buffer = to_vec::<_, byteorder::LE>(&(v32, v64)).unwrap();
+            assert_eq!(&buffer[..4], &v32.to_be_bytes());
+            assert_eq!(&buffer[4..], &v64.to_be_bytes());
+
+            let (r32, r64) = from_slice::<byteorder::LE, (f32, f64)>(&buffer).unwrap();
+            assert_eq!(r32.to_bits(), v32.to_bits());
+            assert_eq!(r64.to_bits(), v64.to_bits());
+        }
+    }
 }
@@ -12,12 +12,12 @@
 //! }
 //! ```
 use {
+    core::mem::MaybeUninit,
     serde::{
         Deserialize,
         Serialize,
         Serializer,
     },
-    std::mem::MaybeUninit,
 };
 
 /// Serialize an array of size N using a const generic parameter to drive serialize_seq.
@@ -35,7 +35,7 @@ where
 /// A visitor that carries type-level information about the length of the array we want to
 /// deserialize.
 struct ArrayVisitor<T, const N: usize> {
-    _marker: std::marker::PhantomData<T>,
+    _marker: core::marker::PhantomData<T>,
 }
 
 /// Implement a Visitor over our ArrayVisitor that knows how many times to
@@ -46,7 +46,7 @@ where
 {
     type Value = [T; N];
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(formatter, "an array of length {N}")
     }
 
@@ -66,7 +66,7 @@ where
                 .ok_or_else(|| serde::de::Error::invalid_length(pos, &self))?;
 
             unsafe {
-                std::ptr::write(ptr.add(pos), next);
+                core::ptr::write(ptr.add(pos), next);
             }
 
             pos += 1;
@@ -91,7 +91,7 @@ where
     deserializer.deserialize_tuple(
         N,
         ArrayVisitor {
-            _marker: std::marker::PhantomData,
+            _marker: core::marker::PhantomData,
         },
     )
 }
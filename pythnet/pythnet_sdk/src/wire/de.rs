@@ -24,9 +24,21 @@
 
 use {
     crate::require,
+    alloc::{
+        boxed::Box,
+        string::ToString,
+    },
     byteorder::{
+        BigEndian,
         ByteOrder,
-        ReadBytesExt,
+    },
+    core::{
+        fmt::{
+            self,
+            Display,
+        },
+        mem::size_of,
+        str::Utf8Error,
     },
     serde::{
         de::{
@@ -37,15 +49,6 @@ use {
         },
         Deserialize,
     },
-    std::{
-        io::{
-            Cursor,
-            Seek,
-            SeekFrom,
-        },
-        mem::size_of,
-    },
-    thiserror::Error,
 };
 
 /// Deserialize a Pyth wire-format buffer into a type.
@@ -64,40 +67,96 @@ where
     T::deserialize(&mut deserializer)
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug)]
 pub enum DeserializerError {
-    #[error("io error: {0}")]
-    Io(#[from] std::io::Error),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    Utf8(Utf8Error),
+    Unsupported,
+    SequenceTooLarge(usize),
+    Message(Box<str>),
+    InvalidEnumVariant,
+    InvalidChar(u32),
+    Eof,
+}
 
-    #[error("invalid utf8: {0}")]
-    Utf8(#[from] std::str::Utf8Error),
+impl Display for DeserializerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            DeserializerError::Io(e) => write!(f, "io error: {e}"),
+            DeserializerError::Utf8(e) => write!(f, "invalid utf8: {e}"),
+            DeserializerError::Unsupported => write!(f, "this type is not supported"),
+            DeserializerError::SequenceTooLarge(len) => {
+                write!(
+                    f,
+                    "sequence too large ({len} elements), max supported is 255"
+                )
+            }
+            DeserializerError::Message(msg) => write!(f, "message: {msg}"),
+            DeserializerError::InvalidEnumVariant => {
+                write!(
+                    f,
+                    "invalid enum variant, higher than expected variant range"
+                )
+            }
+            DeserializerError::InvalidChar(value) => {
+                write!(f, "invalid char, {value:#x} is not a unicode scalar value")
+            }
+            DeserializerError::Eof => write!(f, "eof"),
+        }
+    }
+}
 
-    #[error("this type is not supported")]
-    Unsupported,
+#[cfg(feature = "std")]
+impl std::error::Error for DeserializerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeserializerError::Io(e) => Some(e),
+            DeserializerError::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
-    #[error("sequence too large ({0} elements), max supported is 255")]
-    SequenceTooLarge(usize),
+#[cfg(feature = "std")]
+impl From<std::io::Error> for DeserializerError {
+    fn from(e: std::io::Error) -> Self {
+        DeserializerError::Io(e)
+    }
+}
 
-    #[error("message: {0}")]
-    Message(Box<str>),
+impl From<Utf8Error> for DeserializerError {
+    fn from(e: Utf8Error) -> Self {
+        DeserializerError::Utf8(e)
+    }
+}
 
-    #[error("invalid enum variant, higher than expected variant range")]
-    InvalidEnumVariant,
+impl DeserializerError {
+    /// The error for input that ends in the middle of a fixed size value. With `std` this is an
+    /// `UnexpectedEof` io error, as returned when the deserializer read from `std::io::Read`,
+    /// while without `std` it is reported as `Eof`.
+    #[cfg(feature = "std")]
+    fn unexpected_eof() -> Self {
+        DeserializerError::Io(std::io::ErrorKind::UnexpectedEof.into())
+    }
 
-    #[error("eof")]
-    Eof,
+    #[cfg(not(feature = "std"))]
+    fn unexpected_eof() -> Self {
+        DeserializerError::Eof
+    }
 }
 
 pub struct Deserializer<'de, B>
 where
     B: ByteOrder,
 {
-    cursor: Cursor<&'de [u8]>,
-    endian: std::marker::PhantomData<B>,
+    buffer: &'de [u8],
+    endian: core::marker::PhantomData<B>,
 }
 
 impl serde::de::Error for DeserializerError {
-    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+    fn custom<T: Display>(msg: T) -> Self {
         DeserializerError::Message(msg.to_string().into_boxed_str())
     }
 }
@@ -108,10 +167,33 @@ where
 {
     pub fn new(buffer: &'de [u8]) -> Self {
         Self {
-            cursor: Cursor::new(buffer),
-            endian: std::marker::PhantomData,
+            buffer,
+            endian: core::marker::PhantomData,
         }
     }
+
+    /// Split the next `len` bytes off the front of the remaining input. The returned slice
+    /// borrows from the original buffer, which is what allows `&str` and `&[u8]` fields to be
+    /// deserialized without copying.
+    #[inline]
+    fn take(&mut self, len: usize) -> Result<&'de [u8], DeserializerError> {
+        require!(self.buffer.len() >= len, DeserializerError::Eof);
+        let (head, tail) = self.buffer.split_at(len);
+        self.buffer = tail;
+        Ok(head)
+    }
+
+    /// Same as `take` but for the bytes of a fixed size value such as an integer.
+    #[inline]
+    fn take_value(&mut self, len: usize) -> Result<&'de [u8], DeserializerError> {
+        self.take(len)
+            .map_err(|_| DeserializerError::unexpected_eof())
+    }
+
+    #[inline]
+    fn read_u8(&mut self) -> Result<u8, DeserializerError> {
+        Ok(self.take_value(1)?[0])
+    }
 }
 
 impl<'de, B> serde::de::Deserializer<'de> for &'_ mut Deserializer<'de, B>
@@ -120,6 +202,8 @@ where
 {
     type Error = DeserializerError;
 
+    // The format is not self-describing, so there is no way to tell what the next value is without
+    // being told its type. This also rules out `#[serde(untagged)]` and `#[serde(flatten)]`.
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
@@ -138,7 +222,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self.cursor.read_u8().map_err(DeserializerError::from)?;
+        let value = self.read_u8()?;
         visitor.visit_bool(value != 0)
     }
 
@@ -146,7 +230,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self.cursor.read_i8().map_err(DeserializerError::from)?;
+        let value = self.read_u8()? as i8;
         visitor.visit_i8(value)
     }
 
@@ -154,10 +238,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_i16::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_i16(self.take_value(2)?);
 
         visitor.visit_i16(value)
     }
@@ -166,10 +247,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_i32::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_i32(self.take_value(4)?);
 
         visitor.visit_i32(value)
     }
@@ -178,10 +256,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_i64::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_i64(self.take_value(8)?);
 
         visitor.visit_i64(value)
     }
@@ -190,10 +265,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_i128::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_i128(self.take_value(16)?);
 
         visitor.visit_i128(value)
     }
@@ -202,7 +274,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self.cursor.read_u8().map_err(DeserializerError::from)?;
+        let value = self.read_u8()?;
         visitor.visit_u8(value)
     }
 
@@ -210,10 +282,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_u16::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_u16(self.take_value(2)?);
 
         visitor.visit_u16(value)
     }
@@ -222,10 +291,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_u32::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_u32(self.take_value(4)?);
 
         visitor.visit_u32(value)
     }
@@ -234,10 +300,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_u64::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_u64(self.take_value(8)?);
 
         visitor.visit_u64(value)
     }
@@ -246,60 +309,44 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let value = self
-            .cursor
-            .read_u128::<B>()
-            .map_err(DeserializerError::from)?;
+        let value = B::read_u128(self.take_value(16)?);
 
         visitor.visit_u128(value)
     }
 
-    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        Err(DeserializerError::Unsupported)
+        let value = BigEndian::read_f32(self.take_value(4)?);
+        visitor.visit_f32(value)
     }
 
-    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        Err(DeserializerError::Unsupported)
+        let value = BigEndian::read_f64(self.take_value(8)?);
+        visitor.visit_f64(value)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        Err(DeserializerError::Unsupported)
+        let value = B::read_u32(self.take_value(4)?);
+        let value = char::from_u32(value).ok_or(DeserializerError::InvalidChar(value))?;
+        visitor.visit_char(value)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        let len = self.cursor.read_u8().map_err(DeserializerError::from)? as u64;
-
-        // We cannot use cursor read methods as they copy the data out of the internal buffer,
-        // where we actually want a pointer into that buffer. So instead, we take the internal
-        // representation (the underlying &[u8]) and slice it to get the data we want. We then
-        // advance the cursor to simulate the read.
-        //
-        // Note that we do the advance first because otherwise we run into a immutable->mutable
-        // borrow issue, but the reverse is fine.
-        self.cursor
-            .seek(SeekFrom::Current(len as i64))
-            .map_err(DeserializerError::from)?;
+        let len = self.read_u8()? as usize;
+        let buf = self.take(len)?;
 
-        let buf = {
-            let buf = self.cursor.get_ref();
-            buf[(self.cursor.position() - len) as usize..]
-                .get(..len as usize)
-                .ok_or(DeserializerError::Eof)?
-        };
-
-        visitor.visit_borrowed_str(std::str::from_utf8(buf).map_err(DeserializerError::from)?)
+        visitor.visit_borrowed_str(core::str::from_utf8(buf)?)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -313,19 +360,8 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let len = self.cursor.read_u8().map_err(DeserializerError::from)? as u64;
-
-        // See comment in deserialize_str for an explanation of this code.
-        self.cursor
-            .seek(SeekFrom::Current(len as i64))
-            .map_err(DeserializerError::from)?;
-
-        let buf = {
-            let buf = self.cursor.get_ref();
-            buf[(self.cursor.position() - len) as usize..]
-                .get(..len as usize)
-                .ok_or(DeserializerError::Eof)?
-        };
+        let len = self.read_u8()? as usize;
+        let buf = self.take(len)?;
 
         visitor.visit_borrowed_bytes(buf)
     }
@@ -337,11 +373,15 @@ where
         self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
-        Err(DeserializerError::Unsupported)
+        match self.read_u8()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            _ => Err(DeserializerError::InvalidEnumVariant),
+        }
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -377,7 +417,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let len = self.cursor.read_u8().map_err(DeserializerError::from)? as usize;
+        let len = self.read_u8()? as usize;
         visitor.visit_seq(SequenceIterator::new(self, len))
     }
 
@@ -404,7 +444,7 @@ where
     where
         V: serde::de::Visitor<'de>,
     {
-        let len = self.cursor.read_u8().map_err(DeserializerError::from)? as usize;
+        let len = self.read_u8()? as usize;
         visitor.visit_map(SequenceIterator::new(self, len))
     }
 
@@ -431,7 +471,7 @@ where
     {
         // We read the discriminator here so that we can make the expected enum variant available
         // to the `visit_enum` call.
-        let variant = self.cursor.read_u8().map_err(DeserializerError::from)?;
+        let variant = self.read_u8()?;
         if variant >= variants.len() as u8 {
             return Err(DeserializerError::InvalidEnumVariant);
         }
@@ -578,7 +618,7 @@ impl<'de, 'a, B: ByteOrder> EnumAccess<'de> for Enum<'de, 'a, B> {
         );
 
         Ok((
-            unsafe { std::mem::transmute_copy::<u8, V::Value>(&self.variant) },
+            unsafe { core::mem::transmute_copy::<u8, V::Value>(&self.variant) },
             self.de,
         ))
     }
@@ -1,11 +1,14 @@
-use serde::{
-    de::DeserializeSeed,
-    ser::{
-        SerializeSeq,
-        SerializeStruct,
+use {
+    alloc::vec::Vec,
+    serde::{
+        de::DeserializeSeed,
+        ser::{
+            SerializeSeq,
+            SerializeStruct,
+        },
+        Deserialize,
+        Serialize,
     },
-    Deserialize,
-    Serialize,
 };
 
 /// PrefixlessVec overrides the serialization to _not_ write a length prefix.
@@ -29,7 +32,7 @@ where
 }
 
 struct PrefixlessSeed<T> {
-    __phantom: std::marker::PhantomData<T>,
+    __phantom: core::marker::PhantomData<T>,
     len:       usize,
 }
 
@@ -49,7 +52,7 @@ where
     ) -> Result<Self::Value, D::Error> {
         struct PrefixlessVecVisitor<T> {
             len:       usize,
-            __phantom: std::marker::PhantomData<T>,
+            __phantom: core::marker::PhantomData<T>,
         }
 
         impl<'de, T> serde::de::Visitor<'de> for PrefixlessVecVisitor<T>
@@ -58,7 +61,7 @@ where
         {
             type Value = PrefixlessVec<T>;
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
                 formatter.write_str("struct PrefixlessVec")
             }
 
@@ -83,7 +86,7 @@ where
             self.len,
             PrefixlessVecVisitor {
                 len:       self.len,
-                __phantom: std::marker::PhantomData,
+                __phantom: core::marker::PhantomData,
             },
         )
     }
@@ -101,14 +104,14 @@ where
 /// data on chain anyway.
 #[derive(Clone, Debug, Hash, PartialEq, PartialOrd)]
 pub struct PrefixedVec<L, T> {
-    __phantom: std::marker::PhantomData<L>,
+    __phantom: core::marker::PhantomData<L>,
     data:      PrefixlessVec<T>,
 }
 
 impl<L, T> From<Vec<T>> for PrefixedVec<L, T> {
     fn from(data: Vec<T>) -> Self {
         Self {
-            __phantom: std::marker::PhantomData,
+            __phantom: core::marker::PhantomData,
             data:      PrefixlessVec { inner: data },
         }
     }
@@ -128,7 +131,7 @@ impl<L, T> AsRef<Vec<T>> for PrefixedVec<L, T> {
 
 impl<L, T> IntoIterator for PrefixedVec<L, T> {
     type Item = T;
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type IntoIter = alloc::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.data.inner.into_iter()
@@ -136,7 +139,7 @@ impl<L, T> IntoIterator for PrefixedVec<L, T> {
 }
 
 impl<L, T> PrefixedVec<L, T> {
-    pub fn iter(&self) -> std::slice::Iter<T> {
+    pub fn iter(&self) -> core::slice::Iter<T> {
         self.data.inner.iter()
     }
 }
@@ -146,7 +149,7 @@ where
     T: Serialize,
     L: Serialize,
     L: TryFrom<usize>,
-    <L as TryFrom<usize>>::Error: std::fmt::Debug,
+    <L as TryFrom<usize>>::Error: core::fmt::Debug,
 {
     #[inline]
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
@@ -175,7 +178,7 @@ where
         }
 
         struct PrefixedVecVisitor<L, T> {
-            __phantom: std::marker::PhantomData<(L, T)>,
+            __phantom: core::marker::PhantomData<(L, T)>,
         }
 
         impl<'de, L, T> serde::de::Visitor<'de> for PrefixedVecVisitor<L, T>
@@ -187,7 +190,7 @@ where
         {
             type Value = PrefixedVec<L, T>;
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
                 formatter.write_str("struct PrefixedVec")
             }
 
@@ -206,13 +209,13 @@ where
                 // need to use the PrefixlessSeed to pass the expected size to the deserializer.
                 let data = seq
                     .next_element_seed(PrefixlessSeed {
-                        __phantom: std::marker::PhantomData,
+                        __phantom: core::marker::PhantomData,
                         len,
                     })?
                     .ok_or_else(|| serde::de::Error::invalid_length(1, &"PrefixlessVec"))?;
 
                 Ok(PrefixedVec {
-                    __phantom: std::marker::PhantomData,
+                    __phantom: core::marker::PhantomData,
                     data,
                 })
             }
@@ -222,7 +225,7 @@ where
             "PrefixedVec",
             &["len", "data"],
             PrefixedVecVisitor {
-                __phantom: std::marker::PhantomData,
+                __phantom: core::marker::PhantomData,
             },
         )
     }
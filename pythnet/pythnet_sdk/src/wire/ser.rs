@@ -98,7 +98,12 @@
 //!
 //! Floats:
 //!
-//! - `f32/64/128` are not supported due to different chains having different float formats.
+//! - `f32/64` are serialized as their IEEE-754 bits in big-endian, regardless of the parser
+//!   endianess type param, so a float reads the same on every chain.
+//!
+//! Chars:
+//!
+//! - `char` is serialized as its unicode scalar value in a `u32`.
 //!
 //! Strings:
 //!
@@ -109,11 +114,20 @@
 //!
 //! - `Vec<T>` is serialized as a u8 length followed by the serialized elements of the vector.
 //! - `&[T]` is serialized as a u8 length followed by the serialized elements of the slice.
+//! - `PrefixedVec<L, T>` is serialized as an `L` length followed by the serialized elements, for
+//!   sequences that need more than 255 elements.
+//!
+//! Maps:
+//!
+//! - `BTreeMap<K, V>`, `HashMap<K, V>` etc. are serialized as a u8 length followed by each key
+//!   and value in iteration order, which is the same as a `Vec<(K, V)>`. A map with more than 255
+//!   entries can be sent as a `PrefixedVec<L, (K, V)>` instead.
 //!
 //! Enums:
 //!
 //! - `enum` is serialized as a u8 variant index followed by the serialized variant data.
-//! - `Option<T>` is serialized as a u8 variant index followed by the serialized variant data.
+//! - `Option<T>` is serialized as a u8 `0` for `None` or a u8 `1` followed by the serialized
+//!   value for `Some`.
 //!
 //! Structs:
 //!
@@ -127,6 +141,9 @@
 //!
 //! - `()` is serialized as nothing.
 //!
+//! The format is not self-describing, so types that rely on `deserialize_any` (such as
+//! `#[serde(untagged)]` enums) cannot be deserialized.
+//!
 //!
 //! Example Usage
 //! --------------------------------------------------------------------------------
@@ -141,10 +158,18 @@
 //! }
 //! ```
 
+#[cfg(feature = "std")]
+pub use std::io::Write;
 use {
-    byteorder::{
-        ByteOrder,
-        WriteBytesExt,
+    alloc::{
+        boxed::Box,
+        string::ToString,
+        vec::Vec,
+    },
+    byteorder::ByteOrder,
+    core::fmt::{
+        self,
+        Display,
     },
     serde::{
         ser::{
@@ -158,13 +183,32 @@ use {
         },
         Serialize,
     },
-    std::{
-        fmt::Display,
-        io::Write,
-    },
-    thiserror::Error,
 };
 
+/// Minimal stand-in for `std::io::Write` that the serializer writes into when the `std` feature
+/// is disabled.
+#[cfg(not(feature = "std"))]
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), SerializerError>;
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Vec<u8> {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), SerializerError> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), SerializerError> {
+        (**self).write_all(buf)
+    }
+}
+
 pub fn to_writer<T, W, B>(writer: W, value: &T) -> Result<(), SerializerError>
 where
     T: Serialize,
@@ -185,33 +229,64 @@ where
     Ok(buf)
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug)]
 pub enum SerializerError {
-    #[error("io error: {0}")]
-    Io(#[from] std::io::Error),
-
-    #[error("this type is not supported")]
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
     Unsupported,
-
-    #[error("sequence too large ({0} elements), max supported is 255")]
     SequenceTooLarge(usize),
-
-    #[error("sequence length must be known before serializing")]
     SequenceLengthUnknown,
-
-    #[error("enum variant {0}::{1} cannot be parsed as `u8`: {2}")]
     InvalidEnumVariant(&'static str, u32, &'static str),
-
-    #[error("message: {0}")]
     Message(Box<str>),
 }
 
+impl Display for SerializerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            SerializerError::Io(e) => write!(f, "io error: {e}"),
+            SerializerError::Unsupported => write!(f, "this type is not supported"),
+            SerializerError::SequenceTooLarge(len) => {
+                write!(
+                    f,
+                    "sequence too large ({len} elements), max supported is 255"
+                )
+            }
+            SerializerError::SequenceLengthUnknown => {
+                write!(f, "sequence length must be known before serializing")
+            }
+            SerializerError::InvalidEnumVariant(name, index, variant) => write!(
+                f,
+                "enum variant {name}::{index} cannot be parsed as `u8`: {variant}"
+            ),
+            SerializerError::Message(msg) => write!(f, "message: {msg}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SerializerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerializerError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for SerializerError {
+    fn from(e: std::io::Error) -> Self {
+        SerializerError::Io(e)
+    }
+}
+
 /// A type for Pyth's common serialization format. Note that a ByteOrder type param is required as
 /// we serialize in both big and little endian depending on different use-cases.
 #[derive(Clone)]
 pub struct Serializer<W: Write, B: ByteOrder> {
     writer:  W,
-    _endian: std::marker::PhantomData<B>,
+    _endian: core::marker::PhantomData<B>,
 }
 
 impl serde::ser::Error for SerializerError {
@@ -224,7 +299,7 @@ impl<W: Write, B: ByteOrder> Serializer<W, B> {
     pub fn new(writer: W) -> Self {
         Self {
             writer,
-            _endian: std::marker::PhantomData,
+            _endian: core::marker::PhantomData,
         }
     }
 }
@@ -260,24 +335,30 @@ impl<'a, W: Write, B: ByteOrder> serde::Serializer for &'a mut Serializer<W, B>
 
     #[inline]
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_i16::<B>(v).map_err(SerializerError::from)
+        let mut buf = [0u8; 2];
+        B::write_i16(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_i32::<B>(v).map_err(SerializerError::from)
+        let mut buf = [0u8; 4];
+        B::write_i32(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_i64::<B>(v).map_err(SerializerError::from)
+        let mut buf = [0u8; 8];
+        B::write_i64(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        self.writer
-            .write_i128::<B>(v)
-            .map_err(SerializerError::from)
+        let mut buf = [0u8; 16];
+        B::write_i128(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
@@ -287,39 +368,51 @@ impl<'a, W: Write, B: ByteOrder> serde::Serializer for &'a mut Serializer<W, B>
 
     #[inline]
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_u16::<B>(v).map_err(SerializerError::from)
+        let mut buf = [0u8; 2];
+        B::write_u16(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_u32::<B>(v).map_err(SerializerError::from)
+        let mut buf = [0u8; 4];
+        B::write_u32(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_u64::<B>(v).map_err(SerializerError::from)
+        let mut buf = [0u8; 8];
+        B::write_u64(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        self.writer
-            .write_u128::<B>(v)
-            .map_err(SerializerError::from)
+        let mut buf = [0u8; 16];
+        B::write_u128(&mut buf, v);
+        self.writer.write_all(&buf).map_err(SerializerError::from)
     }
 
+    /// Floats are always written big-endian, independent of `B`.
     #[inline]
-    fn serialize_f32(self, _: f32) -> Result<Self::Ok, Self::Error> {
-        Err(SerializerError::Unsupported)
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.writer
+            .write_all(&v.to_be_bytes())
+            .map_err(SerializerError::from)
     }
 
+    /// Floats are always written big-endian, independent of `B`.
     #[inline]
-    fn serialize_f64(self, _: f64) -> Result<Self::Ok, Self::Error> {
-        Err(SerializerError::Unsupported)
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.writer
+            .write_all(&v.to_be_bytes())
+            .map_err(SerializerError::from)
     }
 
     #[inline]
-    fn serialize_char(self, _: char) -> Result<Self::Ok, Self::Error> {
-        Err(SerializerError::Unsupported)
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u32(v.into())
     }
 
     #[inline]
@@ -340,11 +433,12 @@ impl<'a, W: Write, B: ByteOrder> serde::Serializer for &'a mut Serializer<W, B>
 
     #[inline]
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        Err(SerializerError::Unsupported)
+        self.writer.write_all(&[0]).map_err(SerializerError::from)
     }
 
     #[inline]
     fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        self.writer.write_all(&[1])?;
         value.serialize(self)
     }
 
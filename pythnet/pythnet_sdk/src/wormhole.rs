@@ -5,25 +5,27 @@
 //! allows us to emit and parse messages through Wormhole.
 use {
     crate::Pubkey,
+    alloc::{
+        format,
+        vec::Vec,
+    },
     borsh::{
+        maybestd::io::{
+            Error,
+            ErrorKind::InvalidData,
+            Write,
+        },
         BorshDeserialize,
         BorshSerialize,
     },
+    core::ops::{
+        Deref,
+        DerefMut,
+    },
     serde::{
         Deserialize,
         Serialize,
     },
-    std::{
-        io::{
-            Error,
-            ErrorKind::InvalidData,
-            Write,
-        },
-        ops::{
-            Deref,
-            DerefMut,
-        },
-    },
 };
 
 #[repr(transparent)]
@@ -47,14 +49,14 @@ pub struct MessageData {
 }
 
 impl BorshSerialize for PostedMessageUnreliableData {
-    fn serialize<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> borsh::maybestd::io::Result<()> {
         writer.write_all(b"msu")?;
         BorshSerialize::serialize(&self.message, writer)
     }
 }
 
 impl BorshDeserialize for PostedMessageUnreliableData {
-    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+    fn deserialize(buf: &mut &[u8]) -> borsh::maybestd::io::Result<Self> {
         if buf.len() < 3 {
             return Err(Error::new(InvalidData, "Not enough bytes"));
         }
@@ -79,7 +79,10 @@ use {
         PriceStatus,
     },
     pythnet_sdk::{
-        accumulators::merkle::MerkleRoot,
+        accumulators::merkle::{
+            MerkleMultiProof,
+            MerkleRoot,
+        },
         hashers::keccak256_160::Keccak160,
         messages::Message,
         wire::{
@@ -91,6 +94,7 @@ use {
                 WormholePayload,
                 PYTHNET_ACCUMULATOR_UPDATE_MAGIC,
             },
+            PrefixedVec,
         },
     },
     std::{
@@ -515,20 +519,7 @@ fn parse_accumulator(deps: &Deps, env: &Env, data: &[u8]) -> StdResult<Vec<Price
         .map_err(|_| PythContractError::InvalidAccumulatorPayload)?;
     match update_data.proof {
         Proof::WormholeMerkle { vaa, updates } => {
-            let parsed_vaa = parse_and_verify_vaa(
-                *deps,
-                env.block.time.seconds(),
-                &Binary::from(Vec::from(vaa)),
-            )?;
-            let state = config_read(deps.storage).load()?;
-            verify_vaa_from_data_source(&state, &parsed_vaa)?;
-
-            let msg = WormholeMessage::try_from_bytes(parsed_vaa.payload)
-                .map_err(|_| PythContractError::InvalidWormholeMessage)?;
-
-            let root: MerkleRoot<Keccak160> = MerkleRoot::new(match msg.payload {
-                WormholePayload::Merkle(merkle_root) => merkle_root.root,
-            });
+            let root = parse_accumulator_root(deps, env, vaa)?;
             let mut feeds = vec![];
             for update in updates {
                 let message_vec = Vec::from(update.message);
@@ -536,33 +527,79 @@ fn parse_accumulator(deps: &Deps, env: &Env, data: &[u8]) -> StdResult<Vec<Price
                     return Err(PythContractError::InvalidMerkleProof)?;
                 }
 
-                let msg = from_slice::<BigEndian, Message>(&message_vec)
-                    .map_err(|_| PythContractError::InvalidAccumulatorMessage)?;
-
-                match msg {
-                    Message::PriceFeedMessage(price_feed_message) => {
-                        let price_feed = PriceFeed::new(
-                            PriceIdentifier::new(price_feed_message.feed_id),
-                            Price {
-                                price:        price_feed_message.price,
-                                conf:         price_feed_message.conf,
-                                expo:         price_feed_message.exponent,
-                                publish_time: price_feed_message.publish_time,
-                            },
-                            Price {
-                                price:        price_feed_message.ema_price,
-                                conf:         price_feed_message.ema_conf,
-                                expo:         price_feed_message.exponent,
-                                publish_time: price_feed_message.publish_time,
-                            },
-                        );
-                        feeds.push(price_feed);
-                    }
-                    _ => return Err(PythContractError::InvalidAccumulatorMessageType)?,
-                }
+                feeds.push(parse_accumulator_message(&message_vec)?);
             }
             Ok(feeds)
         }
+        Proof::WormholeMerkleMulti {
+            vaa,
+            messages,
+            hashes,
+            flags,
+        } => {
+            let root = parse_accumulator_root(deps, env, vaa)?;
+            let messages: Vec<Vec<u8>> = messages.into_iter().map(Vec::from).collect();
+            let message_refs: Vec<&[u8]> = messages.iter().map(|m| m.as_slice()).collect();
+            let proof = MerkleMultiProof::<Keccak160> {
+                hashes: hashes.into(),
+                flags:  flags.into(),
+            };
+            if !root.check_multi(&proof, &message_refs) {
+                return Err(PythContractError::InvalidMerkleProof)?;
+            }
+
+            messages
+                .iter()
+                .map(|message_vec| parse_accumulator_message(message_vec))
+                .collect()
+        }
+    }
+}
+
+/// Verify the VAA of an accumulator update and return the Merkle root it signs.
+fn parse_accumulator_root(
+    deps: &Deps,
+    env: &Env,
+    vaa: PrefixedVec<u16, u8>,
+) -> StdResult<MerkleRoot<Keccak160>> {
+    let parsed_vaa = parse_and_verify_vaa(
+        *deps,
+        env.block.time.seconds(),
+        &Binary::from(Vec::from(vaa)),
+    )?;
+    let state = config_read(deps.storage).load()?;
+    verify_vaa_from_data_source(&state, &parsed_vaa)?;
+
+    let msg = WormholeMessage::try_from_bytes(parsed_vaa.payload)
+        .map_err(|_| PythContractError::InvalidWormholeMessage)?;
+
+    Ok(MerkleRoot::new(match msg.payload {
+        WormholePayload::Merkle(merkle_root) => merkle_root.root,
+    }))
+}
+
+/// Parse a message whose membership in the accumulator has already been proven.
+fn parse_accumulator_message(message_vec: &[u8]) -> StdResult<PriceFeed> {
+    let msg = from_slice::<BigEndian, Message>(message_vec)
+        .map_err(|_| PythContractError::InvalidAccumulatorMessage)?;
+
+    match msg {
+        Message::PriceFeedMessage(price_feed_message) => Ok(PriceFeed::new(
+            PriceIdentifier::new(price_feed_message.feed_id),
+            Price {
+                price:        price_feed_message.price,
+                conf:         price_feed_message.conf,
+                expo:         price_feed_message.exponent,
+                publish_time: price_feed_message.publish_time,
+            },
+            Price {
+                price:        price_feed_message.ema_price,
+                conf:         price_feed_message.ema_conf,
+                expo:         price_feed_message.exponent,
+                publish_time: price_feed_message.publish_time,
+            },
+        )),
+        _ => Err(PythContractError::InvalidAccumulatorMessageType)?,
     }
 }
 
@@ -741,6 +778,9 @@ pub fn get_update_fee_amount(deps: &Deps, vaas: &[Binary]) -> StdResult<u128> {
                 Proof::WormholeMerkle { vaa: _, updates } => {
                     total_updates += updates.len() as u128;
                 }
+                Proof::WormholeMerkleMulti { messages, .. } => {
+                    total_updates += messages.len() as u128;
+                }
             }
         } else {
             total_updates += 1;
@@ -1198,13 +1238,12 @@ mod test {
         Message::PriceFeedMessage(msg)
     }
 
-    fn create_accumulator_message_from_updates(
-        price_updates: Vec<MerklePriceUpdate>,
-        tree: MerkleTree<Keccak160>,
+    fn create_accumulator_vaa(
+        tree: &MerkleTree<Keccak160>,
         corrupt_wormhole_message: bool,
         emitter_address: Vec<u8>,
         emitter_chain: u16,
-    ) -> Binary {
+    ) -> PrefixedVec<u16, u8> {
         let mut root_hash = [0u8; 20];
         root_hash.copy_from_slice(&to_vec::<_, BigEndian>(&tree.root).unwrap()[..20]);
         let wormhole_message = WormholeMessage::new(WormholePayload::Merkle(WormholeMerkleRoot {
@@ -1221,9 +1260,23 @@ mod test {
             vaa.payload[0] = 0;
         }
 
-        let vaa_binary = to_binary(&vaa).unwrap();
+        PrefixedVec::from(to_binary(&vaa).unwrap().to_vec())
+    }
+
+    fn create_accumulator_message_from_updates(
+        price_updates: Vec<MerklePriceUpdate>,
+        tree: MerkleTree<Keccak160>,
+        corrupt_wormhole_message: bool,
+        emitter_address: Vec<u8>,
+        emitter_chain: u16,
+    ) -> Binary {
         let accumulator_update_data = AccumulatorUpdateData::new(Proof::WormholeMerkle {
-            vaa:     PrefixedVec::from(vaa_binary.to_vec()),
+            vaa:     create_accumulator_vaa(
+                &tree,
+                corrupt_wormhole_message,
+                emitter_address,
+                emitter_chain,
+            ),
             updates: price_updates,
         });
 
@@ -1260,6 +1313,42 @@ mod test {
         )
     }
 
+    /// Create an update proving all of `updates` with a single multiproof. `tamper` can modify the
+    /// messages, in the order the proof checks them, and the proof before they are encoded.
+    fn create_accumulator_multiproof_message(
+        all_feeds: &[Message],
+        updates: &[Message],
+        tamper: impl FnOnce(&mut Vec<Vec<u8>>, &mut MerkleMultiProof<Keccak160>),
+    ) -> Binary {
+        let all_feeds_bytes: Vec<_> = all_feeds
+            .iter()
+            .map(|f| to_vec::<_, BigEndian>(f).unwrap())
+            .collect();
+        let all_feeds_bytes_refs: Vec<_> = all_feeds_bytes.iter().map(|f| f.as_ref()).collect();
+        let tree = MerkleTree::<Keccak160>::new(all_feeds_bytes_refs.as_slice()).unwrap();
+
+        let updates_bytes: Vec<_> = updates
+            .iter()
+            .map(|u| to_vec::<_, BigEndian>(u).unwrap())
+            .collect();
+        let updates_bytes_refs: Vec<&[u8]> = updates_bytes.iter().map(|u| u.as_ref()).collect();
+        let (order, mut proof) = tree.prove_multi(&updates_bytes_refs).unwrap();
+        let mut messages: Vec<_> = order
+            .into_iter()
+            .map(|i| updates_bytes[i].clone())
+            .collect();
+        tamper(&mut messages, &mut proof);
+
+        let accumulator_update_data = AccumulatorUpdateData::new(Proof::WormholeMerkleMulti {
+            vaa:      create_accumulator_vaa(&tree, false, default_emitter_addr(), EMITTER_CHAIN),
+            messages: messages.into_iter().map(PrefixedVec::from).collect(),
+            hashes:   proof.hashes.into(),
+            flags:    proof.flags.into(),
+        });
+
+        Binary::from(to_vec::<_, BigEndian>(&accumulator_update_data).unwrap())
+    }
+
 
     fn check_price_match(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, msg: &Message) {
         match msg {
@@ -1471,6 +1560,34 @@ mod test {
         check_price_match(&deps, &feed3);
     }
 
+    #[test]
+    fn test_accumulator_multiproof_update() {
+        let (mut deps, env) = setup_test();
+        config(&mut deps.storage)
+            .save(&default_config_info())
+            .unwrap();
+        let feed1 = create_dummy_price_feed_message(100);
+        let feed2 = create_dummy_price_feed_message(200);
+        let feed3 = create_dummy_price_feed_message(300);
+        let msg = create_accumulator_multiproof_message(
+            &[feed1, feed2, feed3],
+            &[feed1, feed3],
+            |_, _| {},
+        );
+        let info = mock_info("123", &[]);
+        let result = update_price_feeds(deps.as_mut(), env, info, &[msg]);
+
+        assert!(result.is_ok());
+        check_price_match(&deps, &feed1);
+        check_price_match(&deps, &feed3);
+        match feed2 {
+            Message::PriceFeedMessage(feed2) => assert!(price_feed_read_bucket(&deps.storage)
+                .load(&feed2.feed_id)
+                .is_err()),
+            _ => panic!("unexpected message type"),
+        }
+    }
+
     #[test]
     fn test_invalid_accumulator_update() {
         let (mut deps, env) = setup_test();
@@ -1572,6 +1689,43 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_invalid_multiproof() {
+        let feed1 = create_dummy_price_feed_message(100);
+        let feed2 = create_dummy_price_feed_message(200);
+        let feed3 = create_dummy_price_feed_message(300);
+        let feed2_bytes = to_vec::<_, BigEndian>(&feed2).unwrap();
+
+        let tampered_messages = [
+            // The proof is valid for feed1 and feed3, but not feed2.
+            create_accumulator_multiproof_message(
+                &[feed1, feed2, feed3],
+                &[feed1, feed3],
+                |messages, _| messages[0] = feed2_bytes,
+            ),
+            // A hash of the proof is corrupted.
+            create_accumulator_multiproof_message(
+                &[feed1, feed2, feed3],
+                &[feed1, feed3],
+                |_, proof| proof.hashes[0][0] ^= 1,
+            ),
+        ];
+
+        for msg in tampered_messages {
+            let (mut deps, env) = setup_test();
+            config(&mut deps.storage)
+                .save(&default_config_info())
+                .unwrap();
+            let info = mock_info("123", &[]);
+            let result = update_price_feeds(deps.as_mut(), env, info, &[msg]);
+            assert!(result.is_err());
+            assert_eq!(
+                result.unwrap_err(),
+                StdError::from(PythContractError::InvalidMerkleProof)
+            );
+        }
+    }
+
     #[test]
     fn test_invalid_message() {
         let (mut deps, env) = setup_test();